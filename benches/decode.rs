@@ -0,0 +1,207 @@
+//! Compares the ways of decoding a whole replay:
+//!
+//! - `stream`: collecting an `InputStream` over a `Cursor`
+//! - `reader`: `ReplayDeserializer::deserialize` over a `Cursor`
+//! - `slice`: `Replay::from_bytes`
+//! - `baseline`: the byte-at-a-time `read_exact` loop the crate decoded v2 streams with
+//!   before any of the above existed, kept here as a fixed point of reference
+//! - `output`: copying the inputs `slice` decoded into a new `Vec`, which is what building
+//!   the result costs without any decoding
+//!
+//! The ratios compare `slice` with `stream`, `reader` and `baseline`. `slice` and
+//! `baseline` come out about even: both spend most of their time on `output`, writing
+//! 32 bytes per input into freshly allocated memory, which no decoder can avoid while it
+//! returns a `Vec<InputCommand>`. Run with `cargo bench --bench decode`.
+
+use std::hint::black_box;
+use std::io::{Cursor, Read};
+use std::time::{Duration, Instant};
+
+use tcm::input::{
+    BugpointInput, Input, InputCommand, PlayerButton, RestartInput, RestartType, TpsInput,
+    VanillaInput,
+};
+use tcm::meta::MetaV2;
+use tcm::replay::{Replay, ReplayDeserializer};
+use tcm::stream::InputStream;
+
+/// Best time of a number of runs, which is the least disturbed by everything else going on.
+fn best_of(runs: usize, mut f: impl FnMut()) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn read_byte(reader: &mut impl Read) -> std::io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+/// The v2 decode loop as it was before the stream decoder, minus its panics.
+fn baseline(bytes: &[u8]) -> std::io::Result<Vec<InputCommand>> {
+    let invalid = || std::io::Error::from(std::io::ErrorKind::InvalidData);
+    let mut reader = Cursor::new(bytes);
+    reader.set_position(0x50);
+
+    let mut inputs = Vec::new();
+    let mut current_frame = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = read_byte(&mut reader)?;
+        current_frame |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+
+    let mut last_delta = 0u64;
+    loop {
+        let byte = match read_byte(&mut reader) {
+            Ok(byte) => byte,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let (blob, magic) = ((byte >> 6) & 0b11, (byte >> 5) & 1 != 0);
+
+        let input_data = byte & 0b11;
+        if input_data > 0 {
+            let button = PlayerButton::try_from(input_data).map_err(|_| invalid())?;
+            let (push, player2) = (byte & 0b100 != 0, byte & 0b1000 != 0);
+            inputs.push(InputCommand::new(
+                current_frame,
+                Input::Vanilla(VanillaInput {
+                    button,
+                    push,
+                    player2,
+                }),
+            ));
+            if byte & 0b10000 != 0 {
+                inputs.push(InputCommand::new(
+                    current_frame,
+                    Input::Vanilla(VanillaInput {
+                        button,
+                        push: !push,
+                        player2,
+                    }),
+                ));
+            }
+        } else {
+            let (custom, extra) = ((byte >> 2) & 0b11, byte & 0b10000 != 0);
+            let input = match (custom, extra) {
+                (3, true) => Input::Bugpoint(BugpointInput),
+                (3, false) => {
+                    let mut buf = [0u8; 4];
+                    reader.read_exact(&mut buf)?;
+                    Input::Tps(TpsInput {
+                        tps: f32::from_le_bytes(buf),
+                    })
+                }
+                (custom, extra) => {
+                    let mut new_seed = None;
+                    if extra {
+                        let mut buf = [0u8; 8];
+                        reader.read_exact(&mut buf)?;
+                        new_seed = Some(u64::from_le_bytes(buf));
+                    }
+                    Input::Restart(RestartInput {
+                        restart_type: RestartType::try_from(custom).map_err(|_| invalid())?,
+                        new_seed,
+                    })
+                }
+            };
+            let restart = matches!(input, Input::Restart(_));
+            inputs.push(InputCommand::new(current_frame, input));
+            if restart {
+                current_frame = 0;
+            }
+        }
+
+        let mut value = 0u64;
+        for (index, len) in [0, 1, 2, 4].into_iter().enumerate() {
+            if blob as usize == index && len > 0 {
+                let mut buf = [0u8; 8];
+                match reader.read_exact(&mut buf[..len]) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(inputs),
+                    Err(e) => return Err(e),
+                }
+                value = u64::from_le_bytes(buf);
+            }
+        }
+        let delta = if magic { last_delta } else { 0 } + value;
+        if blob > 0 && delta != 0 {
+            last_delta = delta;
+        }
+        current_frame += delta;
+    }
+    Ok(inputs)
+}
+
+fn bench(name: &str, bytes: &[u8], runs: usize) {
+    let stream = best_of(runs, || {
+        let stream = InputStream::<_, MetaV2>::new(Cursor::new(bytes)).unwrap();
+        black_box(stream.collect::<Result<Vec<_>, _>>().unwrap());
+    });
+    let reader = best_of(runs, || {
+        black_box(Replay::<MetaV2>::deserialize(&mut Cursor::new(bytes)).unwrap());
+    });
+    let slice = best_of(runs, || {
+        black_box(Replay::<MetaV2>::from_bytes(bytes).unwrap());
+    });
+    let baseline = best_of(runs, || {
+        black_box(baseline(bytes).unwrap());
+    });
+    let decoded = Replay::<MetaV2>::from_bytes(bytes).unwrap().inputs;
+    let output = best_of(runs, || {
+        let mut inputs = Vec::with_capacity(decoded.len());
+        inputs.extend_from_slice(&decoded);
+        black_box(inputs);
+    });
+
+    println!(
+        "{:<18} {:>8} bytes  stream {:>10.2?}  reader {:>10.2?}  baseline {:>10.2?}  \
+         slice {:>10.2?}  output {:>10.2?}  ({:.1}x / {:.1}x / {:.1}x)",
+        name,
+        bytes.len(),
+        stream,
+        reader,
+        baseline,
+        slice,
+        output,
+        stream.as_secs_f64() / slice.as_secs_f64(),
+        reader.as_secs_f64() / slice.as_secs_f64(),
+        baseline.as_secs_f64() / slice.as_secs_f64(),
+    );
+}
+
+fn main() {
+    let long = std::fs::read("examples/data/longv2.tcm").expect("Failed to read longv2.tcm");
+    let decoded = Replay::<MetaV2>::from_bytes(&long).unwrap().inputs;
+    assert_eq!(baseline(&long).unwrap(), decoded);
+    bench("longv2.tcm", &long, 200);
+
+    // A macro the size of the longest ones we see: longv2's attempts over and over.
+    let replay = Replay::<MetaV2>::from_bytes(&long).unwrap();
+    let last_frame = replay.inputs.last().map_or(0, |i| i.frame);
+    let restart = InputCommand::new(
+        last_frame,
+        Input::Restart(RestartInput {
+            restart_type: RestartType::Restart,
+            new_seed: None,
+        }),
+    );
+    let mut inputs = Vec::new();
+    for _ in 0..100 {
+        inputs.extend(replay.inputs.iter().cloned());
+        inputs.push(restart.clone());
+    }
+    let large = Replay::new(replay.meta.clone(), inputs).to_bytes().unwrap();
+    bench("longv2.tcm x100", &large, 20);
+}
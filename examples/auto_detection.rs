@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::BufReader;
-use tcm::{meta::Meta, DynamicReplay};
+use tcm::{meta::MetaInfo, DynamicReplay};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Get filename from command line or use default
@@ -20,7 +20,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let replay = DynamicReplay::from_reader(&mut reader)?;
 
     // Now you can use the replay directly without matching on enum variants!
-    println!("✓ Detected TCM v{} file", replay.meta.version_instance());
+    println!("✓ Detected TCM v{} file", replay.meta.version());
     println!("  TPS: {}", replay.meta.tps());
     println!("  Input count: {}", replay.inputs.len());
 
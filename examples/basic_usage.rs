@@ -1,14 +1,14 @@
 //! Basic usage example showing how to read and write TCM files.
 
 use std::fs::File;
-use std::io;
 use tcm::{
+    error::TcmResult,
     input::{Input, InputCommand, PlayerButton, VanillaInput},
-    meta::{Meta, MetaV2},
+    meta::{MetaInfo, MetaV2},
     replay::{Replay, ReplayDeserializer, ReplaySerializer},
 };
 
-fn main() -> io::Result<()> {
+fn main() -> TcmResult<()> {
     println!("TCM Basic Usage Example");
 
     // Read an existing TCM file (if available)
@@ -1,14 +1,14 @@
 //! Example showing how to convert between TCM format versions.
 
 use std::fs::File;
-use std::io;
 use tcm::{
+    error::TcmResult,
     input::{Input, InputCommand},
-    meta::{Meta, MetaV1, MetaV2},
+    meta::{MetaInfo, MetaV1, MetaV2},
     replay::{Replay, ReplayDeserializer, ReplaySerializer},
 };
 
-fn main() -> io::Result<()> {
+fn main() -> TcmResult<()> {
     println!("TCM Format Conversion Example");
 
     // Try to read a v1 format file
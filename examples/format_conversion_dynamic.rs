@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::BufReader;
-use tcm::{meta::Meta, replay::ReplaySerializer, DynamicReplay};
+use tcm::{meta::MetaInfo, replay::ReplaySerializer, DynamicReplay};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("TCM Format Conversion Example");
@@ -11,7 +11,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut reader = BufReader::new(input_file);
     let dynamic_replay = DynamicReplay::from_reader(&mut reader)?;
 
-    println!("Loaded: TCM v{}", dynamic_replay.meta.version_instance());
+    println!("Loaded: TCM v{}", dynamic_replay.meta.version());
     println!("Original TPS: {}", dynamic_replay.meta.tps());
     println!("Input count: {}", dynamic_replay.inputs.len());
 
@@ -30,10 +30,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut verify_reader = BufReader::new(verify_file);
     let verified_replay = DynamicReplay::from_reader(&mut verify_reader)?;
 
-    println!(
-        "Verification: TCM v{}",
-        verified_replay.meta.version_instance()
-    );
+    println!("Verification: TCM v{}", verified_replay.meta.version());
     println!("Verified TPS: {}", verified_replay.meta.tps());
     println!("Verified input count: {}", verified_replay.inputs.len());
     if let Some(seed) = verified_replay.meta.rng_seed() {
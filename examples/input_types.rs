@@ -1,8 +1,8 @@
 //! Example demonstrating different types of inputs in TCM replays.
 
 use std::fs::File;
-use std::io;
 use tcm::{
+    error::TcmResult,
     input::{
         BugpointInput, Input, InputCommand, PlayerButton, RestartInput, RestartType, TpsInput,
         VanillaInput,
@@ -11,7 +11,7 @@ use tcm::{
     replay::{Replay, ReplaySerializer},
 };
 
-fn main() -> io::Result<()> {
+fn main() -> TcmResult<()> {
     println!("TCM Input Types Example");
 
     let meta = MetaV2::new(240.0, 0, Some(98765));
@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::BufReader;
-use tcm::{meta::Meta, replay::ReplaySerializer, DynamicReplay};
+use tcm::{meta::MetaInfo, replay::ReplaySerializer, DynamicReplay};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Testing Lossless Conversion");
@@ -13,7 +13,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!(
         "   Original: TCM v{}, TPS: {}",
-        v1_replay.meta.version_instance(),
+        v1_replay.meta.version(),
         v1_replay.meta.tps()
     );
 
@@ -36,7 +36,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!(
         "   Loaded: TCM v{}, TPS: {}",
-        loaded_v2.meta.version_instance(),
+        loaded_v2.meta.version(),
         loaded_v2.meta.tps()
     );
     println!("   RNG seed: {:?}", loaded_v2.meta.rng_seed());
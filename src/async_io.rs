@@ -0,0 +1,214 @@
+//! Async reading and writing of replays over tokio's `AsyncRead` and `AsyncWrite`.
+//!
+//! Bytes are moved asynchronously in chunks while the decoding and encoding runs through
+//! the same codec as the blocking API, so both always agree on the format.
+
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Chain};
+
+use crate::{
+    codec::Codec,
+    error::TcmResult,
+    input::InputCommand,
+    meta::MetaFormat,
+    options::ReadOptions,
+    replay::{
+        detect_version, map_eof, CountingReader, Replay, HEADER_SIZE, PREFIX_SIZE, TCBOT_HEADER,
+    },
+    stream::StreamCore,
+    DynamicReplay,
+};
+
+/// Size of the chunks read from and written to the async side.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Reads from `source` until `buffer` holds at least `len` bytes or the source ends.
+async fn fill<R: AsyncRead + Unpin>(
+    source: &mut R,
+    buffer: &mut VecDeque<u8>,
+    eof: &mut bool,
+    len: usize,
+) -> TcmResult<()> {
+    let mut chunk = [0u8; CHUNK_SIZE];
+    while !*eof && buffer.len() < len {
+        let read = source.read(&mut chunk).await?;
+        if read == 0 {
+            *eof = true;
+        }
+        buffer.extend(&chunk[..read]);
+    }
+    Ok(())
+}
+
+/// The async counterpart of [`InputStream`](crate::stream::InputStream), decoding inputs one at a time.
+///
+/// # Example
+/// ```no_run
+/// # async fn run(upload: impl tokio::io::AsyncRead + Unpin) -> tcm::TcmResult<()> {
+/// use tcm::async_io::AsyncInputStream;
+/// use tcm::meta::MetaV2;
+///
+/// let mut stream = AsyncInputStream::<_, MetaV2>::new(upload).await?;
+/// while let Some(input) = stream.next().await {
+///     println!("{:?}", input?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncInputStream<R: AsyncRead + Unpin, M: MetaFormat> {
+    source: R,
+    eof: bool,
+    stream: StreamCore<CountingReader<VecDeque<u8>>, M>,
+}
+
+impl<R: AsyncRead + Unpin, M: MetaFormat> AsyncInputStream<R, M> {
+    /// Reads the header and metadata and prepares to decode inputs.
+    pub async fn new(source: R) -> TcmResult<Self> {
+        Self::with_options(source, &ReadOptions::default()).await
+    }
+
+    /// Like [`AsyncInputStream::new`], applying the given [`ReadOptions`] to the whole
+    /// stream.
+    pub async fn with_options(mut source: R, options: &ReadOptions) -> TcmResult<Self> {
+        let mut buffer = VecDeque::new();
+        let mut eof = false;
+        fill(&mut source, &mut buffer, &mut eof, HEADER_SIZE + M::size()).await?;
+
+        Ok(Self {
+            source,
+            eof,
+            stream: StreamCore::open(CountingReader::new(buffer), options, false)?,
+        })
+    }
+
+    /// The replay metadata.
+    pub fn meta(&self) -> &M {
+        self.stream.meta()
+    }
+
+    /// Consumes the stream, returning the replay metadata.
+    pub fn into_meta(self) -> M {
+        self.stream.into_meta()
+    }
+
+    /// Decodes the next input, or returns `None` at the end of the stream or after an
+    /// error.
+    pub async fn next(&mut self) -> Option<TcmResult<InputCommand>> {
+        let result = self.next_input().await;
+        self.stream.yield_item(result)
+    }
+
+    async fn next_input(&mut self) -> TcmResult<Option<InputCommand>> {
+        loop {
+            if let Some(input) = self.stream.pop_pending() {
+                return Ok(Some(input));
+            }
+            // With the whole unit buffered, the decoder only sees the end of the data
+            // where the source really ends.
+            let len = self.stream.unit_len();
+            let buffer = self.stream.reader_mut().get_mut();
+            fill(&mut self.source, buffer, &mut self.eof, len).await?;
+            if !self.stream.advance()? {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl<M: MetaFormat> Replay<M> {
+    /// Parse a replay from an async reader.
+    pub async fn deserialize_async<R: AsyncRead + Unpin>(reader: &mut R) -> TcmResult<Self> {
+        Self::deserialize_async_with_options(reader, &ReadOptions::default()).await
+    }
+
+    /// Parse a replay from an async reader with the given [`ReadOptions`].
+    pub async fn deserialize_async_with_options<R: AsyncRead + Unpin>(
+        reader: &mut R,
+        options: &ReadOptions,
+    ) -> TcmResult<Self> {
+        let mut stream = AsyncInputStream::<_, M>::with_options(reader, options).await?;
+        let mut inputs = Vec::new();
+        while let Some(input) = stream.next().await {
+            inputs.push(input?);
+        }
+
+        Ok(Replay {
+            meta: stream.into_meta(),
+            inputs,
+        })
+    }
+
+    /// Serialize the replay to an async writer.
+    ///
+    /// Writes the same bytes as
+    /// [`ReplaySerializer::serialize`](crate::replay::ReplaySerializer::serialize).
+    pub async fn serialize_async<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> TcmResult<()> {
+        let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+        buffer.extend_from_slice(&TCBOT_HEADER);
+        buffer.extend_from_slice(&self.meta.to_bytes());
+
+        let codec = Codec::for_version(self.meta.version())?;
+        let mut encoder = codec.encoder(&mut buffer, self.inputs.len())?;
+        for input in &self.inputs {
+            encoder.push(&mut buffer, input)?;
+            drain_full(writer, &mut buffer).await?;
+        }
+        encoder.finish(&mut buffer)?;
+
+        writer.write_all(&buffer).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Writes out the buffer once it has grown to a full chunk.
+async fn drain_full<W: AsyncWrite + Unpin>(writer: &mut W, buffer: &mut Vec<u8>) -> TcmResult<()> {
+    if buffer.len() >= CHUNK_SIZE {
+        writer.write_all(buffer).await?;
+        buffer.clear();
+    }
+    Ok(())
+}
+
+impl DynamicReplay {
+    /// Parse a replay from an async reader, automatically detecting the format version.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn run(mut upload: impl tokio::io::AsyncRead + Unpin) -> tcm::TcmResult<()> {
+    /// use tcm::DynamicReplay;
+    ///
+    /// let replay = DynamicReplay::from_async_reader(&mut upload).await?;
+    /// println!("{} inputs", replay.inputs.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn from_async_reader<R: AsyncRead + Unpin>(reader: &mut R) -> TcmResult<Self> {
+        Self::deserialize_async(reader).await
+    }
+
+    /// Parse a replay from an async reader with the given [`ReadOptions`], automatically
+    /// detecting the format version.
+    pub async fn from_async_reader_with_options<R: AsyncRead + Unpin>(
+        reader: &mut R,
+        options: &ReadOptions,
+    ) -> TcmResult<Self> {
+        Self::deserialize_async_with_options(reader, options).await
+    }
+}
+
+/// The async counterpart of [`detect_version`], buffering the header and metadata to
+/// find the format version of a replay.
+pub async fn detect_version_async<R: AsyncRead + Unpin>(
+    mut reader: R,
+) -> TcmResult<(u8, Chain<Cursor<[u8; PREFIX_SIZE]>, R>)> {
+    let mut prefix = [0u8; PREFIX_SIZE];
+    reader
+        .read_exact(&mut prefix)
+        .await
+        .map_err(|e| map_eof(e, "header and metadata"))?;
+    let (version, _) = detect_version(&prefix[..])?;
+    Ok((version, Cursor::new(prefix).chain(reader)))
+}
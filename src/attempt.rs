@@ -0,0 +1,95 @@
+//! Splitting replays into attempts.
+//!
+//! Every restart input resets the frame counter, starting a new attempt. Attempts are
+//! counted from 0, the way [`SeekIndex`](crate::index::SeekIndex) counts them, and end
+//! with the restart input that starts the next one.
+
+use std::ops::Range;
+
+use crate::{
+    input::{Input, InputCommand, RestartInput, RestartType},
+    meta::MetaFormat,
+    replay::Replay,
+    Frame,
+};
+
+/// The inputs of one attempt of a replay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attempt<'a> {
+    /// Number of restarts before the attempt
+    pub index: usize,
+    /// How the attempt was started, or `None` for the first attempt
+    pub restart_type: Option<RestartType>,
+    /// The seed of the restart starting the attempt, falling back to the seed of the
+    /// metadata
+    pub seed: Option<u64>,
+    /// Frames from the start of the attempt through its latest input
+    pub frames: Range<Frame>,
+    /// Index in the replay of the first input of the attempt
+    pub input_offset: usize,
+    /// The inputs of the attempt, ending with the restart that ends it if there is one
+    pub inputs: &'a [InputCommand],
+}
+
+impl<M: MetaFormat> Replay<M> {
+    /// Splits the inputs into attempts at every restart.
+    ///
+    /// A replay without inputs has no attempts; a replay ending with a restart ends with
+    /// an empty attempt.
+    ///
+    /// # Example
+    /// ```
+    /// use tcm::input::{PlayerButton::Jump, RestartType};
+    /// use tcm::meta::MetaV2;
+    /// use tcm::ReplayBuilder;
+    ///
+    /// let replay = ReplayBuilder::new(MetaV2::new(240.0, 0, Some(7)))
+    ///     .hold(Jump, 10..20)
+    ///     .at(30).restart(RestartType::Death).with_seed(99)
+    ///     .hold(Jump, 5..15)
+    ///     .build()
+    ///     .unwrap();
+    /// let attempts = replay.attempts();
+    /// assert_eq!(attempts.len(), 2);
+    /// assert_eq!((attempts[0].seed, attempts[0].frames.clone()), (Some(7), 0..31));
+    /// assert_eq!(attempts[1].restart_type, Some(RestartType::Death));
+    /// assert_eq!((attempts[1].seed, attempts[1].frames.clone()), (Some(99), 0..16));
+    /// ```
+    pub fn attempts(&self) -> Vec<Attempt<'_>> {
+        let mut attempts = Vec::new();
+        if self.inputs.is_empty() {
+            return attempts;
+        }
+
+        let mut start = 0;
+        let mut started_by: Option<&RestartInput> = None;
+        loop {
+            let end = self.inputs[start..]
+                .iter()
+                .position(|input| matches!(input.input, Input::Restart(_)))
+                .map_or(self.inputs.len(), |at| start + at + 1);
+            let inputs = &self.inputs[start..end];
+
+            attempts.push(Attempt {
+                index: attempts.len(),
+                restart_type: started_by.map(|restart| restart.restart_type),
+                seed: started_by
+                    .and_then(|restart| restart.new_seed)
+                    .or_else(|| self.meta.rng_seed()),
+                frames: 0..inputs
+                    .iter()
+                    .map(|input| input.frame.saturating_add(1))
+                    .max()
+                    .unwrap_or(0),
+                input_offset: start,
+                inputs,
+            });
+
+            match inputs.last().map(|input| &input.input) {
+                Some(Input::Restart(restart)) => started_by = Some(restart),
+                _ => return attempts,
+            }
+            start = end;
+        }
+    }
+}
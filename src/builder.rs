@@ -0,0 +1,293 @@
+//! Fluent construction of replays.
+
+use std::io;
+use std::ops::Range;
+
+use crate::{
+    codec::Codec,
+    error::{TcmError, TcmResult},
+    input::{
+        BugpointInput, Input, InputCommand, PlayerButton, RestartInput, RestartType, TpsInput,
+        VanillaInput,
+    },
+    meta::{check_tps, MetaFormat, MetaV1, MetaV2},
+    replay::Replay,
+    Frame,
+};
+
+/// Builds a replay input by input, checking on [`build`](ReplayBuilder::build) that it
+/// makes sense as gameplay and that the target format version can represent it.
+///
+/// Inputs go to the frame set with [`at`](ReplayBuilder::at) and to the player picked
+/// with [`player1`](ReplayBuilder::player1) or [`player2`](ReplayBuilder::player2), both
+/// of which stay in effect until changed. A restart starts a new attempt with its frames
+/// counted from 0 again. Within an attempt inputs are added in frame order, and presses
+/// and releases of a button take turns.
+///
+/// # Example
+/// ```
+/// use tcm::builder::ReplayBuilder;
+/// use tcm::input::{PlayerButton::Jump, RestartType};
+///
+/// # fn main() -> tcm::TcmResult<()> {
+/// let replay = ReplayBuilder::v2(240.0)
+///     .hold(Jump, 100..150)
+///     .at(200).press(Jump)
+///     .player2().hold(Jump, 210..220)
+///     .player1().at(230).release(Jump)
+///     .at(400).restart(RestartType::Death).with_seed(1234)
+///     .tps(480.0)
+///     .at(50).bugpoint()
+///     .build()?;
+/// assert_eq!(replay.inputs.len(), 9);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReplayBuilder<M: MetaFormat> {
+    meta: M,
+    inputs: Vec<InputCommand>,
+    frame: Frame,
+    player2: bool,
+    /// Frame of the latest input of the current attempt
+    previous: Frame,
+    /// Which buttons are held, for player 1 and 2
+    held: [[bool; 3]; 2],
+    error: Option<TcmError>,
+}
+
+impl ReplayBuilder<MetaV1> {
+    /// Starts a v1 replay running at `tps`.
+    pub fn v1(tps: f32) -> Self {
+        Self::new(MetaV1::new(tps, 0))
+    }
+}
+
+impl ReplayBuilder<MetaV2> {
+    /// Starts a v2 replay running at `tps`, without a seed override.
+    pub fn v2(tps: f32) -> Self {
+        Self::new(MetaV2::new(tps, 0, None))
+    }
+}
+
+impl<M: MetaFormat> ReplayBuilder<M> {
+    /// Starts a replay with the given metadata.
+    pub fn new(meta: M) -> Self {
+        Self {
+            meta,
+            inputs: Vec::new(),
+            frame: 0,
+            player2: false,
+            previous: 0,
+            held: [[false; 3]; 2],
+            error: None,
+        }
+    }
+
+    /// Moves to `frame` of the current attempt.
+    pub fn at(mut self, frame: Frame) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// Sends the following button inputs to player 1.
+    pub fn player1(mut self) -> Self {
+        self.player2 = false;
+        self
+    }
+
+    /// Sends the following button inputs to player 2.
+    pub fn player2(mut self) -> Self {
+        self.player2 = true;
+        self
+    }
+
+    /// Presses `button` on the current frame.
+    pub fn press(self, button: PlayerButton) -> Self {
+        let frame = self.frame;
+        self.button(frame, button, true)
+    }
+
+    /// Releases `button` on the current frame.
+    pub fn release(self, button: PlayerButton) -> Self {
+        let frame = self.frame;
+        self.button(frame, button, false)
+    }
+
+    /// Presses `button` at the start of `frames` and releases it at the end, leaving the
+    /// builder at the release frame.
+    pub fn hold(mut self, button: PlayerButton, frames: Range<Frame>) -> Self {
+        if frames.is_empty() {
+            let message = format!("Empty hold of {:?} over frames {:?}", button, frames);
+            return self.fail(message);
+        }
+        self.frame = frames.end;
+        self.button(frames.start, button, true)
+            .button(frames.end, button, false)
+    }
+
+    /// Restarts on the current frame, starting a new attempt at frame 0.
+    pub fn restart(mut self, restart_type: RestartType) -> Self {
+        let frame = self.frame;
+        self.frame = 0;
+        self.push(
+            frame,
+            Input::Restart(RestartInput {
+                restart_type,
+                new_seed: None,
+            }),
+        )
+    }
+
+    /// Sets the seed of the restart added just before.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        match self.inputs.last_mut() {
+            Some(InputCommand {
+                input: Input::Restart(restart),
+                ..
+            }) => {
+                restart.new_seed = Some(seed);
+                self
+            }
+            _ => self.fail("A seed can only follow a restart"),
+        }
+    }
+
+    /// Changes the TPS from the current frame on.
+    pub fn tps(self, tps: f32) -> Self {
+        let frame = self.frame;
+        self.push(frame, Input::Tps(TpsInput { tps }))
+    }
+
+    /// Marks a bugpoint on the current frame.
+    pub fn bugpoint(self) -> Self {
+        let frame = self.frame;
+        self.push(frame, Input::Bugpoint(BugpointInput))
+    }
+
+    /// Finishes the replay.
+    ///
+    /// Fails with the first misuse of the builder, such as an input going back in frames
+    /// within its attempt, releasing a button that isn't held or pressing one that is.
+    /// Fails as well if the target format version can't represent an input: v1 has no
+    /// TPS changes, bugpoints or restart seeds and its frames have to fit in 32 bits,
+    /// while v2 frame deltas have to fit the encoding.
+    pub fn build(self) -> TcmResult<Replay<M>> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        check_tps(self.meta.tps())?;
+
+        let version = self.meta.version();
+        let codec = Codec::for_version(version)?;
+        let v1 = matches!(codec, Codec::V1);
+        let unsupported = |index: usize, input: &InputCommand, reason: &str| {
+            TcmError::invalid_input(format!(
+                "Input {} at frame {}: {}",
+                index, input.frame, reason
+            ))
+        };
+        // Frame deltas, and whatever a registered codec refuses, only show up when
+        // encoding; the encoded bytes aren't kept.
+        let mut encoder = match codec {
+            Codec::V1 => None,
+            codec => Some(codec.encoder(&mut io::sink(), self.inputs.len())?),
+        };
+        for (index, input) in self.inputs.iter().enumerate() {
+            let reason = match &input.input {
+                Input::Tps(tps) if check_tps(tps.tps).is_err() => {
+                    Some(format!("invalid TPS {}", tps.tps))
+                }
+                _ if !v1 => None,
+                Input::Tps(_) => Some("v1 has no TPS changes".to_string()),
+                Input::Bugpoint(_) => Some("v1 has no bugpoints".to_string()),
+                Input::Restart(RestartInput {
+                    new_seed: Some(_), ..
+                }) => Some("v1 restarts have no seed".to_string()),
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                return Err(unsupported(index, input, &reason));
+            }
+            if v1 && input.frame > u32::MAX as Frame {
+                return Err(TcmError::FrameOutOfRange {
+                    value: input.frame,
+                    field: "v1 input frame".to_string(),
+                });
+            }
+            if let Some(encoder) = &mut encoder {
+                // Frame values out of range keep their own error.
+                encoder.push(&mut io::sink(), input).map_err(|e| match e {
+                    TcmError::InvalidInput { message } => unsupported(index, input, &message),
+                    e => e,
+                })?;
+            }
+        }
+        if let Some(encoder) = encoder {
+            encoder.finish(&mut io::sink())?;
+        }
+
+        Ok(Replay::new(self.meta, self.inputs))
+    }
+
+    fn button(self, frame: Frame, button: PlayerButton, push: bool) -> Self {
+        let player2 = self.player2;
+        self.push(
+            frame,
+            Input::Vanilla(VanillaInput {
+                button,
+                push,
+                player2,
+            }),
+        )
+    }
+
+    fn push(mut self, frame: Frame, input: Input) -> Self {
+        let index = self.inputs.len();
+        let mut misuse = None;
+        if frame < self.previous {
+            misuse = Some(format!("frame goes back from {}", self.previous));
+        }
+        self.previous = self.previous.max(frame);
+        match &input {
+            Input::Vanilla(VanillaInput {
+                button,
+                push,
+                player2,
+            }) => {
+                let held = &mut self.held[*player2 as usize][*button as usize - 1];
+                if *held == *push {
+                    let state = if *push {
+                        "is already held"
+                    } else {
+                        "isn't held"
+                    };
+                    let reason = format!("{} {}", describe(*button, *player2), state);
+                    misuse.get_or_insert(reason);
+                }
+                *held = *push;
+            }
+            Input::Restart(_) => {
+                self.previous = 0;
+                self.held = [[false; 3]; 2];
+            }
+            _ => {}
+        }
+        if let Some(reason) = misuse {
+            self = self.fail(format!("Input {} at frame {}: {}", index, frame, reason));
+        }
+        self.inputs.push(InputCommand::new(frame, input));
+        self
+    }
+
+    fn fail(mut self, message: impl Into<String>) -> Self {
+        self.error
+            .get_or_insert_with(|| TcmError::invalid_input(message));
+        self
+    }
+}
+
+fn describe(button: PlayerButton, player2: bool) -> String {
+    let player = if player2 { 2 } else { 1 };
+    format!("{:?} of player {}", button, player)
+}
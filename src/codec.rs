@@ -0,0 +1,549 @@
+//! The input stream encodings of the format versions.
+//!
+//! Versions 1 and 2 are built in. Further versions, such as experimental or test-only
+//! variants, plug in by implementing [`InputCodec`] and calling [`register`]; reading a
+//! [`DynamicReplay`](crate::DynamicReplay), streaming its inputs, serializing a replay
+//! and recording one with an [`InputWriter`](crate::writer::InputWriter) then pick them
+//! up from the version byte like the built-in ones.
+//!
+//! A codec decodes its stream as the bytes come in, handing every input to an
+//! [`InputSink`] which applies the [`ReadOptions`] of the read on the spot, and encodes
+//! it an input at a time. Its metadata is read by [`InputCodec::read_meta`] into an
+//! [`AnyMeta::Custom`], in the v2 layout unless the codec defines its own.
+//!
+//! # The registry
+//!
+//! Registered codecs live in a single registry shared by the whole process: a version
+//! registered on one thread is read and written by every thread from then on, until it
+//! is unregistered. Lookups take a read lock, so registering is best done once at
+//! startup rather than around each read. As version bytes are a process-wide namespace,
+//! libraries should leave registering to the application, and tests running in parallel
+//! should each register a version of their own.
+//!
+//! # Example
+//! ```
+//! use tcm::codec::{self, InputCodec, InputDecoder, InputEncoder, V2Codec};
+//! use tcm::meta::{AnyMeta, CustomMeta, MetaV2};
+//! use tcm::DynamicReplay;
+//!
+//! /// The v2 encoding under an experimental version number.
+//! struct Experimental;
+//!
+//! impl InputCodec for Experimental {
+//!     fn version(&self) -> u8 {
+//!         0x82
+//!     }
+//!
+//!     fn encoder(&self) -> Box<dyn InputEncoder> {
+//!         V2Codec.encoder()
+//!     }
+//!
+//!     fn decoder(&self) -> Box<dyn InputDecoder> {
+//!         V2Codec.decoder()
+//!     }
+//! }
+//!
+//! codec::register(Experimental)?;
+//! let meta = AnyMeta::Custom(CustomMeta::v2(0x82, MetaV2::new(240.0, 0, None)));
+//! let bytes = DynamicReplay::new(meta, Vec::new()).to_bytes()?;
+//! assert_eq!(DynamicReplay::from_bytes(&bytes)?.meta.version(), 0x82);
+//! # use tcm::meta::MetaInfo;
+//! # Ok::<(), tcm::TcmError>(())
+//! ```
+
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    error::{TcmError, TcmResult},
+    input::InputCommand,
+    meta::{AnyMeta, CustomMeta, MetaFormat, MetaInfo, MetaV1, MetaV2},
+    options::{LimitTracker, ReadOptions},
+    replay::{v1, v2, write_var_u32, CountingReader, SliceReader},
+    stream::{StreamCore, StreamState},
+};
+
+/// Encodes and decodes the input stream of one format version.
+///
+/// The input stream is everything following the metadata block.
+pub trait InputCodec: Send + Sync {
+    /// The format version byte this codec handles.
+    fn version(&self) -> u8;
+
+    /// Reads a metadata block of the version, 0x40 bytes starting with the version byte.
+    /// Strict reads ask for anything outside the canonical layout to be refused.
+    ///
+    /// The default reads the v2 layout, see [`CustomMeta::read_v2`].
+    fn read_meta(&self, bytes: &[u8], strict: bool) -> TcmResult<CustomMeta> {
+        CustomMeta::read_v2(bytes, strict)
+    }
+
+    /// Starts encoding an input stream.
+    fn encoder(&self) -> Box<dyn InputEncoder>;
+
+    /// Starts decoding an input stream.
+    fn decoder(&self) -> Box<dyn InputDecoder>;
+
+    /// Appends the encoding of `inputs` to `out`.
+    fn encode(&self, inputs: &[InputCommand], out: &mut Vec<u8>) -> TcmResult<()> {
+        let mut encoder = self.encoder();
+        for input in inputs {
+            encoder.push(input, out)?;
+        }
+        encoder.finish(out)
+    }
+
+    /// Decodes a whole input stream held in memory, applying `options` to the inputs as
+    /// they are decoded.
+    fn decode(&self, bytes: &[u8], options: &ReadOptions) -> TcmResult<Vec<InputCommand>> {
+        let mut inputs = Vec::new();
+        let mut limits = LimitTracker::new(&options.limits);
+        limits.check_bytes(bytes.len() as u64)?;
+        let mut sink = InputSink::new(&mut inputs, &mut limits, options.strict, usize::MAX, 0);
+        let used = self.decoder().decode(bytes, true, &mut sink)?;
+        if options.strict && used < bytes.len() {
+            return Err(TcmError::TrailingData {
+                offset: used as u64,
+            });
+        }
+        Ok(inputs)
+    }
+}
+
+/// Encodes an input stream an input at a time, see [`InputCodec::encoder`].
+pub trait InputEncoder: Send + Sync {
+    /// Encodes the next input, appending whatever part of the stream is ready to `out`.
+    ///
+    /// An encoder may hold bytes back until later inputs arrive, like v2 does with the
+    /// action byte announcing the delta to the next input.
+    fn push(&mut self, input: &InputCommand, out: &mut Vec<u8>) -> TcmResult<()>;
+
+    /// Appends the rest of the stream to `out` once every input has been pushed.
+    fn finish(&mut self, out: &mut Vec<u8>) -> TcmResult<()>;
+}
+
+/// Decodes an input stream as its bytes come in, see [`InputCodec::decoder`].
+pub trait InputDecoder: Send + Sync {
+    /// Decodes inputs from the start of `bytes` into `inputs`, returning the number of
+    /// bytes used.
+    ///
+    /// Bytes left unused are passed in again on the next call, followed by more, so a
+    /// decoder stops at an input cut short rather than keeping its bytes. `at_end` is set
+    /// once no more bytes follow; whatever is left unused then is trailing data, which
+    /// strict reads refuse.
+    ///
+    /// A decoder returns once [`InputSink::is_full`] and passes on the errors of
+    /// [`InputSink::push`]. Byte offsets in its own errors count from the start of the
+    /// input stream.
+    fn decode(&mut self, bytes: &[u8], at_end: bool, inputs: &mut InputSink) -> TcmResult<usize>;
+}
+
+/// Takes the inputs an [`InputDecoder`] decodes, checking each against the
+/// [`ReadOptions`] of the read.
+pub struct InputSink<'a> {
+    out: &'a mut Vec<InputCommand>,
+    limits: &'a mut LimitTracker,
+    strict: bool,
+    until: usize,
+    /// File offset of the bytes passed to the decoder
+    offset: u64,
+}
+
+impl<'a> InputSink<'a> {
+    pub(crate) fn new(
+        out: &'a mut Vec<InputCommand>,
+        limits: &'a mut LimitTracker,
+        strict: bool,
+        until: usize,
+        offset: u64,
+    ) -> Self {
+        Self {
+            out,
+            limits,
+            strict,
+            until,
+            offset,
+        }
+    }
+
+    /// Adds a decoded input, failing with [`TcmError::LimitExceeded`] if it goes past a
+    /// resource limit; strict reads also refuse invalid TPS values.
+    pub fn push(&mut self, input: InputCommand) -> TcmResult<()> {
+        self.limits.add_inputs(1)?;
+        self.limits.check_contents(&input, self.strict)?;
+        self.out.push(input);
+        Ok(())
+    }
+
+    /// Whether the read is strict, for the checks a codec makes on its own encoding.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Whether the reader has all the inputs it asked for so far.
+    pub fn is_full(&self) -> bool {
+        self.out.len() >= self.until
+    }
+}
+
+/// The built-in v1 encoding: an input count, absolute frames and an end of macro marker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct V1Codec;
+
+/// The built-in v2 encoding: delta-encoded frames with swift pairs, TPS changes and
+/// seeded restarts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct V2Codec;
+
+impl InputCodec for V1Codec {
+    fn version(&self) -> u8 {
+        MetaV1::VERSION
+    }
+
+    /// The input count comes first, so the stream is held back until it is finished.
+    fn encoder(&self) -> Box<dyn InputEncoder> {
+        Box::new(V1Encoder::default())
+    }
+
+    fn decoder(&self) -> Box<dyn InputDecoder> {
+        Box::new(BuiltinDecoder::new(StreamState::V1Start))
+    }
+
+    fn encode(&self, inputs: &[InputCommand], out: &mut Vec<u8>) -> TcmResult<()> {
+        Codec::V1.write_inputs(out, inputs)
+    }
+
+    fn decode(&self, bytes: &[u8], options: &ReadOptions) -> TcmResult<Vec<InputCommand>> {
+        decode_builtin(StreamState::V1Start, bytes, options)
+    }
+}
+
+impl InputCodec for V2Codec {
+    fn version(&self) -> u8 {
+        MetaV2::VERSION
+    }
+
+    fn encoder(&self) -> Box<dyn InputEncoder> {
+        Box::new(V2Encoder::default())
+    }
+
+    fn decoder(&self) -> Box<dyn InputDecoder> {
+        Box::new(BuiltinDecoder::new(StreamState::V2Start))
+    }
+
+    fn encode(&self, inputs: &[InputCommand], out: &mut Vec<u8>) -> TcmResult<()> {
+        Codec::V2.write_inputs(out, inputs)
+    }
+
+    fn decode(&self, bytes: &[u8], options: &ReadOptions) -> TcmResult<Vec<InputCommand>> {
+        decode_builtin(StreamState::V2Start, bytes, options)
+    }
+}
+
+#[derive(Default)]
+struct V1Encoder {
+    count: u64,
+    body: Vec<u8>,
+}
+
+impl InputEncoder for V1Encoder {
+    fn push(&mut self, input: &InputCommand, _out: &mut Vec<u8>) -> TcmResult<()> {
+        v1::write_input(&mut self.body, input)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> TcmResult<()> {
+        write_var_u32(out, self.count, "v1 input count")?;
+        out.append(&mut self.body);
+        out.push(v1::EOM);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct V2Encoder(v2::Encoder);
+
+impl InputEncoder for V2Encoder {
+    fn push(&mut self, input: &InputCommand, out: &mut Vec<u8>) -> TcmResult<()> {
+        self.0.push(out, input)
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> TcmResult<()> {
+        self.0.finish(out)
+    }
+}
+
+/// Runs the crate's own stream decoder over the bytes passed in, a unit at a time so
+/// that it only sees the end of the bytes where the stream really ends.
+struct BuiltinDecoder {
+    /// The decoders never look at the metadata, it only travels along with the stream
+    /// state between calls.
+    parts: Option<(MetaV2, StreamState)>,
+}
+
+impl BuiltinDecoder {
+    fn new(state: StreamState) -> Self {
+        Self {
+            parts: Some((MetaV2::new_empty(1.0), state)),
+        }
+    }
+}
+
+impl InputDecoder for BuiltinDecoder {
+    fn decode(&mut self, bytes: &[u8], at_end: bool, inputs: &mut InputSink) -> TcmResult<usize> {
+        // Only empty while a call is running, `parts` is put back before returning.
+        let Some((meta, state)) = self.parts.take() else {
+            return Ok(0);
+        };
+        let mut reader = CountingReader::new(bytes);
+        reader.offset = inputs.offset;
+        let options = ReadOptions {
+            strict: inputs.strict,
+            ..ReadOptions::default()
+        };
+        let mut stream = StreamCore::start(reader, meta, state, &options, false);
+
+        std::mem::swap(stream.limits_mut(), inputs.limits);
+        let mut result = Ok(());
+        while !inputs.is_full() {
+            let used = (stream.offset() - inputs.offset) as usize;
+            if !at_end && bytes.len() - used < stream.unit_len() {
+                break;
+            }
+            match stream.decode(inputs.out, 0) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        std::mem::swap(stream.limits_mut(), inputs.limits);
+
+        let used = (stream.offset() - inputs.offset) as usize;
+        self.parts = Some(stream.into_parts());
+        result.map(|()| used)
+    }
+}
+
+fn decode_builtin(
+    state: StreamState,
+    bytes: &[u8],
+    options: &ReadOptions,
+) -> TcmResult<Vec<InputCommand>> {
+    let meta = MetaV2::new_empty(1.0);
+    let mut stream = StreamCore::start(SliceReader::new(bytes), meta, state, options, false);
+    let mut inputs = Vec::new();
+    stream.read_to_end(&mut inputs)?;
+    Ok(inputs)
+}
+
+/// A built-in format version, as the [`MetaFormat`] implementation of its metadata
+/// describes it.
+struct Version {
+    version: Option<u8>,
+    codec: fn() -> Option<Arc<dyn InputCodec>>,
+    /// The code path the crate takes for the inputs of the version
+    encoding: Codec,
+    read_meta: fn(&[u8]) -> TcmResult<AnyMeta>,
+    validate_meta: fn(&[u8]) -> TcmResult<()>,
+}
+
+impl Version {
+    const fn of<M: MetaFormat + Into<AnyMeta>>(encoding: Codec) -> Self {
+        Self {
+            version: M::FORMAT_VERSION,
+            codec: M::codec,
+            encoding,
+            read_meta: read_meta_as::<M>,
+            validate_meta: M::validate_bytes,
+        }
+    }
+}
+
+fn read_meta_as<M: MetaFormat + Into<AnyMeta>>(bytes: &[u8]) -> TcmResult<AnyMeta> {
+    M::from_bytes(bytes).map(Into::into)
+}
+
+/// Every built-in format version.
+///
+/// A new version implements [`MetaFormat`] for its metadata and adds a line here; a
+/// version reusing an existing input encoding needs nothing else.
+static VERSIONS: [Version; 2] = [
+    Version::of::<MetaV1>(Codec::V1),
+    Version::of::<MetaV2>(Codec::V2),
+];
+
+fn builtin(version: u8) -> Option<&'static Version> {
+    VERSIONS.iter().find(|v| v.version == Some(version))
+}
+
+/// Codecs registered at runtime, next to the built-in ones, for the whole process; see
+/// the [module documentation](self#the-registry).
+static REGISTRY: RwLock<Vec<Arc<dyn InputCodec>>> = RwLock::new(Vec::new());
+
+/// Registers the codec of a format version that isn't built in.
+///
+/// The codec is used for the version by every thread until it is unregistered, see the
+/// [module documentation](self#the-registry). Fails with [`TcmError::VersionTaken`] if
+/// the version is built in or already has a codec.
+pub fn register(codec: impl InputCodec + 'static) -> TcmResult<()> {
+    let version = codec.version();
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    if builtin(version).is_some() || registry.iter().any(|c| c.version() == version) {
+        return Err(TcmError::VersionTaken { version });
+    }
+    registry.push(Arc::new(codec));
+    Ok(())
+}
+
+/// Removes the codec registered for `version`, returning it.
+///
+/// Built-in versions can't be removed.
+pub fn unregister(version: u8) -> Option<Arc<dyn InputCodec>> {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    let at = registry.iter().position(|c| c.version() == version)?;
+    Some(registry.remove(at))
+}
+
+/// The codec of a format version, built in or registered.
+pub fn for_version(version: u8) -> Option<Arc<dyn InputCodec>> {
+    match builtin(version) {
+        Some(builtin) => (builtin.codec)(),
+        None => registered(version),
+    }
+}
+
+fn registered(version: u8) -> Option<Arc<dyn InputCodec>> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.iter().find(|c| c.version() == version).cloned()
+}
+
+fn version_byte(bytes: &[u8]) -> TcmResult<u8> {
+    bytes
+        .first()
+        .copied()
+        .ok_or_else(|| TcmError::invalid_metadata("empty metadata block"))
+}
+
+/// Reads a metadata block of any version, built in or registered.
+pub(crate) fn read_meta(bytes: &[u8]) -> TcmResult<AnyMeta> {
+    let version = version_byte(bytes)?;
+    match builtin(version) {
+        Some(builtin) => (builtin.read_meta)(bytes),
+        None => read_custom_meta(version, bytes, false).map(AnyMeta::Custom),
+    }
+}
+
+/// Checks a metadata block of any version for strict reads.
+pub(crate) fn validate_meta(bytes: &[u8]) -> TcmResult<()> {
+    let version = version_byte(bytes)?;
+    match builtin(version) {
+        Some(builtin) => (builtin.validate_meta)(bytes),
+        None => read_custom_meta(version, bytes, true).map(drop),
+    }
+}
+
+/// Reads a metadata block through the codec registered for its version.
+fn read_custom_meta(version: u8, bytes: &[u8], strict: bool) -> TcmResult<CustomMeta> {
+    let codec = registered(version).ok_or(TcmError::UnsupportedVersion { version })?;
+    let meta = codec.read_meta(bytes, strict)?;
+    if meta.version() != version {
+        return Err(TcmError::invalid_metadata(format!(
+            "the v{} codec read metadata of v{}",
+            version,
+            meta.version()
+        )));
+    }
+    Ok(meta)
+}
+
+/// How the inputs following the metadata block are encoded.
+#[derive(Clone)]
+pub(crate) enum Codec {
+    /// An input count, absolute frames and an end of macro marker
+    V1,
+    /// Delta-encoded frames with swift pairs, TPS changes and seeded restarts
+    V2,
+    /// A codec plugged in through [`register`]
+    Registered(Arc<dyn InputCodec>),
+}
+
+impl Codec {
+    /// The input encoding of a format version.
+    pub(crate) fn for_version(version: u8) -> TcmResult<Self> {
+        match builtin(version) {
+            Some(builtin) => Ok(builtin.encoding.clone()),
+            None => registered(version)
+                .map(Codec::Registered)
+                .ok_or(TcmError::UnsupportedVersion { version }),
+        }
+    }
+
+    /// Starts encoding a stream of `count` inputs, writing what comes before them.
+    pub(crate) fn encoder(
+        &self,
+        writer: &mut impl Write,
+        count: usize,
+    ) -> TcmResult<StreamEncoder> {
+        Ok(match self {
+            Codec::V1 => {
+                write_var_u32(writer, count as u64, "v1 input count")?;
+                StreamEncoder::V1
+            }
+            Codec::V2 => StreamEncoder::V2(v2::Encoder::default()),
+            Codec::Registered(codec) => StreamEncoder::Registered(codec.encoder(), Vec::new()),
+        })
+    }
+
+    /// Writes a whole input stream.
+    pub(crate) fn write_inputs(
+        &self,
+        writer: &mut impl Write,
+        inputs: &[InputCommand],
+    ) -> TcmResult<()> {
+        let mut encoder = self.encoder(writer, inputs.len())?;
+        for input in inputs {
+            encoder.push(writer, input)?;
+        }
+        encoder.finish(writer)
+    }
+}
+
+/// Encodes an input stream an input at a time, whatever the codec.
+pub(crate) enum StreamEncoder {
+    V1,
+    V2(v2::Encoder),
+    /// A registered codec's encoder and the bytes it hands over
+    Registered(Box<dyn InputEncoder>, Vec<u8>),
+}
+
+impl StreamEncoder {
+    pub(crate) fn push(&mut self, writer: &mut impl Write, input: &InputCommand) -> TcmResult<()> {
+        match self {
+            StreamEncoder::V1 => v1::write_input(writer, input),
+            StreamEncoder::V2(encoder) => encoder.push(writer, input),
+            StreamEncoder::Registered(encoder, bytes) => {
+                encoder.push(input, bytes)?;
+                writer.write_all(bytes)?;
+                bytes.clear();
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes whatever the stream still holds back and its end.
+    pub(crate) fn finish(self, writer: &mut impl Write) -> TcmResult<()> {
+        match self {
+            StreamEncoder::V1 => Ok(writer.write_all(&[v1::EOM])?),
+            StreamEncoder::V2(mut encoder) => encoder.finish(writer),
+            StreamEncoder::Registered(mut encoder, mut bytes) => {
+                encoder.finish(&mut bytes)?;
+                writer.write_all(&bytes)?;
+                Ok(())
+            }
+        }
+    }
+}
@@ -4,6 +4,8 @@
 
 use thiserror::Error;
 
+use crate::{options::ResourceLimit, replay::DeserializerBlob, Frame};
+
 /// Main error type for all TCM operations.
 #[derive(Error, Debug)]
 pub enum TcmError {
@@ -19,6 +21,10 @@ pub enum TcmError {
     #[error("Unsupported format version: {version}")]
     UnsupportedVersion { version: u8 },
 
+    /// A codec is registered for a format version that already has one
+    #[error("Format version {version} already has a codec")]
+    VersionTaken { version: u8 },
+
     /// Invalid input data
     #[error("Invalid input: {message}")]
     InvalidInput { message: String },
@@ -50,6 +56,61 @@ pub enum TcmError {
     /// Deserialization error
     #[error("Deserialization error: {message}")]
     DeserializationError { message: String },
+
+    /// v1 input stream doesn't end with the end of macro marker
+    #[error("Missing end of macro marker")]
+    MissingEndMarker,
+
+    /// Data follows the end of the replay
+    #[error("Unexpected trailing data at byte {offset}")]
+    TrailingData { offset: u64 },
+
+    /// Metadata flags contain bits this version doesn't define
+    #[error("Unknown metadata flags: {flags:#04x}")]
+    UnknownFlags { flags: u8 },
+
+    /// A reserved metadata byte is not zero
+    #[error("Non-zero reserved metadata byte at offset {offset:#x}")]
+    NonZeroReserved { offset: usize },
+
+    /// TPS (or delta time) that isn't a positive finite number
+    #[error("Invalid TPS value: {value}")]
+    InvalidTps { value: f32 },
+
+    /// A frame number or frame delta doesn't fit in the field the format stores it in
+    #[error("Frame value {value} does not fit in the {field}")]
+    FrameOutOfRange { value: Frame, field: String },
+
+    /// A variable-length integer encodes more bits than its field holds
+    #[error("Varint overflow while reading {context}")]
+    VarintOverflow { context: String },
+
+    /// A variable-length integer takes more bytes than its value needs
+    #[error("Overlong varint while reading {context}")]
+    OverlongVarint { context: String },
+
+    /// A seek index belongs to another replay, or to an earlier state of this one
+    #[error("Seek index doesn't match the replay: {message}")]
+    IndexMismatch { message: String },
+
+    /// A configured resource limit was exceeded
+    #[error("Resource limit exceeded: {limit:?} (max {max})")]
+    LimitExceeded { limit: ResourceLimit, max: u64 },
+
+    /// Error raised while decoding the input stream, with the decoder position
+    #[error("Decode error at byte {offset} (input {input_index}, frame {frame}, state {state:?}): {source}")]
+    Decode {
+        /// Byte offset, from the start of the replay, of the blob being decoded
+        offset: u64,
+        /// Number of inputs decoded before the failure
+        input_index: usize,
+        /// Frame the decoder was at when it failed
+        frame: Frame,
+        /// Blob the v2 decoder was reading, or `None` for v1 streams
+        state: Option<DeserializerBlob>,
+        /// The underlying error
+        source: Box<TcmError>,
+    },
 }
 
 /// Result type alias for TCM operations.
@@ -91,6 +152,31 @@ impl TcmError {
         }
     }
 
+    /// Wraps an error with the position of the input decoder.
+    pub fn decode(
+        source: TcmError,
+        offset: u64,
+        input_index: usize,
+        frame: Frame,
+        state: Option<DeserializerBlob>,
+    ) -> Self {
+        Self::Decode {
+            offset,
+            input_index,
+            frame,
+            state,
+            source: Box::new(source),
+        }
+    }
+
+    /// Returns the innermost error, looking through any decoder context.
+    pub fn root_cause(&self) -> &TcmError {
+        match self {
+            Self::Decode { source, .. } => source.root_cause(),
+            _ => self,
+        }
+    }
+
     /// Creates a deserialization error with a message.
     pub fn deserialization_error(message: impl Into<String>) -> Self {
         Self::DeserializationError {
@@ -0,0 +1,212 @@
+//! Pairing presses with releases into hold intervals.
+
+use crate::{
+    attempt::Attempt,
+    input::{Input, PlayerButton, VanillaInput},
+    replay::v2,
+    Frame,
+};
+
+/// A button held down from a press to its release.
+///
+/// The button is down on the frames `start..end`. A swift pair, the press and release or
+/// release and press on the same frame that v2 encodes as one input, makes a hold marked
+/// `swift`; one that leaves the button as it was has `start == end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hold {
+    pub player2: bool,
+    pub button: PlayerButton,
+    /// Frame of the press
+    pub start: Frame,
+    /// Frame of the release, or [`Frame::MAX`] if the button is never released
+    pub end: Frame,
+    /// Whether the press is half of a swift pair
+    pub swift: bool,
+}
+
+impl Hold {
+    /// Returns true if the press is half of a swift pair.
+    pub fn is_swift(&self) -> bool {
+        self.swift
+    }
+
+    /// Returns true if the button is down on `frame`.
+    pub fn contains(&self, frame: Frame) -> bool {
+        (self.start..self.end).contains(&frame)
+    }
+}
+
+/// Which buttons of both players are down on a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ButtonState {
+    held: u8,
+    swift: u8,
+}
+
+impl ButtonState {
+    fn bit(button: PlayerButton, player2: bool) -> u8 {
+        1 << (button as u8 - 1 + 3 * player2 as u8)
+    }
+
+    /// Returns true if `button` of the player is down after the inputs of the frame.
+    pub fn is_held(&self, button: PlayerButton, player2: bool) -> bool {
+        self.held & Self::bit(button, player2) != 0
+    }
+
+    /// Returns true if `button` of the player has a swift pair on the frame.
+    pub fn is_swift(&self, button: PlayerButton, player2: bool) -> bool {
+        self.swift & Self::bit(button, player2) != 0
+    }
+
+    /// Returns true if no button of either player is down.
+    pub fn is_empty(&self) -> bool {
+        self.held == 0
+    }
+}
+
+/// The holds of an attempt, ordered by their press.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Holds {
+    holds: Vec<Hold>,
+}
+
+impl Holds {
+    pub fn as_slice(&self) -> &[Hold] {
+        &self.holds
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Hold> {
+        self.holds.iter()
+    }
+
+    /// The buttons down on `frame`.
+    pub fn state_at(&self, frame: Frame) -> ButtonState {
+        let mut state = ButtonState::default();
+        for hold in self.holds.iter().take_while(|hold| hold.start <= frame) {
+            let bit = ButtonState::bit(hold.button, hold.player2);
+            if hold.contains(frame) {
+                state.held |= bit;
+            }
+            if hold.swift && hold.start == frame {
+                state.swift |= bit;
+            }
+        }
+        state
+    }
+}
+
+impl IntoIterator for Holds {
+    type Item = Hold;
+    type IntoIter = std::vec::IntoIter<Hold>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.holds.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Holds {
+    type Item = &'a Hold;
+    type IntoIter = std::slice::Iter<'a, Hold>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.holds.iter()
+    }
+}
+
+impl Attempt<'_> {
+    /// Pairs the presses of the attempt with their releases.
+    ///
+    /// Swift pairs are found the way the v2 serializer finds them. A press and release
+    /// leaves the button as it was and a release and press leaves it down, so a button
+    /// already down stays in the same hold, next to a `start == end` hold for the pair.
+    /// A release and press of a button that isn't down opens a hold marked swift.
+    /// Otherwise a press of a button that is already down and a release of a button that
+    /// isn't are ignored. Buttons still down when the attempt restarts are released on
+    /// the frame of the restart.
+    ///
+    /// # Example
+    /// ```
+    /// use tcm::input::{PlayerButton::Jump, RestartType};
+    /// use tcm::ReplayBuilder;
+    ///
+    /// let replay = ReplayBuilder::v2(240.0)
+    ///     .hold(Jump, 10..20)
+    ///     .at(30).press(Jump).release(Jump)
+    ///     .player2().at(40).press(Jump)
+    ///     .at(50).restart(RestartType::Death)
+    ///     .build()
+    ///     .unwrap();
+    /// let attempt = &replay.attempts()[0];
+    /// let holds = attempt.holds();
+    /// assert_eq!(holds.as_slice().len(), 3);
+    /// assert!(holds.as_slice()[1].is_swift());
+    /// assert!(attempt.state_at(15).is_held(Jump, false));
+    /// assert!(attempt.state_at(30).is_swift(Jump, false));
+    /// assert!(attempt.state_at(45).is_held(Jump, true));
+    /// assert!(attempt.state_at(50).is_empty());
+    /// ```
+    pub fn holds(&self) -> Holds {
+        let mut holds = Vec::new();
+        // Index into `holds` of the open hold of each button, for player 1 and 2.
+        let mut open: [[Option<usize>; 3]; 2] = [[None; 3]; 2];
+
+        let mut inputs = self.inputs.iter().peekable();
+        while let Some(input) = inputs.next() {
+            let swift = inputs
+                .next_if(|next| v2::is_swift_pair(input, next))
+                .is_some();
+            match &input.input {
+                Input::Vanilla(VanillaInput {
+                    button,
+                    push,
+                    player2,
+                }) => {
+                    let slot = &mut open[*player2 as usize][*button as usize - 1];
+                    let hold = Hold {
+                        player2: *player2,
+                        button: *button,
+                        start: input.frame,
+                        end: input.frame,
+                        swift,
+                    };
+                    // A swift press and release leaves the button as it was, a swift
+                    // release and press leaves it down.
+                    match (*push, swift, *slot) {
+                        (true, false, None) | (false, true, None) => {
+                            *slot = Some(holds.len());
+                            holds.push(Hold {
+                                end: Frame::MAX,
+                                ..hold
+                            });
+                        }
+                        (false, false, Some(at)) => {
+                            holds[at].end = input.frame.max(holds[at].start);
+                            *slot = None;
+                        }
+                        (_, true, _) => holds.push(hold),
+                        _ => {}
+                    }
+                }
+                Input::Restart(_) => {
+                    for at in open.iter_mut().flatten().filter_map(Option::take) {
+                        holds[at].end = input.frame.max(holds[at].start);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Inputs within an attempt are ordered by frame already, unless the replay is
+        // broken; the sort keeps lookups working either way.
+        holds.sort_by_key(|hold| hold.start);
+        Holds { holds }
+    }
+
+    /// The buttons down on `frame` of the attempt.
+    ///
+    /// Pairs up the whole attempt on every call; use [`Attempt::holds`] to look up
+    /// many frames.
+    pub fn state_at(&self, frame: Frame) -> ButtonState {
+        self.holds().state_at(frame)
+    }
+}
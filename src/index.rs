@@ -0,0 +1,323 @@
+//! Seek tables for random access into v2 input streams.
+//!
+//! Every v2 delta depends on the deltas and frames before it, so getting to an input deep
+//! into a replay normally means decoding everything in front of it. A [`SeekIndex`]
+//! records the decoder state at regular checkpoints in one pass; decoding can then pick
+//! up at any checkpoint with [`InputStream::resume`].
+//!
+//! [`InputStream::resume`]: crate::stream::InputStream::resume
+//!
+//! An index can be written next to the replay as a sidecar file, so it only has to be
+//! built once.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::{
+    codec::Codec,
+    error::{TcmError, TcmResult},
+    input::Input,
+    meta::{AnyMeta, MetaFormat, MetaInfo},
+    replay::{
+        read_array, read_var_u64, v2, write_var_u64, CountingReader, DeserializerBlob, HEADER_SIZE,
+        PREFIX_SIZE, TCBOT_HEADER,
+    },
+    Frame,
+};
+
+/// Identifies a sidecar file, followed by the format version of the sidecar.
+const SIDECAR_MAGIC: [u8; 8] = *b"TCMSEEK\0";
+const SIDECAR_VERSION: u8 = 1;
+
+/// The v2 decoder state at a point between two inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Byte offset of the next blob, counted from the start of the file
+    pub offset: u64,
+    /// Number of inputs before the checkpoint
+    pub input_index: usize,
+    /// Number of restarts before the checkpoint
+    pub attempt: usize,
+    /// Frame the decoder is at: that of the next input when `state` is `Action`, or of
+    /// the last input before the checkpoint while its frame delta is pending (0 right
+    /// after a restart)
+    pub frame: Frame,
+    /// The last non-zero frame delta, which magic deltas build on
+    pub last_delta: Frame,
+    /// The blob the decoder expects at `offset`
+    pub state: DeserializerBlob,
+    /// Delta bits of the last action byte, announcing the pending frame delta
+    pub delta_bits: u8,
+}
+
+/// Checkpoints into the input stream of a v2 replay.
+///
+/// # Example
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use tcm::index::SeekIndex;
+/// use tcm::stream::DynamicInputStream;
+///
+/// let index = SeekIndex::build(BufReader::new(File::open("replay.tcm").unwrap()), 4096).unwrap();
+/// index.write_to(&mut File::create("replay.tcm.idx").unwrap()).unwrap();
+///
+/// // Later: decode the inputs of attempt 3 from frame 2,000,000 on.
+/// let index = SeekIndex::read_from(&mut File::open("replay.tcm.idx").unwrap()).unwrap();
+/// let checkpoint = index.checkpoint_for_frame(3, 2_000_000).unwrap();
+/// let file = BufReader::new(File::open("replay.tcm").unwrap());
+/// for input in DynamicInputStream::resume(file, &index, checkpoint).unwrap() {
+///     println!("{:?}", input.unwrap());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeekIndex {
+    prefix: [u8; PREFIX_SIZE],
+    interval: usize,
+    stream_len: u64,
+    input_count: usize,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl SeekIndex {
+    /// Decodes a whole v2 replay, recording a checkpoint at the start of the input stream
+    /// and then every `interval` inputs.
+    pub fn build<R: Read>(reader: R, interval: usize) -> TcmResult<Self> {
+        let interval = interval.max(1);
+        let mut reader = CountingReader::new(reader);
+        let prefix: [u8; PREFIX_SIZE] = read_array(&mut reader, "header and metadata")?;
+        if prefix[..HEADER_SIZE] != TCBOT_HEADER {
+            return Err(TcmError::InvalidHeader);
+        }
+        let meta = AnyMeta::from_bytes(&prefix[HEADER_SIZE..])?;
+        let version = meta.version();
+        if !matches!(Codec::for_version(version)?, Codec::V2) {
+            return Err(TcmError::invalid_input(format!(
+                "Seek indexes are only built for v2 replays, this replay is v{}",
+                version
+            )));
+        }
+
+        let mut index = Self {
+            prefix,
+            interval,
+            stream_len: 0,
+            input_count: 0,
+            checkpoints: Vec::new(),
+        };
+
+        let Some(mut decoder) = v2::Decoder::start(&mut reader, false)? else {
+            index.stream_len = reader.offset;
+            return Ok(index);
+        };
+        let mut attempt = 0;
+        let mut next_checkpoint = 0;
+        let mut decoded = Vec::new();
+        loop {
+            let offset = reader.offset;
+            let state = decoder.state();
+            let between_inputs = matches!(
+                state,
+                DeserializerBlob::Action | DeserializerBlob::FrameDelta
+            );
+            if between_inputs && decoder.decoded() >= next_checkpoint {
+                index.checkpoints.push(Checkpoint {
+                    offset,
+                    input_index: decoder.decoded(),
+                    attempt,
+                    frame: decoder.current_frame(),
+                    last_delta: decoder.last_delta(),
+                    state,
+                    delta_bits: decoder.delta_bits(),
+                });
+                next_checkpoint = decoder.decoded() + interval;
+            }
+
+            decoded.clear();
+            if !decoder.step(&mut reader, &mut decoded)? {
+                decoder.check_permissive_end(offset)?;
+                break;
+            }
+            attempt += decoded
+                .iter()
+                .filter(|input| matches!(input.input, Input::Restart(_)))
+                .count();
+        }
+
+        index.stream_len = reader.offset;
+        index.input_count = decoder.decoded();
+        Ok(index)
+    }
+
+    /// Number of inputs between checkpoints the index was built with.
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Size in bytes of the replay the index was built from.
+    pub fn stream_len(&self) -> u64 {
+        self.stream_len
+    }
+
+    /// Checks that the index was built from the replay in `reader` as it is now.
+    ///
+    /// The header, the metadata and the size of the replay all have to match what the
+    /// index recorded, so an index of another replay, or of this one before it was
+    /// appended to, is refused with [`TcmError::IndexMismatch`]. The reader is left at an
+    /// unspecified position.
+    pub fn check_replay<R: Read + Seek>(&self, reader: &mut R) -> TcmResult<()> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        if len != self.stream_len {
+            return Err(TcmError::IndexMismatch {
+                message: format!(
+                    "the index is of a {} byte replay, this one has {} bytes",
+                    self.stream_len, len
+                ),
+            });
+        }
+
+        reader.seek(SeekFrom::Start(0))?;
+        let prefix: [u8; PREFIX_SIZE] = read_array(reader, "header and metadata")?;
+        if prefix != self.prefix {
+            return Err(TcmError::IndexMismatch {
+                message: "the header or metadata differ".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Number of inputs in the replay the index was built from.
+    pub fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    /// All checkpoints, in stream order.
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// The last checkpoint before the input at `input_index`.
+    pub fn checkpoint_for_input(&self, input_index: usize) -> Option<&Checkpoint> {
+        let after = self
+            .checkpoints
+            .partition_point(|c| c.input_index <= input_index);
+        self.checkpoints.get(after.saturating_sub(1))
+    }
+
+    /// The last checkpoint before any input of `attempt` at or after `frame`.
+    ///
+    /// Attempts are counted from 0 and end with a restart input.
+    pub fn checkpoint_for_frame(&self, attempt: usize, frame: Frame) -> Option<&Checkpoint> {
+        let after = self
+            .checkpoints
+            .partition_point(|c| (c.attempt, c.frame) < (attempt, frame));
+        self.checkpoints.get(after.saturating_sub(1))
+    }
+
+    /// Writes the index in its sidecar file format.
+    pub fn write_to(&self, writer: &mut impl Write) -> TcmResult<()> {
+        writer.write_all(&SIDECAR_MAGIC)?;
+        writer.write_all(&[SIDECAR_VERSION])?;
+        writer.write_all(&self.prefix)?;
+        write_var_u64(writer, self.interval as u64)?;
+        write_var_u64(writer, self.stream_len)?;
+        write_var_u64(writer, self.input_count as u64)?;
+        write_var_u64(writer, self.checkpoints.len() as u64)?;
+
+        // Offsets, input indexes and attempts only grow, so they are stored as differences.
+        let mut previous = (0, 0, 0);
+        for c in &self.checkpoints {
+            write_var_u64(writer, c.offset - previous.0)?;
+            write_var_u64(writer, (c.input_index - previous.1) as u64)?;
+            write_var_u64(writer, (c.attempt - previous.2) as u64)?;
+            write_var_u64(writer, c.frame)?;
+            write_var_u64(writer, c.last_delta)?;
+            let state = match c.state {
+                DeserializerBlob::FrameDelta => 1,
+                _ => 0,
+            };
+            writer.write_all(&[state, c.delta_bits])?;
+            previous = (c.offset, c.input_index, c.attempt);
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads an index written by [`SeekIndex::write_to`].
+    pub fn read_from(reader: &mut impl Read) -> TcmResult<Self> {
+        let magic: [u8; 8] = read_array(reader, "seek index header")?;
+        if magic != SIDECAR_MAGIC {
+            return Err(TcmError::invalid_format("Not a seek index file"));
+        }
+        let [version] = read_array(reader, "seek index version")?;
+        if version != SIDECAR_VERSION {
+            return Err(TcmError::UnsupportedVersion { version });
+        }
+        let prefix: [u8; PREFIX_SIZE] = read_array(reader, "seek index replay prefix")?;
+
+        let interval = read_usize(reader, "seek index interval")?;
+        let stream_len = read_var_u64(reader, "seek index stream length")?;
+        let input_count = read_usize(reader, "seek index input count")?;
+        let count = read_usize(reader, "seek index checkpoint count")?;
+
+        // The count comes from the file, so it only guides the initial allocation so far.
+        let mut checkpoints = Vec::with_capacity(count.min(4096));
+        let mut previous = (0u64, 0usize, 0usize);
+        for _ in 0..count {
+            let offset = read_var_u64(reader, "checkpoint offset")?;
+            let input_index = read_usize(reader, "checkpoint input index")?;
+            let attempt = read_usize(reader, "checkpoint attempt")?;
+            let frame = read_var_u64(reader, "checkpoint frame")?;
+            let last_delta = read_var_u64(reader, "checkpoint last delta")?;
+            let [state, delta_bits] = read_array(reader, "checkpoint state")?;
+
+            let state = match state {
+                0 => DeserializerBlob::Action,
+                1 => DeserializerBlob::FrameDelta,
+                _ => {
+                    return Err(TcmError::invalid_format(format!(
+                        "Invalid checkpoint state: {}",
+                        state
+                    )))
+                }
+            };
+            if delta_bits > 0b111 {
+                return Err(TcmError::invalid_format(format!(
+                    "Invalid checkpoint delta bits: {:#04x}",
+                    delta_bits
+                )));
+            }
+
+            let overflow = || TcmError::invalid_format("Checkpoint position overflow");
+            let checkpoint = Checkpoint {
+                offset: previous.0.checked_add(offset).ok_or_else(overflow)?,
+                input_index: previous.1.checked_add(input_index).ok_or_else(overflow)?,
+                attempt: previous.2.checked_add(attempt).ok_or_else(overflow)?,
+                frame,
+                last_delta,
+                state,
+                delta_bits,
+            };
+            previous = (
+                checkpoint.offset,
+                checkpoint.input_index,
+                checkpoint.attempt,
+            );
+            checkpoints.push(checkpoint);
+        }
+
+        Ok(Self {
+            prefix,
+            interval,
+            stream_len,
+            input_count,
+            checkpoints,
+        })
+    }
+}
+
+fn read_usize(reader: &mut impl Read, context: &str) -> TcmResult<usize> {
+    let value = read_var_u64(reader, context)?;
+    usize::try_from(value).map_err(|_| TcmError::VarintOverflow {
+        context: context.to_string(),
+    })
+}
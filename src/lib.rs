@@ -7,7 +7,7 @@
 //! ## Auto-Detection (Recommended)
 //!
 //! ```rust
-//! use tcm::{DynamicReplay, meta::Meta};
+//! use tcm::{DynamicReplay, meta::MetaInfo};
 //! use std::fs::File;
 //! use std::io::BufReader;
 //!
@@ -16,7 +16,7 @@
 //! let mut reader = BufReader::new(file);
 //! let replay = DynamicReplay::from_reader(&mut reader)?;
 //!
-//! println!("TCM v{} - TPS: {}", replay.meta.version_instance(), replay.meta.tps());
+//! println!("TCM v{} - TPS: {}", replay.meta.version(), replay.meta.tps());
 //! # Ok(())
 //! # }
 //! ```
@@ -24,18 +24,36 @@
 //! ## Type-Specific Usage
 //!
 //! ```rust
-//! use tcm::{meta::{Meta, MetaV2}, replay::{Replay, ReplayDeserializer}};
+//! use tcm::{meta::{MetaInfo, MetaV2}, replay::{Replay, ReplayDeserializer}};
 //!
 //! let replay = Replay::<MetaV2>::new_empty(240.0);
 //! println!("TPS: {}", replay.meta.tps());
 //! ```
 
+#[cfg(feature = "tokio")]
+pub mod async_io;
+pub mod attempt;
+pub mod builder;
+pub mod codec;
 pub mod error;
+pub mod hold;
+pub mod index;
 pub mod input;
+pub mod lint;
 pub mod meta;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod options;
+pub mod recovery;
 pub mod replay;
+pub mod stream;
+pub mod timeline;
+pub mod writer;
 
 pub type Frame = u64;
 
 // Re-export key types for convenience
+pub use builder::ReplayBuilder;
+pub use error::{TcmError, TcmResult};
+pub use options::{ReadLimits, ReadOptions};
 pub use replay::{DynamicReplay, Replay};
@@ -0,0 +1,189 @@
+//! Checks that a replay makes sense as gameplay.
+//!
+//! A replay can be perfectly encodable and still be wrong: a release without a press, a
+//! button pressed twice, frames running backwards. [`Replay::validate`] reports such
+//! findings without changing anything.
+
+use crate::{
+    input::{Input, PlayerButton, VanillaInput},
+    meta::{check_tps, MetaFormat},
+    replay::Replay,
+    Frame,
+};
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Unusual, but can happen in a real recording
+    Warning,
+    /// The replay doesn't play back as recorded
+    Error,
+}
+
+/// What a finding is about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintKind {
+    /// A button is released that isn't held
+    ReleaseWithoutPress { button: PlayerButton, player2: bool },
+    /// A button is pressed while it is already held
+    DoublePress { button: PlayerButton, player2: bool },
+    /// An input comes before the previous input of the same attempt
+    FrameWentBack { previous: Frame },
+    /// A TPS change to a value that isn't a positive finite number
+    InvalidTps { tps: f32 },
+    /// Left or Right in a run that isn't a platformer run
+    PlatformerButton { button: PlayerButton, player2: bool },
+    /// A press in the last attempt that is never released
+    NeverReleased { button: PlayerButton, player2: bool },
+}
+
+impl LintKind {
+    /// The severity findings of this kind are reported with.
+    pub fn severity(&self) -> Severity {
+        match self {
+            LintKind::FrameWentBack { .. } | LintKind::InvalidTps { .. } => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// A single finding of [`Replay::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub severity: Severity,
+    /// Index of the input the finding refers to
+    pub input_index: usize,
+    /// Frame of that input
+    pub frame: Frame,
+    pub kind: LintKind,
+}
+
+/// Options for [`Replay::validate_with`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LintOptions {
+    /// Whether the run is a platformer run, where Left and Right are expected.
+    ///
+    /// Left unset, a run counts as a platformer run if Left and Right make up at least
+    /// half of its button inputs, so a few of them mixed into a Jump run are reported.
+    pub platformer: Option<bool>,
+}
+
+impl<M: MetaFormat> Replay<M> {
+    /// Checks the inputs for things that don't make sense as gameplay.
+    ///
+    /// Findings are ordered by input index. Held buttons are forgotten at every restart,
+    /// so only presses in the last attempt are reported as never released.
+    ///
+    /// # Example
+    /// ```
+    /// use tcm::input::{Input, InputCommand, PlayerButton::Jump, VanillaInput};
+    /// use tcm::lint::{LintKind, Severity};
+    /// use tcm::meta::MetaV2;
+    /// use tcm::Replay;
+    ///
+    /// let press = |frame| {
+    ///     let input = VanillaInput { button: Jump, push: true, player2: false };
+    ///     InputCommand::new(frame, Input::Vanilla(input))
+    /// };
+    /// let replay = Replay::new(MetaV2::new(240.0, 0, None), vec![press(100), press(120)]);
+    /// let lints = replay.validate();
+    /// assert!(matches!(lints[0].kind, LintKind::DoublePress { .. }));
+    /// assert!(lints.iter().all(|lint| lint.severity == Severity::Warning));
+    /// ```
+    pub fn validate(&self) -> Vec<Lint> {
+        self.validate_with(&LintOptions::default())
+    }
+
+    /// Like [`Replay::validate`], with the given [`LintOptions`].
+    pub fn validate_with(&self, options: &LintOptions) -> Vec<Lint> {
+        let platformer = options
+            .platformer
+            .unwrap_or_else(|| self.looks_like_platformer());
+
+        let mut lints = Vec::new();
+        let mut lint = |input_index: usize, frame: Frame, kind: LintKind| {
+            lints.push(Lint {
+                severity: kind.severity(),
+                input_index,
+                frame,
+                kind,
+            })
+        };
+
+        // Index of the press holding each button, for player 1 and 2.
+        let mut held: [[Option<usize>; 3]; 2] = [[None; 3]; 2];
+        let mut previous: Frame = 0;
+        for (index, input) in self.inputs.iter().enumerate() {
+            let frame = input.frame;
+            if frame < previous {
+                lint(index, frame, LintKind::FrameWentBack { previous });
+            }
+            previous = previous.max(frame);
+
+            match &input.input {
+                Input::Vanilla(VanillaInput {
+                    button,
+                    push,
+                    player2,
+                }) => {
+                    let (button, player2) = (*button, *player2);
+                    if !platformer && button != PlayerButton::Jump {
+                        lint(index, frame, LintKind::PlatformerButton { button, player2 });
+                    }
+                    let slot = &mut held[player2 as usize][button as usize - 1];
+                    match (*push, slot.is_some()) {
+                        (true, true) => {
+                            lint(index, frame, LintKind::DoublePress { button, player2 })
+                        }
+                        (false, false) => lint(
+                            index,
+                            frame,
+                            LintKind::ReleaseWithoutPress { button, player2 },
+                        ),
+                        _ => {}
+                    }
+                    *slot = push.then_some(index);
+                }
+                Input::Restart(_) => {
+                    held = [[None; 3]; 2];
+                    previous = 0;
+                }
+                Input::Tps(tps) => {
+                    if check_tps(tps.tps).is_err() {
+                        lint(index, frame, LintKind::InvalidTps { tps: tps.tps });
+                    }
+                }
+                Input::Bugpoint(_) => {}
+            }
+        }
+
+        for (player2, buttons) in held.iter().enumerate() {
+            for (button, press) in buttons.iter().enumerate() {
+                if let Some(index) = *press {
+                    let button =
+                        [PlayerButton::Jump, PlayerButton::Left, PlayerButton::Right][button];
+                    let kind = LintKind::NeverReleased {
+                        button,
+                        player2: player2 == 1,
+                    };
+                    lint(index, self.inputs[index].frame, kind);
+                }
+            }
+        }
+
+        lints.sort_by_key(|lint| lint.input_index);
+        lints
+    }
+
+    /// Whether Left and Right make up at least half of the button inputs.
+    fn looks_like_platformer(&self) -> bool {
+        let (mut buttons, mut sideways) = (0usize, 0usize);
+        for input in &self.inputs {
+            if let Input::Vanilla(vanilla) = &input.input {
+                buttons += 1;
+                sideways += (vanilla.button != PlayerButton::Jump) as usize;
+            }
+        }
+        buttons > 0 && sideways * 2 >= buttons
+    }
+}
@@ -1,38 +1,66 @@
 //! Metadata structures for TCM format versions.
 
-pub trait Meta: Send + Sync {
-    fn size() -> usize
-    where
-        Self: Sized;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::{
+    codec::{self, InputCodec, V1Codec, V2Codec},
+    error::{TcmError, TcmResult},
+};
+
+/// Size of the metadata block used by every format version.
+const META_SIZE: usize = 0x40;
+
+/// Accessors shared by the metadata of every format version.
+///
+/// The trait is object safe, so metadata can be inspected as `&dyn MetaInfo` without
+/// knowing its version.
+pub trait MetaInfo: Send + Sync {
     fn tps(&self) -> f32;
     fn tps_dt(&self) -> f32;
     fn uses_dt(&self) -> bool;
-    fn version() -> u8
-    where
-        Self: Sized;
-    fn version_instance(&self) -> u8;
+    /// The format version of the replay.
+    fn version(&self) -> u8;
     fn rng_seed(&self) -> Option<u64>;
     fn is_rng_seed_set(&self) -> bool;
-    fn from_bytes(bytes: &[u8]) -> Self
-    where
-        Self: Sized;
+    /// Number of times inputs were appended to the replay after it was first written.
+    fn append_counter(&self) -> u8;
+}
+
+/// The binary layout of a metadata block.
+///
+/// Implemented by the metadata of each format version, and by [`AnyMeta`] which picks
+/// the version from the version byte.
+pub trait MetaFormat: MetaInfo + Sized {
+    /// The format version this metadata belongs to, or `None` for [`AnyMeta`], which
+    /// reads whichever version the version byte names.
+    ///
+    /// Built-in versions are looked up through this, together with
+    /// [`MetaFormat::codec`], so a new version only implements this trait for its
+    /// metadata and adds itself to the version table.
+    const FORMAT_VERSION: Option<u8>;
+    /// The codec of the input stream following the metadata, `None` for [`AnyMeta`].
+    fn codec() -> Option<Arc<dyn InputCodec>>;
+    /// Size of the metadata block in bytes.
+    fn size() -> usize {
+        META_SIZE
+    }
+    fn from_bytes(bytes: &[u8]) -> TcmResult<Self>;
     fn to_bytes(&self) -> Box<[u8]>;
-    fn new_empty(tps: f32) -> Self
-    where
-        Self: Sized;
+    /// Checks that a metadata block only uses the canonical layout, for strict reads.
+    fn validate_bytes(bytes: &[u8]) -> TcmResult<()>;
+    fn new_empty(tps: f32) -> Self;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MetaV1 {
     pub tps: f32,
     pub append_counter: u8,
+    reserved: [u8; META_SIZE],
 }
 
-impl Meta for MetaV1 {
-    fn size() -> usize {
-        0x40
-    }
-
+impl MetaInfo for MetaV1 {
     fn tps(&self) -> f32 {
         self.tps
     }
@@ -45,12 +73,8 @@ impl Meta for MetaV1 {
         false
     }
 
-    fn version() -> u8 {
-        1
-    }
-
-    fn version_instance(&self) -> u8 {
-        1
+    fn version(&self) -> u8 {
+        Self::VERSION
     }
 
     fn rng_seed(&self) -> Option<u64> {
@@ -61,39 +85,120 @@ impl Meta for MetaV1 {
         false
     }
 
-    fn from_bytes(bytes: &[u8]) -> Self {
-        assert!(bytes.len() >= Self::size());
-        let version = bytes[0];
-        assert!(version == Self::version());
+    fn append_counter(&self) -> u8 {
+        self.append_counter
+    }
+}
+
+impl MetaFormat for MetaV1 {
+    const FORMAT_VERSION: Option<u8> = Some(MetaV1::VERSION);
+
+    fn codec() -> Option<Arc<dyn InputCodec>> {
+        Some(Arc::new(V1Codec))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> TcmResult<Self> {
+        check_meta_bytes(bytes, Self::size(), Self::VERSION)?;
         let append_counter = bytes[1];
-        let tps = f32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let tps = f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
 
-        Self {
+        Ok(Self {
             tps,
             append_counter,
-        }
+            reserved: extract_reserved(bytes, &Self::RESERVED),
+        })
     }
 
     fn to_bytes(&self) -> Box<[u8]> {
-        let mut bytes = vec![0u8; Self::size()];
-        bytes[0] = Self::version();
+        let mut bytes = self.reserved.to_vec();
+        bytes[0] = Self::VERSION;
         bytes[1] = self.append_counter;
         bytes[4..8].copy_from_slice(&self.tps.to_le_bytes());
         bytes.into_boxed_slice()
     }
 
+    fn validate_bytes(bytes: &[u8]) -> TcmResult<()> {
+        check_meta_bytes(bytes, Self::size(), Self::VERSION)?;
+        check_reserved(bytes, &Self::RESERVED)?;
+        check_tps(f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]))
+    }
+
     fn new_empty(tps: f32) -> Self {
         Self::new(tps, 0)
     }
 }
 
+/// Checks that a metadata block is large enough and carries the expected version byte.
+fn check_meta_bytes(bytes: &[u8], size: usize, version: u8) -> TcmResult<()> {
+    if bytes.len() < size {
+        return Err(TcmError::invalid_metadata(format!(
+            "expected {} bytes, got {}",
+            size,
+            bytes.len()
+        )));
+    }
+    if bytes[0] != version {
+        return Err(TcmError::invalid_metadata(format!(
+            "expected version {}, found {}",
+            version, bytes[0]
+        )));
+    }
+    Ok(())
+}
+
+/// Copies the reserved ranges of a metadata block, leaving every other byte zeroed.
+fn extract_reserved(bytes: &[u8], reserved: &[Range<usize>]) -> [u8; META_SIZE] {
+    let mut out = [0u8; META_SIZE];
+    for range in reserved {
+        out[range.clone()].copy_from_slice(&bytes[range.clone()]);
+    }
+    out
+}
+
+/// Checks that every reserved byte of a metadata block is zero.
+fn check_reserved(bytes: &[u8], reserved: &[Range<usize>]) -> TcmResult<()> {
+    for range in reserved {
+        if let Some(at) = bytes[range.clone()].iter().position(|&b| b != 0) {
+            return Err(TcmError::NonZeroReserved {
+                offset: range.start + at,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a TPS (or delta time) value is a positive finite number.
+#[inline]
+pub(crate) fn check_tps(value: f32) -> TcmResult<()> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(TcmError::InvalidTps { value })
+    }
+}
+
 impl MetaV1 {
+    /// The format version this metadata belongs to.
+    pub const VERSION: u8 = 1;
+
+    /// Byte ranges of the metadata block that v1 doesn't assign a meaning to.
+    const RESERVED: [Range<usize>; 2] = [2..4, 8..META_SIZE];
+
     pub fn new(tps: f32, append_counter: u8) -> Self {
         Self {
             tps,
             append_counter,
+            reserved: [0; META_SIZE],
         }
     }
+
+    /// The metadata block as read from the file, with every known field zeroed.
+    ///
+    /// Non-zero bytes here were written by a newer tcbot build and are written back
+    /// unchanged by [`MetaFormat::to_bytes`].
+    pub fn reserved_bytes(&self) -> &[u8] {
+        &self.reserved
+    }
 }
 
 #[repr(u8)]
@@ -103,6 +208,8 @@ enum MetaV2BitFlags {
 }
 
 impl MetaV2BitFlags {
+    const ALL: u8 = Self::OverrideSeed as u8 | Self::TpsInsteadOfDt as u8;
+
     fn is_set(flags: u8, flag: MetaV2BitFlags) -> bool {
         (flags & (flag as u8)) != 0
     }
@@ -116,19 +223,16 @@ impl MetaV2BitFlags {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MetaV2 {
     pub rng_seed: Option<u64>,
     tps_or_dt: f32,
     pub append_counter: u8,
     flags: u8,
+    reserved: [u8; META_SIZE],
 }
 
-impl Meta for MetaV2 {
-    fn size() -> usize {
-        0x40
-    }
-
+impl MetaInfo for MetaV2 {
     fn tps(&self) -> f32 {
         if MetaV2BitFlags::is_set(self.flags, MetaV2BitFlags::TpsInsteadOfDt) {
             self.tps_or_dt
@@ -149,12 +253,8 @@ impl Meta for MetaV2 {
         !MetaV2BitFlags::is_set(self.flags, MetaV2BitFlags::TpsInsteadOfDt)
     }
 
-    fn version() -> u8 {
-        2
-    }
-
-    fn version_instance(&self) -> u8 {
-        2
+    fn version(&self) -> u8 {
+        Self::VERSION
     }
 
     fn rng_seed(&self) -> Option<u64> {
@@ -165,27 +265,40 @@ impl Meta for MetaV2 {
         MetaV2BitFlags::is_set(self.flags, MetaV2BitFlags::OverrideSeed)
     }
 
-    fn from_bytes(bytes: &[u8]) -> Self {
-        assert!(bytes.len() >= Self::size());
-        let version = bytes[0];
-        assert!(version == Self::version());
+    fn append_counter(&self) -> u8 {
+        self.append_counter
+    }
+}
+
+impl MetaFormat for MetaV2 {
+    const FORMAT_VERSION: Option<u8> = Some(MetaV2::VERSION);
+
+    fn codec() -> Option<Arc<dyn InputCodec>> {
+        Some(Arc::new(V2Codec))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> TcmResult<Self> {
+        check_meta_bytes(bytes, Self::size(), Self::VERSION)?;
         let append_counter = bytes[1];
         let flags = bytes[2];
-        let tps_or_dt = f32::from_le_bytes(bytes[4..8].try_into().unwrap());
-        let seed = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let tps_or_dt = f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let mut seed_bytes = [0u8; 8];
+        seed_bytes.copy_from_slice(&bytes[8..16]);
+        let seed = u64::from_le_bytes(seed_bytes);
         let rng_seed = if seed != 0 { Some(seed) } else { None };
 
-        Self {
+        Ok(Self {
             rng_seed,
             tps_or_dt,
             append_counter,
             flags,
-        }
+            reserved: extract_reserved(bytes, &Self::RESERVED),
+        })
     }
 
     fn to_bytes(&self) -> Box<[u8]> {
-        let mut bytes = vec![0u8; Self::size()];
-        bytes[0] = Self::version();
+        let mut bytes = self.reserved.to_vec();
+        bytes[0] = Self::VERSION;
         bytes[1] = self.append_counter;
         bytes[2] = self.flags;
         bytes[4..8].copy_from_slice(&self.tps_or_dt.to_le_bytes());
@@ -195,12 +308,28 @@ impl Meta for MetaV2 {
         bytes.into_boxed_slice()
     }
 
+    fn validate_bytes(bytes: &[u8]) -> TcmResult<()> {
+        check_meta_bytes(bytes, Self::size(), Self::VERSION)?;
+        let flags = bytes[2];
+        if flags & !MetaV2BitFlags::ALL != 0 {
+            return Err(TcmError::UnknownFlags { flags });
+        }
+        check_reserved(bytes, &Self::RESERVED)?;
+        check_tps(f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]))
+    }
+
     fn new_empty(tps: f32) -> Self {
         Self::new(tps, 0, None)
     }
 }
 
 impl MetaV2 {
+    /// The format version this metadata belongs to.
+    pub const VERSION: u8 = 2;
+
+    /// Byte ranges of the metadata block that v2 doesn't assign a meaning to.
+    const RESERVED: [Range<usize>; 2] = [3..4, 16..META_SIZE];
+
     pub fn new(tps: f32, append_counter: u8, rng_seed: Option<u64>) -> Self {
         let mut flags = 0;
         MetaV2BitFlags::set(&mut flags, MetaV2BitFlags::TpsInsteadOfDt, true);
@@ -210,6 +339,263 @@ impl MetaV2 {
             tps_or_dt: tps,
             append_counter,
             flags,
+            reserved: [0; META_SIZE],
         }
     }
+
+    /// The metadata block as read from the file, with every known field zeroed.
+    ///
+    /// Non-zero bytes here were written by a newer tcbot build and are written back
+    /// unchanged by [`MetaFormat::to_bytes`].
+    pub fn reserved_bytes(&self) -> &[u8] {
+        &self.reserved
+    }
+}
+
+/// The metadata layout of a format version plugged in through [`codec::register`], as
+/// its codec reads it in [`InputCodec::read_meta`].
+///
+/// [`codec::register`]: crate::codec::register
+pub trait CustomLayout: MetaInfo + fmt::Debug {
+    /// The metadata block as written to the file: 0x40 bytes starting with the version
+    /// byte.
+    fn to_bytes(&self) -> Box<[u8]>;
+}
+
+/// Metadata of a format version plugged in through [`codec::register`].
+///
+/// The layout is up to the codec of the version. Codecs that don't define one read the
+/// v2 layout under their own version byte, see [`CustomMeta::v2`].
+///
+/// [`codec::register`]: crate::codec::register
+#[derive(Debug, Clone)]
+pub struct CustomMeta(Arc<dyn CustomLayout>);
+
+impl CustomMeta {
+    pub fn new(layout: impl CustomLayout + 'static) -> Self {
+        Self(Arc::new(layout))
+    }
+
+    /// Metadata in the v2 layout, with `version` as the version byte.
+    pub fn v2(version: u8, meta: MetaV2) -> Self {
+        Self::new(V2Layout { version, meta })
+    }
+
+    /// Reads a block in the v2 layout whatever its version byte, checking it like
+    /// [`MetaV2`] does for strict reads.
+    pub fn read_v2(bytes: &[u8], strict: bool) -> TcmResult<Self> {
+        let version = *bytes
+            .first()
+            .ok_or_else(|| TcmError::invalid_metadata("empty metadata block"))?;
+        let mut bytes = bytes.to_vec();
+        bytes[0] = MetaV2::VERSION;
+        if strict {
+            MetaV2::validate_bytes(&bytes)?;
+        }
+        Ok(Self::v2(version, MetaV2::from_bytes(&bytes)?))
+    }
+
+    pub fn layout(&self) -> &dyn CustomLayout {
+        &*self.0
+    }
+
+    fn to_bytes(&self) -> Box<[u8]> {
+        self.0.to_bytes()
+    }
+}
+
+/// Two custom metadata blocks are equal if they write the same bytes.
+impl PartialEq for CustomMeta {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl MetaInfo for CustomMeta {
+    fn tps(&self) -> f32 {
+        self.0.tps()
+    }
+
+    fn tps_dt(&self) -> f32 {
+        self.0.tps_dt()
+    }
+
+    fn uses_dt(&self) -> bool {
+        self.0.uses_dt()
+    }
+
+    fn version(&self) -> u8 {
+        self.0.version()
+    }
+
+    fn rng_seed(&self) -> Option<u64> {
+        self.0.rng_seed()
+    }
+
+    fn is_rng_seed_set(&self) -> bool {
+        self.0.is_rng_seed_set()
+    }
+
+    fn append_counter(&self) -> u8 {
+        self.0.append_counter()
+    }
+}
+
+/// The v2 layout under another version byte.
+#[derive(Debug)]
+struct V2Layout {
+    version: u8,
+    meta: MetaV2,
+}
+
+impl MetaInfo for V2Layout {
+    fn tps(&self) -> f32 {
+        self.meta.tps()
+    }
+
+    fn tps_dt(&self) -> f32 {
+        self.meta.tps_dt()
+    }
+
+    fn uses_dt(&self) -> bool {
+        self.meta.uses_dt()
+    }
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn rng_seed(&self) -> Option<u64> {
+        self.meta.rng_seed()
+    }
+
+    fn is_rng_seed_set(&self) -> bool {
+        self.meta.is_rng_seed_set()
+    }
+
+    fn append_counter(&self) -> u8 {
+        self.meta.append_counter()
+    }
+}
+
+impl CustomLayout for V2Layout {
+    fn to_bytes(&self) -> Box<[u8]> {
+        let mut bytes = self.meta.to_bytes();
+        bytes[0] = self.version;
+        bytes
+    }
+}
+
+/// Metadata of any supported format version.
+///
+/// This is the metadata of a [`DynamicReplay`](crate::DynamicReplay): reading picks the
+/// variant from the version byte, and matching on it reaches the fields specific to a
+/// version. Versions plugged in through [`codec::register`] read as [`AnyMeta::Custom`].
+///
+/// [`codec::register`]: crate::codec::register
+///
+/// # Example
+/// ```
+/// use tcm::meta::{AnyMeta, MetaInfo};
+/// use tcm::DynamicReplay;
+///
+/// let bytes = std::fs::read("examples/data/restartv2.tcm").unwrap();
+/// let replay = DynamicReplay::from_bytes(&bytes).unwrap();
+/// match &replay.meta {
+///     AnyMeta::V1(meta) => println!("v1 at {} TPS", meta.tps),
+///     AnyMeta::V2(meta) => println!("v2 with seed {:?}", meta.rng_seed),
+///     AnyMeta::Custom(meta) => println!("registered v{}", meta.version()),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnyMeta {
+    V1(MetaV1),
+    V2(MetaV2),
+    Custom(CustomMeta),
+}
+
+impl AnyMeta {
+    fn inner(&self) -> &dyn MetaInfo {
+        match self {
+            AnyMeta::V1(meta) => meta,
+            AnyMeta::V2(meta) => meta,
+            AnyMeta::Custom(meta) => meta,
+        }
+    }
+}
+
+impl MetaInfo for AnyMeta {
+    fn tps(&self) -> f32 {
+        self.inner().tps()
+    }
+
+    fn tps_dt(&self) -> f32 {
+        self.inner().tps_dt()
+    }
+
+    fn uses_dt(&self) -> bool {
+        self.inner().uses_dt()
+    }
+
+    fn version(&self) -> u8 {
+        self.inner().version()
+    }
+
+    fn rng_seed(&self) -> Option<u64> {
+        self.inner().rng_seed()
+    }
+
+    fn is_rng_seed_set(&self) -> bool {
+        self.inner().is_rng_seed_set()
+    }
+
+    fn append_counter(&self) -> u8 {
+        self.inner().append_counter()
+    }
+}
+
+impl MetaFormat for AnyMeta {
+    const FORMAT_VERSION: Option<u8> = None;
+
+    fn codec() -> Option<Arc<dyn InputCodec>> {
+        None
+    }
+
+    fn from_bytes(bytes: &[u8]) -> TcmResult<Self> {
+        codec::read_meta(bytes)
+    }
+
+    fn to_bytes(&self) -> Box<[u8]> {
+        match self {
+            AnyMeta::V1(meta) => meta.to_bytes(),
+            AnyMeta::V2(meta) => meta.to_bytes(),
+            AnyMeta::Custom(meta) => meta.to_bytes(),
+        }
+    }
+
+    fn validate_bytes(bytes: &[u8]) -> TcmResult<()> {
+        codec::validate_meta(bytes)
+    }
+
+    fn new_empty(tps: f32) -> Self {
+        AnyMeta::V2(MetaV2::new_empty(tps))
+    }
+}
+
+impl From<MetaV1> for AnyMeta {
+    fn from(meta: MetaV1) -> Self {
+        AnyMeta::V1(meta)
+    }
+}
+
+impl From<MetaV2> for AnyMeta {
+    fn from(meta: MetaV2) -> Self {
+        AnyMeta::V2(meta)
+    }
+}
+
+impl From<CustomMeta> for AnyMeta {
+    fn from(meta: CustomMeta) -> Self {
+        AnyMeta::Custom(meta)
+    }
 }
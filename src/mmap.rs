@@ -0,0 +1,105 @@
+//! Memory-mapped opening of replay files.
+//!
+//! The file is mapped into memory and decoded straight from the mapping, so even replays
+//! with millions of inputs are never copied into a heap buffer first.
+//!
+//! The mapping reflects the file as it is on disk: if another process truncates or
+//! rewrites the file while it is mapped, reads may fault or see partially written data.
+//! Only map files that aren't modified while a [`MappedReplay`] is alive.
+
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::{
+    error::TcmResult, meta::MetaFormat, options::ReadOptions, replay::Replay, stream::InputStream,
+};
+
+/// A replay file mapped into memory.
+///
+/// Decodes either eagerly into a [`Replay`] or lazily through an [`InputStream`] borrowing
+/// the mapping.
+///
+/// # Example
+/// ```
+/// use tcm::mmap::MappedReplay;
+/// use tcm::meta::MetaV1;
+///
+/// # fn main() -> tcm::TcmResult<()> {
+/// let mapped = MappedReplay::open("examples/data/restartv1.tcm")?;
+/// let restarts = mapped
+///     .inputs::<MetaV1>()?
+///     .filter(|input| matches!(input, Ok(i) if matches!(i.input, tcm::input::Input::Restart(_))))
+///     .count();
+/// println!("{} restarts", restarts);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MappedReplay {
+    map: Mmap,
+}
+
+impl MappedReplay {
+    /// Maps the file at `path` read-only.
+    pub fn open(path: impl AsRef<Path>) -> TcmResult<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only; the module docs cover files changing under it.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self { map })
+    }
+
+    /// The mapped file contents.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Decodes the whole replay.
+    pub fn replay<M: MetaFormat>(&self) -> TcmResult<Replay<M>> {
+        self.replay_with_options(&ReadOptions::default())
+    }
+
+    /// Decodes the whole replay with the given [`ReadOptions`].
+    pub fn replay_with_options<M: MetaFormat>(
+        &self,
+        options: &ReadOptions,
+    ) -> TcmResult<Replay<M>> {
+        Replay::from_bytes_with_options(self.as_bytes(), options)
+    }
+
+    /// Lazily decodes inputs from the mapping, one at a time.
+    pub fn inputs<M: MetaFormat>(&self) -> TcmResult<InputStream<&[u8], M>> {
+        self.inputs_with_options(&ReadOptions::default())
+    }
+
+    /// Like [`MappedReplay::inputs`], applying the given [`ReadOptions`] to the whole stream.
+    pub fn inputs_with_options<M: MetaFormat>(
+        &self,
+        options: &ReadOptions,
+    ) -> TcmResult<InputStream<&[u8], M>> {
+        InputStream::with_options(self.as_bytes(), options)
+    }
+}
+
+impl<M: MetaFormat> Replay<M> {
+    /// Maps the file at `path` and decodes the replay from the mapping.
+    ///
+    /// # Example
+    /// ```
+    /// use tcm::{DynamicReplay, meta::MetaInfo};
+    ///
+    /// # fn main() -> tcm::TcmResult<()> {
+    /// let replay = DynamicReplay::open("examples/data/longv2.tcm")?;
+    /// println!("TCM v{}: {} inputs", replay.meta.version(), replay.inputs.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open(path: impl AsRef<Path>) -> TcmResult<Self> {
+        MappedReplay::open(path)?.replay()
+    }
+
+    /// Maps the file at `path` and decodes the replay with the given [`ReadOptions`].
+    pub fn open_with_options(path: impl AsRef<Path>, options: &ReadOptions) -> TcmResult<Self> {
+        MappedReplay::open(path)?.replay_with_options(options)
+    }
+}
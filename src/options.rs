@@ -0,0 +1,153 @@
+//! Options controlling how replays are read.
+
+use crate::{
+    error::{TcmError, TcmResult},
+    input::{Input, InputCommand},
+    meta::check_tps,
+    Frame,
+};
+
+/// Options for reading replays.
+///
+/// The default is the permissive behaviour of [`ReplayDeserializer::deserialize`].
+///
+/// [`ReplayDeserializer::deserialize`]: crate::replay::ReplayDeserializer::deserialize
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadOptions {
+    /// Reject anything that isn't exactly the canonical binary layout: unknown metadata
+    /// flags, non-zero reserved bytes, non-positive or NaN TPS values, varints longer
+    /// than their value needs, a missing v1 end of macro marker, truncated deltas and
+    /// trailing data.
+    pub strict: bool,
+    /// Resource limits applied while decoding the input stream
+    pub limits: ReadLimits,
+}
+
+impl ReadOptions {
+    /// Options for strict reading, see [`ReadOptions::strict`](#structfield.strict).
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the resource limits.
+    pub fn with_limits(mut self, limits: ReadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+/// Caps on how much a single replay may make the reader do.
+///
+/// Every limit is off (`None`) by default. Set them when parsing files from untrusted
+/// sources so a tiny file can't ask for huge allocations or endless decoding.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadLimits {
+    /// Maximum number of inputs, including the count announced by a v1 header
+    pub max_inputs: Option<usize>,
+    /// Maximum frame number of any input
+    pub max_frame: Option<Frame>,
+    /// Maximum number of TPS change inputs
+    pub max_tps_changes: Option<usize>,
+    /// Maximum number of bytes read for a single replay, header included
+    pub max_bytes: Option<u64>,
+}
+
+/// The resource limit that a replay exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceLimit {
+    Inputs,
+    Frame,
+    TpsChanges,
+    Bytes,
+}
+
+#[inline]
+fn check_limit(limit: ResourceLimit, max: Option<u64>, value: u64) -> TcmResult<()> {
+    match max {
+        Some(max) if value > max => Err(TcmError::LimitExceeded { limit, max }),
+        _ => Ok(()),
+    }
+}
+
+/// Counts decoded data against a set of [`ReadLimits`].
+#[derive(Default)]
+pub(crate) struct LimitTracker {
+    limits: ReadLimits,
+    inputs: usize,
+    tps_changes: usize,
+}
+
+impl LimitTracker {
+    pub(crate) fn new(limits: &ReadLimits) -> Self {
+        Self {
+            limits: limits.clone(),
+            inputs: 0,
+            tps_changes: 0,
+        }
+    }
+
+    /// Checks an input count announced up front, before anything is allocated for it.
+    #[inline]
+    pub(crate) fn check_count(&self, count: usize) -> TcmResult<()> {
+        let max = self.limits.max_inputs.map(|max| max as u64);
+        check_limit(ResourceLimit::Inputs, max, count as u64)
+    }
+
+    /// Number of inputs that can still be decoded without exceeding the input limit.
+    #[inline]
+    pub(crate) fn inputs_left(&self) -> usize {
+        match self.limits.max_inputs {
+            Some(max) => max.saturating_sub(self.inputs),
+            None => usize::MAX,
+        }
+    }
+
+    /// Counts `count` decoded inputs at once.
+    #[inline]
+    pub(crate) fn add_inputs(&mut self, count: usize) -> TcmResult<()> {
+        self.inputs = self.inputs.saturating_add(count);
+        self.check_count(self.inputs)
+    }
+
+    /// The highest frame an input may have.
+    #[inline]
+    pub(crate) fn max_frame(&self) -> Frame {
+        self.limits.max_frame.unwrap_or(Frame::MAX)
+    }
+
+    /// Runs the checks on an input that don't depend on how many came before it, counting
+    /// TPS changes; strict reads also reject invalid TPS values.
+    ///
+    /// The input count is left to [`LimitTracker::add_inputs`].
+    #[inline]
+    pub(crate) fn check_contents(&mut self, input: &InputCommand, strict: bool) -> TcmResult<()> {
+        check_limit(ResourceLimit::Frame, self.limits.max_frame, input.frame)?;
+        if let Input::Tps(tps) = &input.input {
+            self.tps_changes += 1;
+            let max = self.limits.max_tps_changes.map(|max| max as u64);
+            check_limit(ResourceLimit::TpsChanges, max, self.tps_changes as u64)?;
+            if strict {
+                check_tps(tps.tps)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn check_bytes(&self, offset: u64) -> TcmResult<()> {
+        check_limit(ResourceLimit::Bytes, self.limits.max_bytes, offset)
+    }
+
+    /// Number of bytes that can still be read after `offset` without exceeding the byte
+    /// limit.
+    #[inline]
+    pub(crate) fn bytes_left(&self, offset: u64) -> u64 {
+        match self.limits.max_bytes {
+            Some(max) => max.saturating_sub(offset),
+            None => u64::MAX,
+        }
+    }
+}
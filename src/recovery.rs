@@ -0,0 +1,149 @@
+//! Lenient decoding that salvages truncated or corrupt replays.
+
+use std::io::Read;
+
+use crate::{
+    error::{TcmError, TcmResult},
+    meta::MetaFormat,
+    options::ReadOptions,
+    replay::Replay,
+    stream::InputStream,
+};
+
+/// What went wrong at a point where lenient decoding gave up or skipped data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecoveryDiagnosticKind {
+    /// The stream ended in the middle of a blob
+    Truncated { context: String },
+    /// Invalid data stopped decoding
+    Corrupt { message: String },
+    /// A v1 stream held fewer inputs than its header announced
+    MissingInputs { expected: usize, found: usize },
+    /// Bytes following the stopping point that were not decoded
+    SkippedBytes { count: u64 },
+}
+
+/// A single finding of a lenient decode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveryDiagnostic {
+    /// Byte offset, from the start of the replay, the finding refers to
+    pub offset: u64,
+    /// Number of inputs decoded before this point
+    pub input_index: usize,
+    /// What happened
+    pub kind: RecoveryDiagnosticKind,
+}
+
+impl RecoveryDiagnostic {
+    pub(crate) fn from_error(err: &TcmError) -> Self {
+        let (offset, input_index) = match err {
+            TcmError::Decode {
+                offset,
+                input_index,
+                ..
+            } => (*offset, *input_index),
+            _ => (0, 0),
+        };
+
+        let kind = match err.root_cause() {
+            TcmError::UnexpectedEof { context } => RecoveryDiagnosticKind::Truncated {
+                context: context.clone(),
+            },
+            cause => RecoveryDiagnosticKind::Corrupt {
+                message: cause.to_string(),
+            },
+        };
+
+        Self {
+            offset,
+            input_index,
+            kind,
+        }
+    }
+}
+
+/// The outcome of [`Replay::recover`].
+#[derive(Debug, Clone)]
+pub struct RecoveredReplay<M: MetaFormat> {
+    /// The replay holding the longest valid prefix of inputs
+    pub replay: Replay<M>,
+    /// Everything that was truncated or skipped, in stream order
+    pub diagnostics: Vec<RecoveryDiagnostic>,
+    /// Byte offset, from the start of the replay, where decoding stopped
+    pub stopped_at: u64,
+}
+
+impl<M: MetaFormat> RecoveredReplay<M> {
+    /// Returns true if the whole stream decoded without any findings.
+    pub fn is_complete(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+impl<M: MetaFormat> Replay<M> {
+    /// Parse a replay leniently, keeping every input decoded before the first problem.
+    ///
+    /// The header and metadata still have to be valid. Anything wrong in the input
+    /// stream is reported through [`RecoveredReplay::diagnostics`] instead of failing.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::fs::File;
+    /// use tcm::{meta::MetaV2, Replay};
+    ///
+    /// let mut file = File::open("crashed.tcm").unwrap();
+    /// let recovered = Replay::<MetaV2>::recover(&mut file).unwrap();
+    /// println!("Salvaged {} inputs", recovered.replay.inputs.len());
+    /// for diagnostic in &recovered.diagnostics {
+    ///     println!("{:?}", diagnostic);
+    /// }
+    /// ```
+    pub fn recover<R: Read>(reader: &mut R) -> TcmResult<RecoveredReplay<M>> {
+        let mut stream = InputStream::<_, M>::open(reader, &ReadOptions::default(), true)?;
+        let mut diagnostics = Vec::new();
+        let mut inputs = Vec::new();
+
+        while let Some(input) = stream.next() {
+            match input {
+                Ok(input) => inputs.push(input),
+                // Anything but a decode failure, like a resource limit, stays fatal.
+                Err(err @ TcmError::Decode { .. }) => {
+                    diagnostics.push(RecoveryDiagnostic::from_error(&err));
+                    match stream.core_mut().announced() {
+                        Some(expected) if inputs.len() < expected => {
+                            diagnostics.push(RecoveryDiagnostic {
+                                offset: diagnostics[0].offset,
+                                input_index: inputs.len(),
+                                kind: RecoveryDiagnosticKind::MissingInputs {
+                                    expected,
+                                    found: inputs.len(),
+                                },
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let offset = stream.offset();
+        let stopped_at = diagnostics.first().map_or(offset, |d| d.offset);
+        let skipped = offset - stopped_at
+            + std::io::copy(stream.core_mut().reader_mut(), &mut std::io::sink())?;
+        if skipped > 0 {
+            diagnostics.push(RecoveryDiagnostic {
+                offset: stopped_at,
+                input_index: inputs.len(),
+                kind: RecoveryDiagnosticKind::SkippedBytes { count: skipped },
+            });
+        }
+        let meta = stream.into_meta();
+
+        Ok(RecoveredReplay {
+            replay: Replay { meta, inputs },
+            diagnostics,
+            stopped_at,
+        })
+    }
+}
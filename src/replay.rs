@@ -1,28 +1,38 @@
 //! Replay serialization and deserialization.
 
-use std::io::{Read, Seek, Write};
+use std::io::{Chain, Cursor, Read, Write};
 
 use crate::{
-    input::{BugpointInput, Input, InputCommand, PlayerButton, RestartInput, VanillaInput},
-    meta::{Meta, MetaV1, MetaV2},
+    codec::Codec,
+    error::{TcmError, TcmResult},
+    input::{Input, InputCommand},
+    meta::{AnyMeta, MetaFormat, MetaInfo, MetaV1, MetaV2},
+    options::ReadOptions,
+    stream::{InputStream, StreamCore},
     Frame,
 };
 
-pub trait ReplaySerializer<W: Write + Seek> {
-    fn serialize(&self, writer: &mut W) -> std::io::Result<()>;
+pub use v2::DeserializerBlob;
+
+pub trait ReplaySerializer<W: Write> {
+    fn serialize(&self, writer: &mut W) -> TcmResult<()>;
 }
 
-pub trait ReplayDeserializer<R: Read + Seek, M: Meta> {
-    fn deserialize(reader: &mut R) -> std::io::Result<Replay<M>>;
+pub trait ReplayDeserializer<R: Read, M: MetaFormat> {
+    fn deserialize(reader: &mut R) -> TcmResult<Replay<M>> {
+        Self::deserialize_with_options(reader, &ReadOptions::default())
+    }
+
+    fn deserialize_with_options(reader: &mut R, options: &ReadOptions) -> TcmResult<Replay<M>>;
 }
 
-#[derive(Debug, Clone)]
-pub struct Replay<M: Meta> {
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay<M: MetaFormat> {
     pub meta: M,
     pub inputs: Vec<InputCommand>,
 }
 
-impl<M: Meta> Replay<M> {
+impl<M: MetaFormat> Replay<M> {
     pub fn new_empty(tps: f32) -> Self {
         Self {
             meta: M::new_empty(tps),
@@ -33,20 +43,62 @@ impl<M: Meta> Replay<M> {
     pub fn new(meta: M, inputs: Vec<InputCommand>) -> Self {
         Self { meta, inputs }
     }
-}
 
-trait InternalSerializer<W: Write + Seek> {
-    fn serialize_inputs_v1(&self, writer: &mut W) -> std::io::Result<()>;
-    fn serialize_inputs_v2(&self, writer: &mut W) -> std::io::Result<()>;
-}
+    /// Parse a replay held in memory.
+    ///
+    /// The decoder runs over the slice directly instead of copying the input stream out
+    /// of a reader first, as [`ReplayDeserializer::deserialize`] does with a [`Cursor`].
+    /// That isn't much faster: most of the time goes into building the `Vec` of inputs,
+    /// which both pay for.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tcm::{meta::MetaV2, Replay};
+    ///
+    /// let bytes = std::fs::read("replay.tcm").unwrap();
+    /// let replay = Replay::<MetaV2>::from_bytes(&bytes).unwrap();
+    /// println!("{} inputs", replay.inputs.len());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> TcmResult<Self> {
+        Self::from_bytes_with_options(bytes, &ReadOptions::default())
+    }
+
+    /// Parse a replay held in memory with the given [`ReadOptions`].
+    pub fn from_bytes_with_options(bytes: &[u8], options: &ReadOptions) -> TcmResult<Self> {
+        let mut stream = StreamCore::<_, M>::open(SliceReader::new(bytes), options, false)?;
+        // Inputs average well over two bytes each, and the estimate is never trusted
+        // beyond the input limit.
+        let estimate = bytes.len().saturating_sub(PREFIX_SIZE) / 2;
+        let capacity = match options.limits.max_inputs {
+            Some(max) => estimate.min(max),
+            None => estimate,
+        };
+        let mut inputs = Vec::with_capacity(capacity);
+        stream.read_to_end(&mut inputs)?;
+
+        Ok(Replay {
+            meta: stream.into_meta(),
+            inputs,
+        })
+    }
 
-trait InternalDeserializer<R: Read + Seek> {
-    fn deserialize_inputs_v1(reader: &mut R) -> std::io::Result<Vec<InputCommand>>;
-    fn deserialize_inputs_v2(reader: &mut R) -> std::io::Result<Vec<InputCommand>>;
+    /// Serialize the replay into a new buffer.
+    pub fn to_bytes(&self) -> TcmResult<Vec<u8>> {
+        // Most inputs take two bytes or fewer.
+        let mut bytes = Vec::with_capacity(PREFIX_SIZE + 2 * self.inputs.len());
+        self.serialize(&mut bytes)?;
+        Ok(bytes)
+    }
 }
 
-mod v1 {
-    use crate::input::{Input, RestartInput, RestartType, VanillaInput};
+pub(crate) mod v1 {
+    use std::io::Write;
+
+    use super::write_var_u32;
+    use crate::{
+        error::{TcmError, TcmResult},
+        input::{Input, InputCommand, RestartInput, RestartType, VanillaInput},
+    };
 
     pub const INPUT_MASK: u8 = 0b111;
     pub const PUSH_OFFSET: u8 = 7;
@@ -71,6 +123,15 @@ mod v1 {
         }
     }
 
+    /// Writes the frame and input byte of a single v1 input.
+    pub fn write_input(writer: &mut impl Write, input: &InputCommand) -> TcmResult<()> {
+        let data = serialize_input(&input.input)
+            .ok_or_else(|| TcmError::invalid_input("Unsupported input type in v1 replay"))?;
+        write_var_u32(writer, input.frame, "v1 input frame")?;
+        writer.write_all(&[data])?;
+        Ok(())
+    }
+
     pub fn deserialize_input(data: u8) -> Option<Input> {
         let input = data & INPUT_MASK;
         if input < 3 {
@@ -99,25 +160,157 @@ mod v1 {
     }
 }
 
-/// Reads a variable-length u32 from a reader using LEB128 encoding.
-fn read_var_u32(reader: &mut (impl Read + Seek)) -> std::io::Result<u32> {
-    let mut value = 0u32;
-    let mut shift = 0usize;
-    let mut buf = [0u8; 1];
+/// Maps a short read to [`TcmError::UnexpectedEof`], keeping other I/O errors as-is.
+pub(crate) fn map_eof(err: std::io::Error, context: &str) -> TcmError {
+    if err.kind() == std::io::ErrorKind::UnexpectedEof {
+        TcmError::unexpected_eof(context)
+    } else {
+        TcmError::Io(err)
+    }
+}
+
+/// Anything the decoders can take bytes from.
+pub(crate) trait Source {
+    /// Fills `buf` completely, or fails with [`TcmError::UnexpectedEof`].
+    fn read_exact_into(&mut self, buf: &mut [u8], context: &str) -> TcmResult<()>;
+
+    /// Reads exactly `N` bytes.
+    #[inline]
+    fn read_array<const N: usize>(&mut self, context: &str) -> TcmResult<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.read_exact_into(&mut buf, context)?;
+        Ok(buf)
+    }
+}
+
+impl<R: Read> Source for R {
+    #[inline]
+    fn read_exact_into(&mut self, buf: &mut [u8], context: &str) -> TcmResult<()> {
+        self.read_exact(buf).map_err(|e| map_eof(e, context))
+    }
+}
+
+/// A source over an in-memory replay, indexing the slice directly instead of going
+/// through [`Read`].
+pub(crate) struct SliceReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The bytes that haven't been read yet.
+    pub(crate) fn rest(&self) -> &[u8] {
+        &self.bytes[self.position..]
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) {
+        self.position += len;
+    }
+}
+
+impl Source for SliceReader<'_> {
+    fn read_exact_into(&mut self, buf: &mut [u8], context: &str) -> TcmResult<()> {
+        let end = self.position.saturating_add(buf.len());
+        match self.bytes.get(self.position..end) {
+            Some(bytes) => {
+                buf.copy_from_slice(bytes);
+                self.position = end;
+                Ok(())
+            }
+            None => Err(self.short_read(context)),
+        }
+    }
+
+    #[inline(always)]
+    fn read_array<const N: usize>(&mut self, context: &str) -> TcmResult<[u8; N]> {
+        let end = self.position.saturating_add(N);
+        match self.bytes.get(self.position..end) {
+            Some(bytes) => {
+                self.position = end;
+                let mut buf = [0u8; N];
+                buf.copy_from_slice(bytes);
+                Ok(buf)
+            }
+            None => Err(self.short_read(context)),
+        }
+    }
+}
+
+impl SliceReader<'_> {
+    /// Like a reader, a short read consumes what is left.
+    #[cold]
+    fn short_read(&mut self, context: &str) -> TcmError {
+        self.position = self.bytes.len();
+        TcmError::unexpected_eof(context)
+    }
+}
+
+/// Reads exactly `N` bytes from a source.
+#[inline(always)]
+pub(crate) fn read_array<const N: usize>(
+    reader: &mut impl Source,
+    context: &str,
+) -> TcmResult<[u8; N]> {
+    reader.read_array(context)
+}
+
+/// Reads a variable-length u64 from a reader using LEB128 encoding.
+pub(crate) fn read_var_u64(reader: &mut impl Source, context: &str) -> TcmResult<u64> {
+    read_var(reader, context, false)
+}
+
+/// Reads a LEB128 varint; `strict` rejects encodings with trailing zero groups.
+#[inline(always)]
+fn read_var(reader: &mut impl Source, context: &str, strict: bool) -> TcmResult<u64> {
+    let mut value = 0u64;
+    let mut shift = 0u32;
 
     loop {
-        reader.read_exact(&mut buf)?;
-        let byte = buf[0];
-        value |= ((byte & 0x7F) as u32) << shift;
+        let [byte] = read_array(reader, context)?;
+        let bits = (byte & 0x7F) as u64;
+        // Reject groups that would shift set bits past the top of the value.
+        if shift >= u64::BITS || (shift > 0 && bits >> (u64::BITS - shift) != 0) {
+            return Err(TcmError::VarintOverflow {
+                context: context.to_string(),
+            });
+        }
+        value |= bits << shift;
         if byte & 0x80 == 0 {
+            if strict && byte == 0 && shift > 0 {
+                return Err(TcmError::OverlongVarint {
+                    context: context.to_string(),
+                });
+            }
             return Ok(value);
         }
         shift += 7;
     }
 }
 
-/// Writes a variable-length u32 to a writer using LEB128 encoding.
-fn write_var_u32(writer: &mut (impl Write + Seek), mut value: u32) -> std::io::Result<()> {
+/// Reads a variable-length u32 from a reader using LEB128 encoding.
+///
+/// Strict reads refuse encodings longer than the value needs.
+pub(crate) fn read_var_u32(
+    reader: &mut impl Source,
+    context: &str,
+    strict: bool,
+) -> TcmResult<u32> {
+    let value = read_var(reader, context, strict)?;
+    u32::try_from(value).map_err(|_| TcmError::VarintOverflow {
+        context: context.to_string(),
+    })
+}
+
+/// Writes a variable-length u64 to a writer using LEB128 encoding.
+pub(crate) fn write_var_u64(writer: &mut impl Write, mut value: u64) -> TcmResult<()> {
     let mut buf = [0u8; 1];
 
     loop {
@@ -133,11 +326,29 @@ fn write_var_u32(writer: &mut (impl Write + Seek), mut value: u32) -> std::io::R
     }
 }
 
-mod v2 {
-    use std::io::{Read, Seek, Write};
+/// Writes a value to a u32 varint field, failing if it doesn't fit.
+pub(crate) fn write_var_u32(writer: &mut impl Write, value: u64, field: &str) -> TcmResult<()> {
+    if value > u32::MAX as u64 {
+        return Err(TcmError::FrameOutOfRange {
+            value,
+            field: field.to_string(),
+        });
+    }
+    write_var_u64(writer, value)
+}
 
+pub(crate) mod v2 {
+    use std::io::Write;
+
+    use super::{read_var_u32, write_var_u32};
     use crate::{
-        input::{Input, RestartInput, VanillaInput},
+        error::{TcmError, TcmResult},
+        input::{
+            BugpointInput, Input, InputCommand, PlayerButton, RestartInput, RestartType, TpsInput,
+            VanillaInput,
+        },
+        options::{LimitTracker, ResourceLimit},
+        stream::ByteSource,
         Frame,
     };
 
@@ -195,14 +406,20 @@ mod v2 {
             }
         }
 
-        pub fn serialize(self, writer: &mut impl Write, value: Frame) -> std::io::Result<()> {
-            assert!(value <= self.max());
+        pub fn serialize(self, writer: &mut impl Write, value: Frame) -> TcmResult<()> {
+            if value > self.max() {
+                return Err(TcmError::serialization_error(format!(
+                    "Value {} does not fit in {:?} byte blob",
+                    value, self
+                )));
+            }
             match self {
-                ByteBlob::Zero => Ok(()),
-                ByteBlob::One => writer.write_all(&[value as u8]),
-                ByteBlob::Two => writer.write_all(&(value as u16).to_le_bytes()),
-                ByteBlob::Four => writer.write_all(&(value as u32).to_le_bytes()),
+                ByteBlob::Zero => {}
+                ByteBlob::One => writer.write_all(&[value as u8])?,
+                ByteBlob::Two => writer.write_all(&(value as u16).to_le_bytes())?,
+                ByteBlob::Four => writer.write_all(&(value as u32).to_le_bytes())?,
             }
+            Ok(())
         }
 
         pub fn iter() -> impl Iterator<Item = Self> {
@@ -219,14 +436,14 @@ mod v2 {
     }
 
     impl SerializerDeltaInfo {
-        pub fn new(delta: Frame, last_delta: Frame) -> Self {
+        pub fn new(delta: Frame, last_delta: Frame) -> TcmResult<Self> {
             for byte in ByteBlob::iter() {
                 if delta <= byte.max() {
-                    return Self {
+                    return Ok(Self {
                         blob: byte,
                         magic: None,
                         delta,
-                    };
+                    });
                 }
 
                 if last_delta == 0 || last_delta > delta {
@@ -235,15 +452,18 @@ mod v2 {
 
                 let magic = delta - last_delta;
                 if magic <= byte.max() {
-                    return Self {
+                    return Ok(Self {
                         blob: byte,
                         magic: Some(last_delta),
                         delta,
-                    };
+                    });
                 }
             }
 
-            panic!("Delta too big: {} (last: {})", delta, last_delta);
+            Err(TcmError::FrameOutOfRange {
+                value: delta,
+                field: format!("v2 frame delta (last delta {})", last_delta),
+            })
         }
 
         pub fn empty(&self) -> bool {
@@ -258,10 +478,9 @@ mod v2 {
             (self.magic() as u8) | ((self.blob as u8) << 1)
         }
 
-        pub fn serialize(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        pub fn serialize(&self, writer: &mut impl Write) -> TcmResult<()> {
             if self.empty() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
+                return Err(TcmError::serialization_error(
                     "Cannot serialize empty delta",
                 ));
             }
@@ -285,50 +504,135 @@ mod v2 {
         }
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq)]
-    pub enum SerializerBlob {
-        Action,
-        FrameDelta,
-        Tps(f32),
-        Seed(u64),
+    pub fn serialize_input(input: &Input, swift: bool) -> TcmResult<u8> {
+        let (t, extra) = match input {
+            Input::Vanilla(VanillaInput {
+                button,
+                push,
+                player2,
+            }) => return Ok(craft_input(*button as u8, *push, *player2, swift)),
+            Input::Restart(RestartInput {
+                restart_type,
+                new_seed,
+            }) => (*restart_type as u8, new_seed.is_some()),
+            Input::Tps(_) => (3, false),
+            Input::Bugpoint(_) => (3, true),
+        };
+
+        if swift {
+            return Err(TcmError::serialization_error(
+                "Non-vanilla inputs cannot be swift",
+            ));
+        }
+
+        Ok((t | (extra as u8) << 2) << CUSTOM_OFFSET)
     }
 
-    pub fn serialize_input(input: &Input, swift: bool) -> u8 {
-        if let Input::Vanilla(VanillaInput {
-            button,
-            push,
-            player2,
-        }) = input
-        {
-            return craft_input(*button as u8, *push, *player2, swift);
+    /// Returns true if `next` undoes `input` on the same frame, so both fit in one swift
+    /// action byte.
+    pub fn is_swift_pair(input: &InputCommand, next: &InputCommand) -> bool {
+        if next.frame != input.frame {
+            return false;
         }
 
-        assert!(!swift, "Non-vanilla inputs cannot be swift");
+        match (&input.input, &next.input) {
+            (Input::Vanilla(a), Input::Vanilla(b)) => {
+                a.button == b.button && a.push != b.push && a.player2 == b.player2
+            }
+            _ => false,
+        }
+    }
 
-        let t: u8;
-        let mut extra = false;
+    /// Incremental encoder for the v2 input stream.
+    ///
+    /// An action byte announces the size of the delta to the following input, so the
+    /// encoder holds back the latest input until the next one (or the end) is known.
+    #[derive(Default)]
+    pub struct Encoder {
+        last_delta: Frame,
+        held: Option<(InputCommand, bool)>,
+        started: bool,
+    }
 
-        match input {
-            Input::Restart(RestartInput {
-                restart_type,
-                new_seed,
-            }) => {
-                t = *restart_type as u8;
-                extra = new_seed.is_some();
+    impl Encoder {
+        /// An encoder continuing a stream whose last action byte encoded `input`.
+        ///
+        /// `last_delta` is the delta state from before that action byte, which is
+        /// written again with the delta to whatever input comes next.
+        pub fn resume(last_delta: Frame, input: InputCommand, swift: bool) -> Self {
+            Self {
+                last_delta,
+                held: Some((input, swift)),
+                started: true,
+            }
+        }
+
+        pub fn push(&mut self, writer: &mut impl Write, input: &InputCommand) -> TcmResult<()> {
+            if !self.started {
+                write_var_u32(writer, input.frame, "v2 initial frame")?;
+                self.started = true;
             }
-            Input::Tps(_) => {
-                t = 3;
+
+            match self.held.take() {
+                Some((held, false)) if is_swift_pair(&held, input) => {
+                    self.held = Some((held, true));
+                }
+                Some((held, swift)) => {
+                    self.encode(writer, &held, swift, Some(input))?;
+                    self.held = Some((input.clone(), false));
+                }
+                None => self.held = Some((input.clone(), false)),
             }
-            Input::Bugpoint(_) => {
-                t = 3;
-                extra = true;
+            Ok(())
+        }
+
+        /// Writes the held back input, if any.
+        pub fn finish(&mut self, writer: &mut impl Write) -> TcmResult<()> {
+            match self.held.take() {
+                Some((held, swift)) => self.encode(writer, &held, swift, None),
+                None => Ok(()),
             }
-            _ => unreachable!(),
         }
 
-        (t | (extra as u8) << 2) << CUSTOM_OFFSET
+        fn encode(
+            &mut self,
+            writer: &mut impl Write,
+            input: &InputCommand,
+            swift: bool,
+            next: Option<&InputCommand>,
+        ) -> TcmResult<()> {
+            let next_delta = match next {
+                Some(next) => {
+                    let delta = next.frame.saturating_sub(input.adjusted_frame());
+                    SerializerDeltaInfo::new(delta, self.last_delta)?
+                }
+                None => SerializerDeltaInfo::default(),
+            };
+
+            if next_delta.delta != 0 {
+                self.last_delta = next_delta.delta;
+            }
+
+            let data = serialize_input(&input.input, swift)?;
+            writer.write_all(&[data | (next_delta.craft() << DELTA_OFFSET)])?;
+
+            match &input.input {
+                Input::Tps(tps) => writer.write_all(&tps.tps.to_le_bytes())?,
+                Input::Restart(RestartInput {
+                    new_seed: Some(seed),
+                    ..
+                }) => writer.write_all(&seed.to_le_bytes())?,
+                _ => {}
+            }
+
+            if !next_delta.empty() {
+                next_delta.serialize(writer)?;
+            }
+            Ok(())
+        }
     }
 
+    #[derive(Clone, Copy)]
     pub struct DeserializerDeltaInfo {
         pub blob: ByteBlob,
         pub last_delta: Option<Frame>,
@@ -343,463 +647,626 @@ mod v2 {
         }
     }
 
+    /// The kind of blob the v2 decoder expects to read next.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum DeserializerBlob {
+        /// An action byte carrying the input and the size of the following delta
         Action,
+        /// The frame delta announced by the previous action byte
         FrameDelta,
+        /// The 4-byte TPS value of a TPS input
         Tps,
+        /// The 8-byte seed of a restart input
         Seed,
     }
 
     impl DeserializerDeltaInfo {
+        #[inline(always)]
         pub fn new(data: u8, last_delta: Frame) -> Self {
-            let bytes = (data >> 1) & 0b11;
+            let blob = match (data >> 1) & 0b11 {
+                0 => ByteBlob::Zero,
+                1 => ByteBlob::One,
+                2 => ByteBlob::Two,
+                _ => ByteBlob::Four,
+            };
             let magic = (data & 1) != 0;
 
             Self {
-                blob: bytes.try_into().unwrap(),
+                blob,
                 last_delta: if magic { Some(last_delta) } else { None },
             }
         }
 
-        pub fn empty(&self) -> bool {
-            self.blob == ByteBlob::Zero
+        /// Reads the delta from the start of `bytes`, or returns `None` if they are too
+        /// short.
+        ///
+        /// Returns the delta and the number of bytes it took, or an error message if the
+        /// delta overflows.
+        #[inline(always)]
+        fn read(&self, bytes: &[u8]) -> Option<(Result<Frame, &'static str>, usize)> {
+            let (value, len) = match self.blob {
+                ByteBlob::Zero => (0, 0),
+                ByteBlob::One => (*bytes.first()? as Frame, 1),
+                ByteBlob::Two => match bytes {
+                    [a, b, ..] => (u16::from_le_bytes([*a, *b]) as Frame, 2),
+                    _ => return None,
+                },
+                ByteBlob::Four => match bytes {
+                    [a, b, c, d, ..] => (u32::from_le_bytes([*a, *b, *c, *d]) as Frame, 4),
+                    _ => return None,
+                },
+            };
+            // Empty delta: magic returns last_delta, non-magic returns 0
+            let base = self.last_delta.unwrap_or(0);
+            Some((base.checked_add(value).ok_or("Frame delta overflow"), len))
         }
+    }
 
-        pub fn read<R: Read + Seek>(
-            &self,
-            reader: &mut R,
-            p_last_delta: &mut Frame,
-        ) -> std::io::Result<Frame> {
-            if self.empty() {
-                let result = self.last_delta.unwrap_or(0); // Empty delta: magic returns last_delta, non-magic returns 0
-                return Ok(result);
+    /// Incremental state machine for the v2 input stream.
+    ///
+    /// The decoder runs over byte slices: [`Decoder::decode`] takes whatever part of the
+    /// stream is at hand and stops at the first blob that isn't complete, picking up
+    /// there on the next call. [`Decoder::step`] feeds it from a reader one blob at a
+    /// time.
+    pub struct Decoder {
+        current_frame: Frame,
+        last_delta: Frame,
+        next_blob: DeserializerBlob,
+        /// The delta bits of the last action byte
+        delta_bits: u8,
+        awaiting_seed: Option<(RestartType, Frame)>,
+        decoded: usize,
+    }
+
+    impl Decoder {
+        pub fn new(initial_frame: Frame) -> Self {
+            Self {
+                current_frame: initial_frame,
+                last_delta: 0,
+                next_blob: DeserializerBlob::Action,
+                delta_bits: 0,
+                awaiting_seed: None,
+                decoded: 0,
             }
+        }
 
-            let value = match self.blob {
-                ByteBlob::Zero => 0,
-                ByteBlob::One => {
-                    let mut buf = [0u8; 1];
-                    reader.read_exact(&mut buf)?;
-                    buf[0] as Frame
-                }
-                ByteBlob::Two => {
-                    let mut buf = [0u8; 2];
-                    reader.read_exact(&mut buf)?;
-                    u16::from_le_bytes(buf) as Frame
+        /// Reads the initial frame at the start of an input stream, returning a decoder
+        /// for the inputs after it.
+        ///
+        /// Empty v2 replays have no initial frame at all, which gives `None`. Strict
+        /// reads refuse an overlong initial frame.
+        pub(crate) fn start(reader: &mut impl ByteSource, strict: bool) -> TcmResult<Option<Self>> {
+            let offset = reader.offset();
+            match read_var_u32(reader, "v2 initial frame", strict) {
+                Ok(frame) => Ok(Some(Self::new(frame as Frame))),
+                Err(TcmError::UnexpectedEof { .. }) if reader.offset() == offset => Ok(None),
+                Err(e) => {
+                    let state = Some(DeserializerBlob::Action);
+                    Err(TcmError::decode(e, offset, 0, 0, state))
                 }
-                ByteBlob::Four => {
-                    let mut buf = [0u8; 4];
-                    reader.read_exact(&mut buf)?;
-                    u32::from_le_bytes(buf) as Frame
-                }
-            };
+            }
+        }
 
-            let result = self.last_delta.unwrap_or(0) + value;
+        pub fn current_frame(&self) -> Frame {
+            self.current_frame
+        }
 
-            if result != 0 {
-                *p_last_delta = result;
-            }
+        pub fn last_delta(&self) -> Frame {
+            self.last_delta
+        }
 
-            Ok(result)
+        /// The delta bits of the last action byte, describing the pending frame delta.
+        pub fn delta_bits(&self) -> u8 {
+            self.delta_bits
         }
-    }
-}
 
-impl<W: Write + Seek, M: Meta> InternalSerializer<W> for Replay<M> {
-    fn serialize_inputs_v1(&self, writer: &mut W) -> std::io::Result<()> {
-        write_var_u32(writer, self.inputs.len() as u32)?;
-
-        self.inputs
-            .iter()
-            .try_for_each(|input| -> std::io::Result<()> {
-                let input_data = v1::serialize_input(&input.input).ok_or(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Unsupported input type in v1 replay",
-                ))?;
-                write_var_u32(writer, input.frame as u32)?;
-                writer.write_all(&[input_data])?;
-                Ok(())
-            })?;
+        /// A decoder picking up the stream at a blob boundary recorded earlier.
+        ///
+        /// Only boundaries between inputs can be resumed from; in the middle of a TPS or
+        /// seed input part of the input is already behind the decoder.
+        pub fn resume(
+            current_frame: Frame,
+            last_delta: Frame,
+            state: DeserializerBlob,
+            delta_bits: u8,
+            decoded: usize,
+        ) -> TcmResult<Self> {
+            if let DeserializerBlob::Tps | DeserializerBlob::Seed = state {
+                return Err(TcmError::invalid_input(format!(
+                    "Cannot resume decoding at a {:?} blob",
+                    state
+                )));
+            }
 
-        writer.write_all(&[v1::EOM])?;
-        Ok(())
-    }
+            Ok(Self {
+                current_frame,
+                last_delta,
+                next_blob: state,
+                delta_bits,
+                awaiting_seed: None,
+                decoded,
+            })
+        }
 
-    fn serialize_inputs_v2(&self, writer: &mut W) -> std::io::Result<()> {
-        if self.inputs.is_empty() {
-            return Ok(());
+        pub fn state(&self) -> DeserializerBlob {
+            self.next_blob
         }
 
-        use v2::{SerializerBlob, SerializerDeltaInfo};
+        /// Number of bytes the next blob takes.
+        pub fn unit_len(&self) -> usize {
+            match self.next_blob {
+                DeserializerBlob::Action => 1,
+                DeserializerBlob::FrameDelta => {
+                    match DeserializerDeltaInfo::new(self.delta_bits, 0).blob {
+                        ByteBlob::Zero => 0,
+                        ByteBlob::One => 1,
+                        ByteBlob::Two => 2,
+                        ByteBlob::Four => 4,
+                    }
+                }
+                DeserializerBlob::Tps => 4,
+                DeserializerBlob::Seed => 8,
+            }
+        }
 
-        write_var_u32(writer, self.inputs[0].frame as u32)?;
+        /// Number of inputs emitted so far.
+        pub fn decoded(&self) -> usize {
+            self.decoded
+        }
 
-        let mut next_blob = SerializerBlob::Action;
-        let mut last_delta = 0u64;
-        let mut next_delta = SerializerDeltaInfo::default();
+        /// Reads the next blob from a reader and decodes it, pushing any completed inputs
+        /// into `out`.
+        ///
+        /// Returns `Ok(false)` if the reader ends before the blob is complete; see
+        /// [`Decoder::check_end`] for whether the stream may end there.
+        pub fn step(
+            &mut self,
+            reader: &mut impl ByteSource,
+            out: &mut Vec<InputCommand>,
+        ) -> TcmResult<bool> {
+            self.step_checked(reader, out, &mut LimitTracker::default(), false)
+        }
 
-        let mut iter = self.inputs.iter().peekable();
+        /// Like [`Decoder::step`], checking inputs as [`Decoder::decode`] does.
+        pub(crate) fn step_checked(
+            &mut self,
+            reader: &mut impl ByteSource,
+            out: &mut Vec<InputCommand>,
+            limits: &mut LimitTracker,
+            strict: bool,
+        ) -> TcmResult<bool> {
+            let offset = reader.offset();
+            let mut buf = [0u8; 8];
+            let unit = &mut buf[..self.unit_len()];
+            match reader.read_exact_into(unit, self.next_blob.context()) {
+                Ok(()) => {}
+                Err(TcmError::UnexpectedEof { .. }) => return Ok(false),
+                Err(e) => return Err(self.wrap_error(e, offset)),
+            }
+            self.decode(unit, offset, out, usize::MAX, limits, strict)?;
+            Ok(true)
+        }
 
-        while iter.peek().is_some() {
-            match next_blob {
-                SerializerBlob::Action => {
-                    let input = iter.next().unwrap();
-                    let mut next = iter.peek();
-                    let swift = next.is_some_and(|n| {
-                        if n.frame != input.frame {
-                            return false;
-                        }
+        /// Decodes the complete blobs at the start of `bytes`, stopping early once `out`
+        /// holds `until` inputs.
+        ///
+        /// `offset` is the position of `bytes` in the replay, for decode errors. Inputs
+        /// are checked against `limits` before they are added to `out`, leaving the input
+        /// count to the caller; limit errors are passed on as they are. Returns the number
+        /// of bytes decoded.
+        #[inline]
+        pub(crate) fn decode(
+            &mut self,
+            bytes: &[u8],
+            offset: u64,
+            out: &mut Vec<InputCommand>,
+            until: usize,
+            limits: &mut LimitTracker,
+            strict: bool,
+        ) -> TcmResult<usize> {
+            // Vanilla inputs only need their frame checked, which is done here rather
+            // than going through the tracker for every one of them.
+            let max_frame = limits.max_frame();
+            let mut position = 0;
+            let mut frame = self.current_frame;
+            let mut last_delta = self.last_delta;
+            let mut delta_bits = self.delta_bits;
+            let mut next_blob = self.next_blob;
+            let start = out.len();
+
+            let result = loop {
+                let blob = position;
+                match next_blob {
+                    DeserializerBlob::Action => {
+                        let Some(&byte) = bytes.get(position) else {
+                            break Ok(());
+                        };
+                        position += 1;
+                        let delta_data = (byte & DELTA_DATA_MASK) >> DELTA_OFFSET;
+                        delta_bits = delta_data;
+                        next_blob = DeserializerBlob::FrameDelta;
 
-                        match (&input.input, &n.input) {
-                            (Input::Vanilla(a), Input::Vanilla(b)) => {
-                                a.button == b.button && a.push != b.push && a.player2 == b.player2
+                        let input_data = byte & INPUT_MASK;
+                        let extra = (byte & EXTRA_MASK) != 0;
+                        if input_data > 0 {
+                            if frame > max_frame {
+                                let (limit, max) = (ResourceLimit::Frame, max_frame);
+                                break Err(TcmError::LimitExceeded { limit, max });
+                            }
+                            let button = match input_data {
+                                1 => PlayerButton::Jump,
+                                2 => PlayerButton::Left,
+                                _ => PlayerButton::Right,
+                            };
+                            let push = (byte & PUSH_MASK) != 0;
+                            let player2 = (byte & PLAYER2_MASK) != 0;
+                            out.push(vanilla(frame, button, push, player2));
+                            if extra {
+                                out.push(vanilla(frame, button, !push, player2));
+                            }
+                        } else {
+                            match self.custom_action(byte, frame, out, limits, strict) {
+                                Ok((DeserializerBlob::FrameDelta, next_frame)) => {
+                                    frame = next_frame
+                                }
+                                Ok((blob, next_frame)) => {
+                                    (next_blob, frame) = (blob, next_frame);
+                                    continue;
+                                }
+                                Err(e) => break Err(e),
                             }
-                            _ => false,
                         }
-                    });
-
-                    if swift {
-                        iter.next();
-                        next = iter.peek();
                     }
-
-                    if let Some(next1) = next {
-                        let this_frame = input.adjusted_frame();
-                        let next_frame = next1.frame;
-                        let delta = next_frame.saturating_sub(this_frame);
-                        next_delta = SerializerDeltaInfo::new(delta, last_delta);
-                    } else {
-                        next_delta = SerializerDeltaInfo::default();
+                    DeserializerBlob::FrameDelta => {}
+                    DeserializerBlob::Tps => {
+                        let Some(&[a, b, c, d]) = bytes.get(position..position + 4) else {
+                            break Ok(());
+                        };
+                        position += 4;
+                        next_blob = DeserializerBlob::FrameDelta;
+                        let tps = f32::from_le_bytes([a, b, c, d]);
+                        let input = Input::Tps(TpsInput { tps });
+                        if let Err(e) = emit(out, limits, strict, frame, input) {
+                            break Err(e);
+                        }
                     }
-
-                    if next_delta.delta != 0 {
-                        last_delta = next_delta.delta;
+                    DeserializerBlob::Seed => {
+                        let Some(&[a, b, c, d, e, f, g, h]) = bytes.get(position..position + 8)
+                        else {
+                            break Ok(());
+                        };
+                        let Some((restart_type, restart_frame)) = self.awaiting_seed.take() else {
+                            let e = TcmError::deserialization_error("Unexpected seed input");
+                            let at = offset + blob as u64;
+                            let decoded = self.decoded + (out.len() - start);
+                            let state = DeserializerBlob::Seed;
+                            break Err(Self::error_at(e, at, decoded, frame, state));
+                        };
+                        position += 8;
+                        next_blob = DeserializerBlob::FrameDelta;
+                        let restart = RestartInput {
+                            restart_type,
+                            new_seed: Some(u64::from_le_bytes([a, b, c, d, e, f, g, h])),
+                        };
+                        let input = Input::Restart(restart);
+                        if let Err(e) = emit(out, limits, strict, restart_frame, input) {
+                            break Err(e);
+                        }
                     }
+                }
 
-                    let data = v2::serialize_input(&input.input, swift);
-                    let delta_data = next_delta.craft();
-                    let data = data | (delta_data << v2::DELTA_OFFSET);
-
-                    writer.write_all(&[data])?;
-
-                    match &input.input {
-                        Input::Tps(tps) => {
-                            next_blob = SerializerBlob::Tps(tps.tps);
-                        }
-                        Input::Restart(restart) => {
-                            if let Some(seed) = restart.new_seed {
-                                next_blob = SerializerBlob::Seed(seed);
-                            } else if next_delta.empty() {
-                                next_blob = SerializerBlob::Action;
-                            } else {
-                                next_blob = SerializerBlob::FrameDelta;
-                            }
-                        }
-                        _ => {
-                            if next_delta.empty() {
-                                next_blob = SerializerBlob::Action;
-                            } else {
-                                next_blob = SerializerBlob::FrameDelta;
-                            }
-                        }
+                // Every input ends with a frame delta, read here along with the rest of it.
+                let next_delta = DeserializerDeltaInfo::new(delta_bits, last_delta);
+                let Some((delta, len)) = next_delta.read(&bytes[position..]) else {
+                    break Ok(());
+                };
+                let advanced = delta.and_then(|delta| {
+                    let next_frame = frame.checked_add(delta).ok_or("Frame counter overflow")?;
+                    Ok((delta, next_frame))
+                });
+                let (delta, next_frame) = match advanced {
+                    Ok(advanced) => advanced,
+                    Err(message) => {
+                        let e = TcmError::invalid_format(message);
+                        let at = offset + position as u64;
+                        let decoded = self.decoded + (out.len() - start);
+                        let state = DeserializerBlob::FrameDelta;
+                        break Err(Self::error_at(e, at, decoded, frame, state));
                     }
+                };
+                // An empty delta repeats the last one without becoming it.
+                if len > 0 && delta != 0 {
+                    last_delta = delta;
                 }
-                SerializerBlob::FrameDelta => {
-                    next_delta.serialize(writer)?;
-                    next_blob = SerializerBlob::Action;
+                frame = next_frame;
+                position += len;
+                next_blob = DeserializerBlob::Action;
+                if out.len() >= until {
+                    break Ok(());
                 }
-                SerializerBlob::Tps(tps) => {
-                    let tps_bytes = tps.to_le_bytes();
-                    writer.write_all(&tps_bytes)?;
-                    if next_delta.empty() {
-                        next_blob = SerializerBlob::Action;
-                    } else {
-                        next_blob = SerializerBlob::FrameDelta;
-                    }
+            };
+
+            self.current_frame = frame;
+            self.last_delta = last_delta;
+            self.delta_bits = delta_bits;
+            self.next_blob = next_blob;
+            self.decoded += out.len() - start;
+            result.map(|()| position)
+        }
+
+        /// Handles an action byte carrying something other than a vanilla input.
+        ///
+        /// Returns the blob to read next and the frame the stream continues at.
+        #[cold]
+        fn custom_action(
+            &mut self,
+            byte: u8,
+            frame: Frame,
+            out: &mut Vec<InputCommand>,
+            limits: &mut LimitTracker,
+            strict: bool,
+        ) -> TcmResult<(DeserializerBlob, Frame)> {
+            let extra = (byte & EXTRA_MASK) != 0;
+            match ((byte & CUSTOM_MASK) >> CUSTOM_OFFSET, extra) {
+                (3, true) => {
+                    emit(out, limits, strict, frame, Input::Bugpoint(BugpointInput))?;
+                    Ok((DeserializerBlob::FrameDelta, frame))
                 }
-                SerializerBlob::Seed(seed) => {
-                    writer.write_all(&seed.to_le_bytes())?;
-                    if next_delta.empty() {
-                        next_blob = SerializerBlob::Action;
-                    } else {
-                        next_blob = SerializerBlob::FrameDelta;
+                (3, false) => Ok((DeserializerBlob::Tps, frame)),
+                (custom_type, extra) => {
+                    let restart_type = match custom_type {
+                        0 => RestartType::Restart,
+                        1 => RestartType::RestartFull,
+                        _ => RestartType::Death,
+                    };
+                    // Restarts go back to frame 0.
+                    if extra {
+                        self.awaiting_seed = Some((restart_type, frame));
+                        return Ok((DeserializerBlob::Seed, 0));
                     }
+                    let restart = RestartInput {
+                        restart_type,
+                        new_seed: None,
+                    };
+                    emit(out, limits, strict, frame, Input::Restart(restart))?;
+                    Ok((DeserializerBlob::FrameDelta, 0))
                 }
             }
         }
 
-        Ok(())
-    }
-}
-
-macro_rules! break_if_eof {
-    ($result:expr) => {
-        match $result {
-            Ok(v) => v,
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                    break;
+        /// Checks that the stream may end where the decoder is, between two inputs with
+        /// no frame delta pending, given the offset of the blob cut short.
+        pub fn check_end(&self, offset: u64) -> TcmResult<()> {
+            match self.next_blob {
+                DeserializerBlob::Action => Ok(()),
+                state => {
+                    let e = TcmError::unexpected_eof(state.context());
+                    Err(self.wrap_error(e, offset))
                 }
-                return Err(e);
             }
         }
-    };
-}
 
-impl<R: Read + Seek, M: Meta> InternalDeserializer<R> for Replay<M> {
-    fn deserialize_inputs_v1(reader: &mut R) -> std::io::Result<Vec<InputCommand>> {
-        let input_count = read_var_u32(reader)? as usize;
-        let mut inputs = Vec::with_capacity(input_count);
-
-        let mut buf = [0u8; 1];
+        /// Like [`Decoder::check_end`], but letting the stream end in a frame delta, as
+        /// permissive reads always have.
+        pub fn check_permissive_end(&self, offset: u64) -> TcmResult<()> {
+            match self.next_blob {
+                DeserializerBlob::FrameDelta => Ok(()),
+                _ => self.check_end(offset),
+            }
+        }
 
-        for _ in 0..input_count {
-            let frame = read_var_u32(reader)? as Frame;
-            reader.read_exact(&mut buf)?;
-            let byte = buf[0];
-            let input = v1::deserialize_input(byte).ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Invalid input byte in v1 replay",
-            ))?;
-            inputs.push(InputCommand { frame, input });
+        /// Adds the decoder position to an error raised decoding the blob at `offset`.
+        pub fn wrap_error(&self, e: TcmError, offset: u64) -> TcmError {
+            TcmError::decode(
+                e,
+                offset,
+                self.decoded,
+                self.current_frame,
+                Some(self.next_blob),
+            )
         }
 
-        Ok(inputs)
+        /// Wraps an error raised in the middle of [`Decoder::decode`], before its state is
+        /// written back.
+        #[cold]
+        fn error_at(
+            e: TcmError,
+            offset: u64,
+            decoded: usize,
+            frame: Frame,
+            state: DeserializerBlob,
+        ) -> TcmError {
+            TcmError::decode(e, offset, decoded, frame, Some(state))
+        }
     }
 
-    fn deserialize_inputs_v2(reader: &mut R) -> std::io::Result<Vec<InputCommand>> {
-        use v2::{DeserializerBlob, DeserializerDeltaInfo};
-
-        let mut inputs = Vec::new();
-
-        let mut current_frame = read_var_u32(reader)? as Frame;
-        let mut last_delta = 0u64;
-        let mut next_blob = DeserializerBlob::Action;
-        let mut next_delta = DeserializerDeltaInfo::default();
-        let mut awaiting_seed = None;
-
-        loop {
-            match next_blob {
-                DeserializerBlob::Action => {
-                    use v2::{
-                        CUSTOM_MASK, CUSTOM_OFFSET, DELTA_DATA_MASK, EXTRA_MASK, INPUT_MASK,
-                        PLAYER2_MASK, PUSH_MASK,
-                    };
-
-                    let mut buf = [0u8; 1];
-                    let read = reader.read_exact(&mut buf);
-                    break_if_eof!(read);
-                    let byte = buf[0];
-
-                    let delta_data = (byte & DELTA_DATA_MASK) >> v2::DELTA_OFFSET;
-                    next_delta = DeserializerDeltaInfo::new(delta_data, last_delta);
-
-                    let input_data = byte & INPUT_MASK;
-
-                    if input_data > 0 {
-                        let button: PlayerButton = input_data.try_into().unwrap();
-                        let push = (byte & PUSH_MASK) != 0;
-                        let player2 = (byte & PLAYER2_MASK) != 0;
-                        let swift = (byte & EXTRA_MASK) != 0;
-
-                        inputs.push(InputCommand {
-                            frame: current_frame,
-                            input: Input::Vanilla(VanillaInput {
-                                button,
-                                push,
-                                player2,
-                            }),
-                        });
-
-                        if swift {
-                            inputs.push(InputCommand {
-                                frame: current_frame,
-                                input: Input::Vanilla(VanillaInput {
-                                    button,
-                                    push: !push,
-                                    player2,
-                                }),
-                            });
-                        }
-
-                        next_blob = DeserializerBlob::FrameDelta;
-                    } else {
-                        let custom_type = (byte & CUSTOM_MASK) >> CUSTOM_OFFSET;
-                        let extra = (byte & EXTRA_MASK) != 0;
-
-                        if custom_type == 3 {
-                            if extra {
-                                inputs.push(InputCommand {
-                                    frame: current_frame,
-                                    input: Input::Bugpoint(BugpointInput),
-                                });
-                                next_blob = DeserializerBlob::FrameDelta;
-                            } else {
-                                next_blob = DeserializerBlob::Tps;
-                            }
-                        } else {
-                            let restart_type = custom_type.try_into().unwrap();
-                            if extra {
-                                awaiting_seed = Some((restart_type, current_frame));
-                                next_blob = DeserializerBlob::Seed;
-                            } else {
-                                inputs.push(InputCommand {
-                                    frame: current_frame,
-                                    input: Input::Restart(RestartInput {
-                                        restart_type,
-                                        new_seed: None,
-                                    }),
-                                });
-                                next_blob = DeserializerBlob::FrameDelta;
-                            }
-                            current_frame = 0;
-                        }
-                    }
-                }
-                DeserializerBlob::FrameDelta => {
-                    let dt = next_delta.read(reader, &mut last_delta);
-                    let dt = break_if_eof!(dt);
-                    current_frame += dt;
-                    next_blob = DeserializerBlob::Action;
-                }
-                DeserializerBlob::Tps => {
-                    let mut buf = [0u8; 4];
-                    reader.read_exact(&mut buf)?;
-                    let tps = f32::from_le_bytes(buf);
-                    inputs.push(InputCommand {
-                        frame: current_frame,
-                        input: Input::Tps(crate::input::TpsInput { tps }),
-                    });
-                    next_blob = DeserializerBlob::FrameDelta;
-                }
-                DeserializerBlob::Seed => {
-                    if awaiting_seed.is_none() {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            "Unexpected seed input",
-                        ));
-                    }
-
-                    let (t, frame) = awaiting_seed.take().unwrap();
-                    let seed = {
-                        let mut buf = [0u8; 8];
-                        reader.read_exact(&mut buf)?;
-                        u64::from_le_bytes(buf)
-                    };
-                    inputs.push(InputCommand {
-                        frame,
-                        input: Input::Restart(RestartInput {
-                            restart_type: t,
-                            new_seed: Some(seed),
-                        }),
-                    });
-                    next_blob = DeserializerBlob::FrameDelta;
-                }
+    impl DeserializerBlob {
+        /// What a blob of this kind is called in end of stream errors.
+        fn context(self) -> &'static str {
+            match self {
+                DeserializerBlob::Action => "v2 action",
+                DeserializerBlob::FrameDelta => "frame delta",
+                DeserializerBlob::Tps => "v2 tps",
+                DeserializerBlob::Seed => "v2 restart seed",
             }
         }
-
-        Ok(inputs)
     }
-}
-
-const HEADER_SIZE: usize = 0x10;
-const TCBOT_HEADER: [u8; HEADER_SIZE] = [
-    0x9f, 0x88, 0x89, 0x84, 0x9f, 0x3b, 0x1d, 0xd8, 0xcc, 0xa1, 0x86, 0x8a, 0x88, 0x99, 0x84, 0x00,
-];
 
-impl<W: Write + Seek, M: Meta> ReplaySerializer<W> for Replay<M> {
-    fn serialize(&self, writer: &mut W) -> std::io::Result<()> {
-        writer.write_all(&TCBOT_HEADER)?;
-        writer.write_all(&self.meta.to_bytes())?;
-        let version = self.meta.version_instance();
-        if version == 1 {
-            self.serialize_inputs_v1(writer)?;
-        } else if version == 2 {
-            self.serialize_inputs_v2(writer)?;
-        } else {
-            panic!("Unsupported meta version: {}", version);
+    #[inline(always)]
+    fn vanilla(frame: Frame, button: PlayerButton, push: bool, player2: bool) -> InputCommand {
+        let input = VanillaInput {
+            button,
+            push,
+            player2,
+        };
+        InputCommand {
+            frame,
+            input: Input::Vanilla(input),
         }
+    }
+
+    /// Checks and adds an input other than a vanilla one, which are far rarer.
+    #[cold]
+    fn emit(
+        out: &mut Vec<InputCommand>,
+        limits: &mut LimitTracker,
+        strict: bool,
+        frame: Frame,
+        input: Input,
+    ) -> TcmResult<()> {
+        let input = InputCommand { frame, input };
+        limits.check_contents(&input, strict)?;
+        out.push(input);
         Ok(())
     }
 }
 
-impl<R: Read + Seek, M: Meta> ReplayDeserializer<R, M> for Replay<M> {
-    fn deserialize(reader: &mut R) -> std::io::Result<Replay<M>> {
-        let mut header = [0u8; HEADER_SIZE];
-        reader.read_exact(&mut header)?;
-        if header != TCBOT_HEADER {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Invalid header",
-            ));
-        }
+/// Reads a single frame/input pair of a v1 stream; strict reads refuse an overlong frame.
+pub(crate) fn read_v1_input(reader: &mut impl Source, strict: bool) -> TcmResult<InputCommand> {
+    let frame = read_var_u32(reader, "v1 input frame", strict)? as Frame;
+    let [byte] = read_array(reader, "v1 input")?;
+    let input = v1::deserialize_input(byte).ok_or_else(|| {
+        TcmError::invalid_format(format!("Invalid input byte in v1 replay: {byte:#04x}"))
+    })?;
+    Ok(InputCommand { frame, input })
+}
 
-        let mut meta_bytes = vec![0u8; M::size()];
-        reader.read_exact(&mut meta_bytes)?;
-        let meta = M::from_bytes(&meta_bytes);
+/// A reader that keeps track of how many bytes have been consumed.
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    pub(crate) offset: u64,
+}
 
-        let inputs = if M::version() == 1 {
-            Self::deserialize_inputs_v1(reader)?
-        } else if M::version() == 2 {
-            Self::deserialize_inputs_v2(reader)?
-        } else {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Unsupported meta version",
-            ));
-        };
+impl<R: Read> CountingReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self { inner, offset: 0 }
+    }
 
-        Ok(Replay { meta, inputs })
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
     }
 }
 
-/// A TCM replay with dynamic metadata type that can be either V1 or V2.
-/// This allows working with replays without knowing the specific version at compile time.
-pub type DynamicReplay = Replay<Box<dyn Meta>>;
-
-impl Meta for Box<dyn Meta> {
-    fn size() -> usize {
-        0x40 // Both MetaV1 and MetaV2 have the same size
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.offset += read as u64;
+        Ok(read)
     }
 
-    fn tps(&self) -> f32 {
-        self.as_ref().tps()
+    #[inline]
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> std::io::Result<()> {
+        // A single byte is read or it isn't, so the reader's own (usually much faster)
+        // `read_exact` can be counted exactly. Anything longer may be cut short halfway,
+        // and the offset has to include what was read before the end.
+        if buf.len() == 1 {
+            self.inner.read_exact(buf)?;
+            self.offset += 1;
+            return Ok(());
+        }
+        while !buf.is_empty() {
+            match self.read(buf) {
+                Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                Ok(read) => buf = &mut buf[read..],
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
     }
+}
 
-    fn tps_dt(&self) -> f32 {
-        self.as_ref().tps_dt()
-    }
+pub(crate) const HEADER_SIZE: usize = 0x10;
+pub(crate) const TCBOT_HEADER: [u8; HEADER_SIZE] = [
+    0x9f, 0x88, 0x89, 0x84, 0x9f, 0x3b, 0x1d, 0xd8, 0xcc, 0xa1, 0x86, 0x8a, 0x88, 0x99, 0x84, 0x00,
+];
 
-    fn uses_dt(&self) -> bool {
-        self.as_ref().uses_dt()
+impl<W: Write, M: MetaFormat> ReplaySerializer<W> for Replay<M> {
+    fn serialize(&self, writer: &mut W) -> TcmResult<()> {
+        writer.write_all(&TCBOT_HEADER)?;
+        let codec = Codec::for_version(self.meta.version())?;
+        writer.write_all(&self.meta.to_bytes())?;
+        codec.write_inputs(writer, &self.inputs)
     }
+}
 
-    fn version() -> u8 {
-        0 // This will be overridden by version_instance
+/// Reads and validates the file header followed by the metadata block.
+pub(crate) fn read_preamble<M: MetaFormat>(
+    reader: &mut impl Source,
+    options: &ReadOptions,
+) -> TcmResult<M> {
+    let header: [u8; HEADER_SIZE] = read_array(reader, "header")?;
+    if header != TCBOT_HEADER {
+        return Err(TcmError::InvalidHeader);
     }
 
-    fn version_instance(&self) -> u8 {
-        self.as_ref().version_instance()
+    let mut meta_bytes = vec![0u8; M::size()];
+    reader.read_exact_into(&mut meta_bytes, "metadata")?;
+    if options.strict {
+        M::validate_bytes(&meta_bytes)?;
     }
+    M::from_bytes(&meta_bytes)
+}
 
-    fn rng_seed(&self) -> Option<u64> {
-        self.as_ref().rng_seed()
+/// Size of the header and metadata block that precede the input stream.
+pub const PREFIX_SIZE: usize = HEADER_SIZE + 0x40;
+
+/// A reader that replays a buffered prefix before continuing with the underlying reader.
+pub type PrefixedReader<R> = Chain<Cursor<[u8; PREFIX_SIZE]>, R>;
+
+/// Reads the header and metadata ahead to find the format version of a replay.
+///
+/// No seeking is involved: the first [`PREFIX_SIZE`] bytes are buffered and handed back
+/// in front of the rest of the reader, ready for the deserializer matching the version.
+///
+/// # Example
+/// ```no_run
+/// use tcm::meta::{MetaV1, MetaV2};
+/// use tcm::replay::{detect_version, Replay, ReplayDeserializer};
+///
+/// let (version, mut reader) = detect_version(std::io::stdin().lock()).unwrap();
+/// match version {
+///     1 => println!("{:?}", Replay::<MetaV1>::deserialize(&mut reader).unwrap().meta),
+///     2 => println!("{:?}", Replay::<MetaV2>::deserialize(&mut reader).unwrap().meta),
+///     _ => println!("unsupported version {}", version),
+/// }
+/// ```
+pub fn detect_version<R: Read>(mut reader: R) -> TcmResult<(u8, PrefixedReader<R>)> {
+    let prefix: [u8; PREFIX_SIZE] = read_array(&mut reader, "header and metadata")?;
+    if prefix[..HEADER_SIZE] != TCBOT_HEADER {
+        return Err(TcmError::InvalidHeader);
     }
+    Ok((prefix[HEADER_SIZE], Cursor::new(prefix).chain(reader)))
+}
 
-    fn is_rng_seed_set(&self) -> bool {
-        self.as_ref().is_rng_seed_set()
-    }
+impl<R: Read, M: MetaFormat> ReplayDeserializer<R, M> for Replay<M> {
+    fn deserialize_with_options(reader: &mut R, options: &ReadOptions) -> TcmResult<Replay<M>> {
+        let mut stream = InputStream::with_options(reader, options)?;
+        let mut inputs = Vec::new();
+        stream.core_mut().read_to_end(&mut inputs)?;
 
-    fn from_bytes(_bytes: &[u8]) -> Self {
-        panic!("from_bytes cannot be called on Box<dyn Meta>, use specific types")
+        Ok(Replay {
+            meta: stream.into_meta(),
+            inputs,
+        })
     }
+}
 
-    fn to_bytes(&self) -> Box<[u8]> {
-        self.as_ref().to_bytes()
+/// A TCM replay of any supported format version.
+///
+/// The version is picked from the metadata when reading; match on [`AnyMeta`] to get
+/// at the metadata of a specific version.
+pub type DynamicReplay = Replay<AnyMeta>;
+
+impl From<Replay<MetaV1>> for DynamicReplay {
+    fn from(replay: Replay<MetaV1>) -> Self {
+        Replay::new(replay.meta.into(), replay.inputs)
     }
+}
 
-    fn new_empty(tps: f32) -> Self {
-        Box::new(crate::meta::MetaV2::new_empty(tps))
+impl From<Replay<MetaV2>> for DynamicReplay {
+    fn from(replay: Replay<MetaV2>) -> Self {
+        Replay::new(replay.meta.into(), replay.inputs)
     }
 }
 
@@ -807,69 +1274,60 @@ impl DynamicReplay {
     /// Parse a replay from a reader, automatically detecting the format version.
     ///
     /// This method reads the version byte from the metadata and creates the appropriate
-    /// metadata type, returning a single replay instance that can be used with any
-    /// Meta trait methods.
+    /// [`AnyMeta`] variant, returning a single replay instance that can be used with any
+    /// [`MetaInfo`] methods. The reader is consumed strictly in order, so pipes and
+    /// sockets work as well as files.
     ///
     /// # Example
     /// ```no_run
     /// use std::fs::File;
-    /// use tcm::DynamicReplay;
+    /// use tcm::{DynamicReplay, meta::MetaInfo};
     ///
     /// let mut file = File::open("replay.tcm").unwrap();
     /// let replay = DynamicReplay::from_reader(&mut file).unwrap();
     /// println!("TPS: {}", replay.meta.tps());
     /// ```
-    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> std::io::Result<Self> {
-        // Read version byte from metadata start (after header)
-        let current_pos = reader.stream_position()?;
-
-        // Skip header to get to metadata
-        reader.seek(std::io::SeekFrom::Start(current_pos + HEADER_SIZE as u64))?;
-
-        let mut version_buf = [0u8; 1];
-        reader.read_exact(&mut version_buf)?;
-        let version = version_buf[0];
-
-        // Reset to start for deserialize methods
-        reader.seek(std::io::SeekFrom::Start(current_pos))?;
-
-        match version {
-            1 => {
-                let concrete_replay = Replay::<MetaV1>::deserialize(reader)?;
-                Ok(Replay {
-                    meta: Box::new(concrete_replay.meta) as Box<dyn Meta>,
-                    inputs: concrete_replay.inputs,
-                })
-            }
-            2 => {
-                let concrete_replay = Replay::<MetaV2>::deserialize(reader)?;
-                Ok(Replay {
-                    meta: Box::new(concrete_replay.meta) as Box<dyn Meta>,
-                    inputs: concrete_replay.inputs,
-                })
-            }
-            _ => Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Unsupported version: {}", version),
-            )),
-        }
+    pub fn from_reader<R: Read>(reader: &mut R) -> TcmResult<Self> {
+        Self::from_reader_with_options(reader, &ReadOptions::default())
+    }
+
+    /// Parse a replay from a reader with the given [`ReadOptions`], automatically
+    /// detecting the format version.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::fs::File;
+    /// use tcm::{DynamicReplay, ReadOptions};
+    ///
+    /// let mut file = File::open("replay.tcm").unwrap();
+    /// let replay = DynamicReplay::from_reader_with_options(&mut file, &ReadOptions::strict());
+    /// ```
+    pub fn from_reader_with_options<R: Read>(
+        reader: &mut R,
+        options: &ReadOptions,
+    ) -> TcmResult<Self> {
+        // The metadata picks its variant from the version byte.
+        Self::deserialize_with_options(reader, options)
     }
 
     /// Convert to V1 format.
-    pub fn to_v1(self) -> Result<Replay<MetaV1>, String> {
+    pub fn to_v1(self) -> TcmResult<Replay<MetaV1>> {
         // Check for inputs that actually can't be represented in V1
         for input_cmd in &self.inputs {
             match &input_cmd.input {
-                Input::Tps(_) => return Err("TPS changes not supported in V1".to_string()),
-                Input::Bugpoint(_) => return Err("Bugpoint inputs not supported in V1".to_string()),
+                Input::Tps(_) => {
+                    return Err(TcmError::invalid_input("TPS changes not supported in V1"))
+                }
+                Input::Bugpoint(_) => {
+                    return Err(TcmError::invalid_input(
+                        "Bugpoint inputs not supported in V1",
+                    ))
+                }
                 _ => {}
             }
         }
 
-        let meta_v1 = MetaV1 {
-            tps: self.meta.tps(),
-            append_counter: 0,
-        };
+        let meta_v1 = MetaV1::new(self.meta.tps(), self.meta.append_counter());
 
         Ok(Replay {
             meta: meta_v1,
@@ -881,7 +1339,7 @@ impl DynamicReplay {
     pub fn to_v2(self, rng_seed: Option<u64>) -> Replay<MetaV2> {
         let final_seed = rng_seed.or_else(|| self.meta.rng_seed());
 
-        let meta_v2 = MetaV2::new(self.meta.tps(), 0, final_seed);
+        let meta_v2 = MetaV2::new(self.meta.tps(), self.meta.append_counter(), final_seed);
 
         Replay {
             meta: meta_v2,
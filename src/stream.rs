@@ -0,0 +1,760 @@
+//! Lazy, constant-memory iteration over the inputs of a replay.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{
+    codec::{Codec, InputDecoder, InputSink},
+    error::{TcmError, TcmResult},
+    index::{Checkpoint, SeekIndex},
+    input::InputCommand,
+    meta::{AnyMeta, MetaFormat},
+    options::{LimitTracker, ReadOptions},
+    replay::{
+        read_array, read_preamble, read_v1_input, read_var_u32, v1, v2, CountingReader,
+        SliceReader, Source,
+    },
+    Frame,
+};
+
+pub(crate) enum StreamState {
+    V1Start,
+    V1 {
+        index: usize,
+        count: usize,
+    },
+    V1End,
+    V2Start,
+    V2(v2::Decoder),
+    /// A registered codec, fed the stream a chunk at a time
+    Registered(Feed),
+    Trailing,
+    Done,
+}
+
+/// The decoder of a registered codec with the bytes read for it that it hasn't used.
+pub(crate) struct Feed {
+    decoder: Box<dyn InputDecoder>,
+    /// Bytes read for the decoder, used up to `used`
+    buffer: Vec<u8>,
+    used: usize,
+    /// Whether the decoder has used what it can of `buffer` and needs more bytes
+    hungry: bool,
+    at_end: bool,
+}
+
+impl Feed {
+    fn new(decoder: Box<dyn InputDecoder>) -> Self {
+        Self {
+            decoder,
+            buffer: Vec::new(),
+            used: 0,
+            hungry: true,
+            at_end: false,
+        }
+    }
+
+    /// Number of bytes to read for the decoder next, growing with a buffered input it
+    /// couldn't decode yet.
+    fn read_len(&self, limits: &LimitTracker, offset: u64) -> u64 {
+        if !self.hungry || self.at_end {
+            return 0;
+        }
+        let len = (self.buffer.len() - self.used).max(CHUNK_SIZE) as u64;
+        // One byte past the limit is enough to report it.
+        len.min(limits.bytes_left(offset).saturating_add(1))
+    }
+}
+
+/// Size of the chunks readers are decoded in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of inputs the iterator asks to have decoded ahead at a time.
+const BATCH: usize = 4096;
+
+/// Sources the stream decoder can read from, keeping track of the byte offset.
+pub(crate) trait ByteSource: Source {
+    fn offset(&self) -> u64;
+
+    /// Reads up to `max` bytes, or to the end of the source if it comes first.
+    fn read_rest(&mut self, out: &mut Vec<u8>, max: u64) -> TcmResult<()>;
+
+    /// Reads what a single read hands out, up to `max` bytes, returning how many were
+    /// read; 0 means the source has ended.
+    fn read_some(&mut self, out: &mut Vec<u8>, max: u64) -> TcmResult<usize>;
+
+    /// Runs a v2 decoder until `out` holds `until` inputs or the source runs out, in
+    /// which case the offset of the blob cut short is returned.
+    ///
+    /// With `until` at 0, the reader is fed to the decoder a single blob, so nothing past
+    /// it is read. Otherwise the reader is read a chunk at a time into `ahead`, and each
+    /// chunk is decoded in memory as far as it goes, leaving the start of a blob it cuts
+    /// short in `ahead` for the next one. Unless every input is asked for, a chunk is
+    /// what a single read hands out, and decoding stops after the first one that
+    /// completes an input, so a live source is never waited on for more than it has.
+    #[inline]
+    fn decode_v2(
+        &mut self,
+        decoder: &mut v2::Decoder,
+        ahead: &mut Vec<u8>,
+        out: &mut Vec<InputCommand>,
+        until: usize,
+        limits: &mut LimitTracker,
+        strict: bool,
+    ) -> TcmResult<Option<u64>>
+    where
+        Self: Sized,
+    {
+        if until == 0 {
+            let offset = self.offset();
+            if !decoder.step_checked(self, out, limits, strict)? {
+                return Ok(Some(offset));
+            }
+            limits.check_bytes(self.offset())?;
+            return Ok(None);
+        }
+
+        let start = out.len();
+        let most = max_inputs(out, limits);
+        loop {
+            let offset = self.offset() - ahead.len() as u64;
+            // One byte past the limit is enough to report it.
+            let max = (CHUNK_SIZE - ahead.len()) as u64;
+            let max = max.min(limits.bytes_left(self.offset()).saturating_add(1));
+            let at_end = if until == usize::MAX {
+                let filled = ahead.len();
+                self.read_rest(ahead, max)?;
+                ((ahead.len() - filled) as u64) < max
+            } else {
+                self.read_some(ahead, max)? == 0
+            };
+            let used = decode_v2_slice(decoder, ahead, offset, out, most, limits, strict)?;
+            if at_end {
+                ahead.clear();
+                return Ok(Some(offset + used as u64));
+            }
+            ahead.drain(..used);
+            let some = until != usize::MAX && out.len() > start;
+            if some || out.len() >= until.min(most) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl<R: Read> ByteSource for CountingReader<R> {
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn read_rest(&mut self, out: &mut Vec<u8>, max: u64) -> TcmResult<()> {
+        self.take(max).read_to_end(out)?;
+        Ok(())
+    }
+
+    fn read_some(&mut self, out: &mut Vec<u8>, max: u64) -> TcmResult<usize> {
+        let filled = out.len();
+        out.resize(filled + usize::try_from(max).unwrap_or(CHUNK_SIZE), 0);
+        let read = loop {
+            match self.read(&mut out[filled..]) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    out.truncate(filled);
+                    return Err(e.into());
+                }
+            }
+        };
+        out.truncate(filled + read);
+        Ok(read)
+    }
+}
+
+impl ByteSource for SliceReader<'_> {
+    fn offset(&self) -> u64 {
+        self.position() as u64
+    }
+
+    fn read_rest(&mut self, out: &mut Vec<u8>, max: u64) -> TcmResult<()> {
+        let rest = self.rest();
+        let len = rest.len().min(usize::try_from(max).unwrap_or(usize::MAX));
+        out.extend_from_slice(&rest[..len]);
+        self.skip(len);
+        Ok(())
+    }
+
+    fn read_some(&mut self, out: &mut Vec<u8>, max: u64) -> TcmResult<usize> {
+        let filled = out.len();
+        self.read_rest(out, max)?;
+        Ok(out.len() - filled)
+    }
+
+    /// Decodes everything in memory in one go, stopping short of the byte limit.
+    #[inline]
+    fn decode_v2(
+        &mut self,
+        decoder: &mut v2::Decoder,
+        _ahead: &mut Vec<u8>,
+        out: &mut Vec<InputCommand>,
+        until: usize,
+        limits: &mut LimitTracker,
+        strict: bool,
+    ) -> TcmResult<Option<u64>> {
+        let until = until.min(max_inputs(out, limits));
+        let offset = self.offset();
+        let rest = self.rest();
+        let used = decode_v2_slice(decoder, rest, offset, out, until, limits, strict)?;
+        if out.len() >= until {
+            self.skip(used);
+            return Ok(None);
+        }
+        // The decoder has read what there is of the blob cut short.
+        self.skip(rest.len());
+        Ok(Some(offset + used as u64))
+    }
+}
+
+/// The length `out` may grow to before the input limit is exceeded; going one input past
+/// it is enough to report it.
+#[inline]
+fn max_inputs(out: &[InputCommand], limits: &LimitTracker) -> usize {
+    out.len()
+        .saturating_add(limits.inputs_left())
+        .saturating_add(1)
+}
+
+/// Decodes the part of a v2 stream held in `bytes`, which starts at `offset`, returning
+/// the number of bytes used.
+///
+/// Decoding stops short of the byte limit, which is reported if `bytes` go past it and
+/// `out` hasn't got all the inputs asked for.
+#[inline]
+fn decode_v2_slice(
+    decoder: &mut v2::Decoder,
+    bytes: &[u8],
+    offset: u64,
+    out: &mut Vec<InputCommand>,
+    until: usize,
+    limits: &mut LimitTracker,
+    strict: bool,
+) -> TcmResult<usize> {
+    let len = bytes
+        .len()
+        .min(usize::try_from(limits.bytes_left(offset)).unwrap_or(usize::MAX));
+    let used = decoder.decode(&bytes[..len], offset, out, until, limits, strict)?;
+    if out.len() < until {
+        limits.check_bytes(offset + bytes.len() as u64)?;
+    }
+    Ok(used)
+}
+
+/// The stream decoding state machine shared by every way of reading inputs.
+pub(crate) struct StreamCore<S: ByteSource, M: MetaFormat> {
+    reader: S,
+    meta: M,
+    options: ReadOptions,
+    limits: LimitTracker,
+    state: StreamState,
+    /// Inputs decoded ahead of the iterator, handed out from `pending_at` on
+    pending: Vec<InputCommand>,
+    pending_at: usize,
+    /// An error met decoding ahead, handed out once the inputs before it are
+    failed: Option<TcmError>,
+    /// Bytes read from the reader that the v2 decoder hasn't used yet
+    ahead: Vec<u8>,
+    /// Whether the end of the stream is checked the way lenient recovery needs it
+    recovering: bool,
+    announced: Option<usize>,
+    last_frame: Frame,
+}
+
+impl<S: ByteSource, M: MetaFormat> StreamCore<S, M> {
+    pub(crate) fn open(mut reader: S, options: &ReadOptions, recovering: bool) -> TcmResult<Self> {
+        let meta: M = read_preamble(&mut reader, options)?;
+
+        let state = match Codec::for_version(meta.version())? {
+            Codec::V1 => StreamState::V1Start,
+            Codec::V2 => StreamState::V2Start,
+            Codec::Registered(codec) => StreamState::Registered(Feed::new(codec.decoder())),
+        };
+
+        Ok(Self::start(reader, meta, state, options, recovering))
+    }
+
+    /// Sets up decoding of an input stream starting at the current reader position.
+    pub(crate) fn start(
+        reader: S,
+        meta: M,
+        state: StreamState,
+        options: &ReadOptions,
+        recovering: bool,
+    ) -> Self {
+        Self {
+            reader,
+            meta,
+            options: options.clone(),
+            limits: LimitTracker::new(&options.limits),
+            state,
+            pending: Vec::new(),
+            pending_at: 0,
+            failed: None,
+            ahead: Vec::new(),
+            recovering,
+            announced: None,
+            last_frame: 0,
+        }
+    }
+
+    pub(crate) fn meta(&self) -> &M {
+        &self.meta
+    }
+
+    pub(crate) fn into_meta(self) -> M {
+        self.meta
+    }
+
+    pub(crate) fn into_parts(self) -> (M, StreamState) {
+        (self.meta, self.state)
+    }
+
+    pub(crate) fn limits_mut(&mut self) -> &mut LimitTracker {
+        &mut self.limits
+    }
+
+    pub(crate) fn offset(&self) -> u64 {
+        self.reader.offset()
+    }
+
+    /// The input count announced by a v1 stream, once it has been read.
+    pub(crate) fn announced(&self) -> Option<usize> {
+        self.announced
+    }
+
+    pub(crate) fn reader_mut(&mut self) -> &mut S {
+        &mut self.reader
+    }
+
+    /// Continues a v2 stream with a decoder set up for the current reader position.
+    pub(crate) fn resume_v2(&mut self, decoder: v2::Decoder) -> TcmResult<()> {
+        let version = self.meta.version();
+        if !matches!(Codec::for_version(version)?, Codec::V2) {
+            return Err(TcmError::invalid_input(format!(
+                "Only v2 streams can be resumed, this replay is v{}",
+                version
+            )));
+        }
+        self.state = StreamState::V2(decoder);
+        Ok(())
+    }
+
+    fn checks_end(&self) -> bool {
+        self.options.strict || self.recovering
+    }
+
+    /// Upper bound on the number of bytes the next call to `advance` reads.
+    ///
+    /// Lets callers that fill the reader from elsewhere make sure a unit is complete
+    /// before decoding it.
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn unit_len(&self) -> usize {
+        match &self.state {
+            // A varint spans at most ten bytes.
+            StreamState::V1Start | StreamState::V2Start => 10,
+            StreamState::V1 { .. } => 11,
+            StreamState::V1End => 1,
+            StreamState::V2(decoder) => decoder.unit_len(),
+            StreamState::Registered(feed) => {
+                let len = feed.read_len(&self.limits, self.reader.offset());
+                usize::try_from(len).unwrap_or(usize::MAX)
+            }
+            StreamState::Trailing => 1,
+            StreamState::Done => 0,
+        }
+    }
+
+    /// Decodes the next unit of the stream into `pending`.
+    ///
+    /// Returns `Ok(false)` once there is nothing left to decode.
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn advance(&mut self) -> TcmResult<bool> {
+        let mut pending = std::mem::take(&mut self.pending);
+        let result = self.decode(&mut pending, 0);
+        self.pending = pending;
+        result
+    }
+
+    /// Decodes the next unit of the stream into `out`, then keeps going while `out`
+    /// holds fewer than `until` inputs and the stream is in the middle of its inputs.
+    /// v2 streams from readers may stop sooner, see [`ByteSource::decode_v2`].
+    ///
+    /// The v1 and v2 input loops run in here rather than coming back through the state
+    /// dispatch for every input, which is what decoding time is spent on. Inputs are
+    /// checked as they are decoded; one failing the checks isn't added to `out`.
+    /// Returns `Ok(false)` once there is nothing left to decode.
+    #[inline]
+    pub(crate) fn decode(&mut self, out: &mut Vec<InputCommand>, until: usize) -> TcmResult<bool> {
+        let mut offset = self.reader.offset();
+
+        match &mut self.state {
+            StreamState::V1Start => {
+                let count = read_var_u32(&mut self.reader, "v1 input count", self.options.strict)
+                    .map_err(|e| TcmError::decode(e, offset, 0, 0, None))?
+                    as usize;
+                self.limits.check_count(count)?;
+                self.announced = Some(count);
+                self.state = StreamState::V1 { index: 0, count };
+            }
+            StreamState::V1 { index, count } => loop {
+                if *index == *count {
+                    self.state = StreamState::V1End;
+                    return Ok(true);
+                }
+                let input = read_v1_input(&mut self.reader, self.options.strict)
+                    .map_err(|e| TcmError::decode(e, offset, *index, self.last_frame, None))?;
+                *index += 1;
+                check_input(&mut self.limits, self.options.strict, &input)?;
+                self.last_frame = input.frame;
+                out.push(input);
+
+                offset = self.reader.offset();
+                self.limits.check_bytes(offset)?;
+                if out.len() >= until {
+                    return Ok(true);
+                }
+            },
+            StreamState::V1End => {
+                // Permissive reads have never looked at the end of macro marker.
+                if self.checks_end() {
+                    let err = match read_array(&mut self.reader, "v1 end of macro marker") {
+                        Ok([v1::EOM]) => None,
+                        Ok(_) => Some(TcmError::MissingEndMarker),
+                        Err(TcmError::UnexpectedEof { .. }) if !self.recovering => {
+                            Some(TcmError::MissingEndMarker)
+                        }
+                        Err(e) => Some(e),
+                    };
+                    if let Some(e) = err {
+                        let index = self.announced.unwrap_or(0);
+                        return Err(TcmError::decode(e, offset, index, self.last_frame, None));
+                    }
+                }
+                self.state = StreamState::Trailing;
+            }
+            StreamState::V2Start => {
+                self.state = match v2::Decoder::start(&mut self.reader, self.options.strict)? {
+                    Some(decoder) => StreamState::V2(decoder),
+                    None => StreamState::Trailing,
+                };
+            }
+            StreamState::V2(decoder) => {
+                let strict = self.options.strict;
+                let start = out.len();
+                let left = self.limits.inputs_left();
+                let (ahead, limits) = (&mut self.ahead, &mut self.limits);
+                let result = self
+                    .reader
+                    .decode_v2(decoder, ahead, out, until, limits, strict);
+                if let Err(e) = self.limits.add_inputs(out.len() - start) {
+                    out.truncate(start.saturating_add(left));
+                    return Err(e);
+                }
+                let Some(end) = result? else {
+                    return Ok(true);
+                };
+                if self.options.strict || self.recovering {
+                    decoder.check_end(end)?;
+                } else {
+                    decoder.check_permissive_end(end)?;
+                }
+                self.state = StreamState::Trailing;
+            }
+            StreamState::Registered(feed) => loop {
+                let max = feed.read_len(&self.limits, self.reader.offset());
+                if max > 0 {
+                    feed.buffer.drain(..feed.used);
+                    feed.used = 0;
+                    let filled = feed.buffer.len();
+                    self.reader.read_rest(&mut feed.buffer, max)?;
+                    feed.at_end = ((feed.buffer.len() - filled) as u64) < max;
+                    self.limits.check_bytes(self.reader.offset())?;
+                }
+
+                let bytes = &feed.buffer[feed.used..];
+                let start = self.reader.offset() - bytes.len() as u64;
+                // At least one input per call, as with the built-in versions.
+                let wanted = until.max(out.len() + 1);
+                let strict = self.options.strict;
+                let mut sink = InputSink::new(out, &mut self.limits, strict, wanted, start);
+                let used =
+                    feed.decoder
+                        .decode(bytes, feed.at_end, &mut sink)
+                        .map_err(|e| match e {
+                            TcmError::LimitExceeded { .. }
+                            | TcmError::Decode { .. }
+                            | TcmError::TrailingData { .. } => e,
+                            e => TcmError::decode(e, start, 0, self.last_frame, None),
+                        })?;
+                let full = sink.is_full();
+                let used = used.min(bytes.len());
+                feed.used += used;
+                let left = feed.buffer.len() - feed.used;
+                if let Some(input) = out.last() {
+                    self.last_frame = input.frame;
+                }
+
+                if full {
+                    feed.hungry = left == 0;
+                    return Ok(true);
+                }
+                if feed.at_end {
+                    if self.options.strict && left > 0 {
+                        return Err(TcmError::TrailingData {
+                            offset: start + used as u64,
+                        });
+                    }
+                    self.state = StreamState::Done;
+                    return Ok(true);
+                }
+                feed.hungry = true;
+                if until == 0 {
+                    return Ok(true);
+                }
+            },
+            StreamState::Trailing => {
+                if self.options.strict {
+                    match read_array::<1>(&mut self.reader, "trailing data") {
+                        Ok(_) => return Err(TcmError::TrailingData { offset }),
+                        Err(TcmError::UnexpectedEof { .. }) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                self.state = StreamState::Done;
+            }
+            StreamState::Done => return Ok(false),
+        }
+
+        self.limits.check_bytes(self.reader.offset())?;
+        Ok(true)
+    }
+
+    /// Hands out the next input that is already decoded, if any.
+    #[inline]
+    pub(crate) fn pop_pending(&mut self) -> Option<InputCommand> {
+        let input = self.pending.get(self.pending_at)?.clone();
+        self.pending_at += 1;
+        if self.pending_at == self.pending.len() {
+            self.pending.clear();
+            self.pending_at = 0;
+        }
+        Some(input)
+    }
+
+    /// Decodes every remaining input into `out`.
+    ///
+    /// Does the same as collecting the iterator, decoding straight into `out` instead of
+    /// going through `pending` one input at a time.
+    pub(crate) fn read_to_end(&mut self, out: &mut Vec<InputCommand>) -> TcmResult<()> {
+        out.extend(self.pending.drain(self.pending_at..));
+        self.pending_at = 0;
+        if let Some(e) = self.failed.take() {
+            self.state = StreamState::Done;
+            return Err(e);
+        }
+        loop {
+            match self.decode(out, usize::MAX) {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(e) => {
+                    self.state = StreamState::Done;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Turns the outcome of a decode into an iterator item, stopping the stream on errors.
+    pub(crate) fn yield_item(
+        &mut self,
+        result: TcmResult<Option<InputCommand>>,
+    ) -> Option<TcmResult<InputCommand>> {
+        match result {
+            Ok(input) => input.map(Ok),
+            Err(e) => {
+                self.state = StreamState::Done;
+                self.pending.clear();
+                self.pending_at = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn next_input(&mut self) -> TcmResult<Option<InputCommand>> {
+        loop {
+            if let Some(input) = self.pop_pending() {
+                return Ok(Some(input));
+            }
+            if let Some(e) = self.failed.take() {
+                return Err(e);
+            }
+            // Inputs are decoded a batch or v2 chunk at a time. The inputs decoded before an
+            // error are still handed out first, as decoding one at a time would have.
+            let mut pending = std::mem::take(&mut self.pending);
+            let result = self.decode(&mut pending, BATCH);
+            self.pending = pending;
+            match result {
+                Ok(true) => {}
+                Ok(false) => return Ok(None),
+                Err(e) => self.failed = Some(e),
+            }
+        }
+    }
+}
+
+/// Runs the per-input checks on a decoded input before it is handed out.
+#[inline]
+fn check_input(limits: &mut LimitTracker, strict: bool, input: &InputCommand) -> TcmResult<()> {
+    limits.add_inputs(1)?;
+    limits.check_contents(input, strict)
+}
+
+impl<S: ByteSource, M: MetaFormat> Iterator for StreamCore<S, M> {
+    type Item = TcmResult<InputCommand>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(input) = self.pop_pending() {
+            return Some(Ok(input));
+        }
+        let result = self.next_input();
+        self.yield_item(result)
+    }
+}
+
+/// An iterator over the inputs of a replay that decodes them a batch at a time.
+///
+/// The header and metadata are read up front by [`InputStream::new`]; inputs are then
+/// decoded on demand from the underlying reader, a few thousand at a time (for v2, what
+/// a 64 KiB chunk of the reader holds), so memory use doesn't grow with the size of the
+/// replay. A live reader is only waited on until a read completes an input. After the
+/// first error the stream yields `None`.
+///
+/// Use [`DynamicInputStream`] to detect the format version from the metadata.
+///
+/// # Example
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use tcm::stream::DynamicInputStream;
+///
+/// let file = BufReader::new(File::open("replay.tcm").unwrap());
+/// let mut presses = 0;
+/// for input in DynamicInputStream::new(file).unwrap() {
+///     if let tcm::input::Input::Vanilla(v) = input.unwrap().input {
+///         presses += v.push as usize;
+///     }
+/// }
+/// println!("{} presses", presses);
+/// ```
+pub struct InputStream<R: Read, M: MetaFormat> {
+    core: StreamCore<CountingReader<R>, M>,
+}
+
+/// An input stream with dynamic metadata type, for any supported format version.
+pub type DynamicInputStream<R> = InputStream<R, AnyMeta>;
+
+impl<R: Read, M: MetaFormat> InputStream<R, M> {
+    /// Reads the header and metadata and prepares to decode inputs.
+    pub fn new(reader: R) -> TcmResult<Self> {
+        Self::with_options(reader, &ReadOptions::default())
+    }
+
+    /// Like [`InputStream::new`], applying the given [`ReadOptions`] to the whole stream.
+    pub fn with_options(reader: R, options: &ReadOptions) -> TcmResult<Self> {
+        Self::open(reader, options, false)
+    }
+
+    pub(crate) fn open(reader: R, options: &ReadOptions, recovering: bool) -> TcmResult<Self> {
+        let core = StreamCore::open(CountingReader::new(reader), options, recovering)?;
+        Ok(Self { core })
+    }
+
+    /// The replay metadata.
+    pub fn meta(&self) -> &M {
+        self.core.meta()
+    }
+
+    /// Consumes the stream, returning the replay metadata.
+    pub fn into_meta(self) -> M {
+        self.core.into_meta()
+    }
+
+    /// Number of bytes consumed from the reader so far, header included.
+    pub fn offset(&self) -> u64 {
+        self.core.offset()
+    }
+
+    pub(crate) fn core_mut(&mut self) -> &mut StreamCore<CountingReader<R>, M> {
+        &mut self.core
+    }
+}
+
+impl<R: Read + Seek, M: MetaFormat> InputStream<R, M> {
+    /// Picks up decoding a v2 replay at a checkpoint of its [`SeekIndex`].
+    ///
+    /// The index is checked against the replay first (see [`SeekIndex::check_replay`]),
+    /// and the checkpoint has to be one of its own. The header and metadata are then read
+    /// from the start of the reader, which is moved to the checkpoint. The stream yields
+    /// the inputs from the checkpoint's input index on.
+    pub fn resume(reader: R, index: &SeekIndex, checkpoint: &Checkpoint) -> TcmResult<Self> {
+        Self::resume_with_options(reader, index, checkpoint, &ReadOptions::default())
+    }
+
+    /// Like [`InputStream::resume`], applying the given [`ReadOptions`] to the rest of the
+    /// stream.
+    ///
+    /// The inputs and bytes before the checkpoint count towards the limits; TPS changes
+    /// before it aren't recorded by the index and only those after it are counted.
+    pub fn resume_with_options(
+        mut reader: R,
+        index: &SeekIndex,
+        checkpoint: &Checkpoint,
+        options: &ReadOptions,
+    ) -> TcmResult<Self> {
+        index.check_replay(&mut reader)?;
+        if index.checkpoint_for_input(checkpoint.input_index) != Some(checkpoint) {
+            return Err(TcmError::IndexMismatch {
+                message: format!(
+                    "no checkpoint at input {} matches the one given",
+                    checkpoint.input_index
+                ),
+            });
+        }
+
+        reader.seek(SeekFrom::Start(0))?;
+        let mut stream = Self::with_options(reader, options)?;
+        let limits = stream.core.limits_mut();
+        limits.add_inputs(checkpoint.input_index)?;
+        limits.check_bytes(checkpoint.offset)?;
+
+        let decoder = v2::Decoder::resume(
+            checkpoint.frame,
+            checkpoint.last_delta,
+            checkpoint.state,
+            checkpoint.delta_bits,
+            checkpoint.input_index,
+        )?;
+        let reader = stream.core.reader_mut();
+        reader.get_mut().seek(SeekFrom::Start(checkpoint.offset))?;
+        reader.offset = checkpoint.offset;
+        stream.core.resume_v2(decoder)?;
+        Ok(stream)
+    }
+}
+
+impl<R: Read, M: MetaFormat> Iterator for InputStream<R, M> {
+    type Item = TcmResult<InputCommand>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.core.next()
+    }
+}
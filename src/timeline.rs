@@ -0,0 +1,317 @@
+//! A single clock running across all attempts of a replay.
+//!
+//! Frames count from 0 again after every restart, so on their own they can't say when
+//! something happened in a recording session. A [`Timeline`] lays the attempts end to
+//! end on a global tick, and integrates the tick rate, TPS changes included, into the
+//! wall-clock time elapsed since the first tick.
+
+use crate::{
+    input::Input,
+    meta::{check_tps, MetaFormat},
+    replay::Replay,
+    Frame,
+};
+
+/// The global tick and elapsed time of an input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimelineEntry {
+    /// Attempt the input belongs to
+    pub attempt: usize,
+    /// Ticks since the start of the replay
+    pub tick: u64,
+    /// Seconds since the start of the replay
+    pub seconds: f64,
+}
+
+/// How long a tick lasts, kept in the form the replay stores it in.
+///
+/// Dividing by a TPS value or multiplying by a delta time once per conversion, instead
+/// of adding up ticks or converting between the two, keeps long replays free of drift.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rate {
+    Tps(f64),
+    Dt(f64),
+}
+
+impl Rate {
+    fn seconds(self, ticks: u64) -> f64 {
+        match self {
+            Rate::Tps(tps) => ticks as f64 / tps,
+            Rate::Dt(dt) => ticks as f64 * dt,
+        }
+    }
+
+    fn ticks(self, seconds: f64) -> f64 {
+        match self {
+            Rate::Tps(tps) => seconds * tps,
+            Rate::Dt(dt) => seconds / dt,
+        }
+    }
+}
+
+/// A stretch of ticks running at the same rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    tick: u64,
+    seconds: f64,
+    rate: Rate,
+}
+
+/// Global ticks and elapsed time for every input of a replay.
+///
+/// Each attempt lasts from its frame 0 through its latest input, and the next attempt
+/// starts on the following tick. A TPS change takes effect on its own tick and stays in
+/// effect across restarts; changes to a TPS that isn't a positive finite number are
+/// ignored.
+///
+/// # Example
+/// ```
+/// use tcm::input::{PlayerButton::Jump, RestartType};
+/// use tcm::ReplayBuilder;
+///
+/// let replay = ReplayBuilder::v2(240.0)
+///     .at(479).restart(RestartType::Death)
+///     .tps(480.0)
+///     .at(480).press(Jump)
+///     .build()
+///     .unwrap();
+/// let timeline = replay.timeline();
+/// let press = timeline.entries()[2];
+/// assert_eq!((press.attempt, press.tick, press.seconds), (1, 960, 3.0));
+/// assert_eq!(timeline.locate(960), Some((1, 480)));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    /// First global tick of every attempt
+    starts: Vec<u64>,
+    segments: Vec<Segment>,
+    entries: Vec<TimelineEntry>,
+}
+
+impl<M: MetaFormat> Replay<M> {
+    /// Lays the attempts of the replay out on a global tick.
+    pub fn timeline(&self) -> Timeline {
+        let rate = if self.meta.uses_dt() {
+            Rate::Dt(self.meta.tps_dt() as f64)
+        } else {
+            Rate::Tps(self.meta.tps() as f64)
+        };
+        let mut timeline = Timeline {
+            starts: Vec::new(),
+            segments: vec![Segment {
+                tick: 0,
+                seconds: 0.0,
+                rate,
+            }],
+            entries: Vec::with_capacity(self.inputs.len()),
+        };
+
+        let mut start = 0;
+        for attempt in self.attempts() {
+            timeline.starts.push(start);
+            for input in attempt.inputs {
+                let tick = start.saturating_add(input.frame);
+                if let Input::Tps(tps) = &input.input {
+                    if check_tps(tps.tps).is_ok() {
+                        timeline.change_rate(tick, Rate::Tps(tps.tps as f64));
+                    }
+                }
+                timeline.entries.push(TimelineEntry {
+                    attempt: attempt.index,
+                    tick,
+                    seconds: timeline.seconds_at(tick),
+                });
+            }
+            start = start.saturating_add(attempt.frames.end);
+        }
+        timeline
+    }
+
+    /// Seconds from the start of `attempt` to the start of its `frame`, following the
+    /// TPS changes of the replay.
+    ///
+    /// Builds the [`Timeline`] on every call; keep one around for many conversions.
+    ///
+    /// # Example
+    /// ```
+    /// use tcm::input::RestartType;
+    /// use tcm::ReplayBuilder;
+    ///
+    /// let replay = ReplayBuilder::v2(240.0)
+    ///     .at(480).tps(60.0)
+    ///     .at(600).restart(RestartType::Death)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(replay.frame_to_seconds(0, 540), Some(3.0));
+    /// assert_eq!(replay.frame_to_seconds(1, 60), Some(1.0));
+    /// assert_eq!(replay.seconds_to_frame(0, 3.0), Some(540));
+    /// assert_eq!(replay.seconds_to_frame(0, 60.0), None);
+    /// ```
+    pub fn frame_to_seconds(&self, attempt: usize, frame: Frame) -> Option<f64> {
+        self.timeline().frame_to_seconds(attempt, frame)
+    }
+
+    /// The frame of `attempt` running at `seconds` after the start of the attempt.
+    ///
+    /// See [`Timeline::seconds_to_frame`].
+    pub fn seconds_to_frame(&self, attempt: usize, seconds: f64) -> Option<Frame> {
+        self.timeline().seconds_to_frame(attempt, seconds)
+    }
+}
+
+impl Timeline {
+    /// The tick and time of every input, in the order of the inputs.
+    pub fn entries(&self) -> &[TimelineEntry] {
+        &self.entries
+    }
+
+    /// Number of attempts on the timeline.
+    pub fn attempts(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The global tick of `frame` in `attempt`.
+    ///
+    /// Returns `None` for attempts that don't exist and for frames past the end of an
+    /// attempt that was restarted; the last attempt runs on indefinitely.
+    pub fn tick_at(&self, attempt: usize, frame: Frame) -> Option<u64> {
+        let start = *self.starts.get(attempt)?;
+        let tick = start.checked_add(frame)?;
+        match self.starts.get(attempt + 1) {
+            Some(&next) if tick >= next => None,
+            _ => Some(tick),
+        }
+    }
+
+    /// The attempt and frame a global tick falls on.
+    ///
+    /// Returns `None` only for a replay without inputs.
+    pub fn locate(&self, tick: u64) -> Option<(usize, Frame)> {
+        let attempt = self
+            .starts
+            .partition_point(|&start| start <= tick)
+            .checked_sub(1)?;
+        Some((attempt, tick - self.starts[attempt]))
+    }
+
+    /// Seconds elapsed from the start of the replay to the start of `tick`.
+    pub fn seconds_at(&self, tick: u64) -> f64 {
+        let segment = self.segment_at(|segment| segment.tick <= tick);
+        segment.seconds + segment.rate.seconds(tick - segment.tick)
+    }
+
+    /// The tick running at `seconds` into the replay.
+    ///
+    /// This is the last tick starting at or before `seconds`, so it undoes
+    /// [`Timeline::seconds_at`] exactly. Negative times give tick 0.
+    pub fn tick_at_seconds(&self, seconds: f64) -> u64 {
+        let segment = self.segment_at(|segment| segment.seconds <= seconds);
+        let estimate = segment.rate.ticks(seconds - segment.seconds).max(0.0) as u64;
+        let mut tick = segment.tick.saturating_add(estimate);
+
+        // The float estimate can land a tick off either way; settle it on the clock itself.
+        while tick > segment.tick && self.seconds_at(tick) > seconds {
+            tick -= 1;
+        }
+        while tick < u64::MAX && self.seconds_at(tick + 1) <= seconds {
+            tick += 1;
+        }
+        tick
+    }
+
+    /// Seconds from the start of `attempt` to the start of its `frame`.
+    ///
+    /// Each stretch of ticks at the same rate is converted on its own, so within an
+    /// attempt that doesn't change TPS this is exactly `frame / tps`, or `frame * dt` for
+    /// replays storing a delta time. Returns `None` where [`Timeline::tick_at`] does.
+    pub fn frame_to_seconds(&self, attempt: usize, frame: Frame) -> Option<f64> {
+        let tick = self.tick_at(attempt, frame)?;
+        Some(self.seconds_between(self.starts[attempt], tick))
+    }
+
+    /// The frame of `attempt` running at `seconds` after the start of the attempt.
+    ///
+    /// This is the last frame starting at or before `seconds`, so it undoes
+    /// [`Timeline::frame_to_seconds`] exactly. Returns `None` for negative, NaN or
+    /// infinite times, for attempts that don't exist and for times past the end of an
+    /// attempt that was restarted.
+    pub fn seconds_to_frame(&self, attempt: usize, seconds: f64) -> Option<Frame> {
+        if !seconds.is_finite() || seconds < 0.0 {
+            return None;
+        }
+        let start = *self.starts.get(attempt)?;
+        let end = self.starts.get(attempt + 1).copied().unwrap_or(u64::MAX);
+
+        // Walk the segments of the attempt to the one the time falls into.
+        let first = self
+            .segments
+            .partition_point(|segment| segment.tick <= start)
+            - 1;
+        let (mut tick, mut elapsed) = (start, 0.0);
+        let mut estimate = start;
+        for (at, segment) in self.segments.iter().enumerate().skip(first) {
+            let next = self.segments.get(at + 1).map_or(u64::MAX, |next| next.tick);
+            let span = segment.rate.seconds(next - tick);
+            if next == u64::MAX || elapsed + span > seconds {
+                let ticks = segment.rate.ticks(seconds - elapsed).max(0.0) as u64;
+                estimate = tick.saturating_add(ticks).min(next);
+                break;
+            }
+            (tick, elapsed) = (next, elapsed + span);
+        }
+
+        // The float estimate can land a frame off either way; settle it on the clock itself.
+        let mut frame = estimate.saturating_sub(start);
+        while frame > 0 && self.seconds_between(start, start + frame) > seconds {
+            frame -= 1;
+        }
+        while start.saturating_add(frame) < u64::MAX
+            && self.seconds_between(start, start + frame + 1) <= seconds
+        {
+            frame += 1;
+        }
+        (start.saturating_add(frame) < end).then_some(frame)
+    }
+
+    /// Seconds from the start of tick `from` to the start of tick `to`.
+    fn seconds_between(&self, from: u64, to: u64) -> f64 {
+        let first = self
+            .segments
+            .partition_point(|segment| segment.tick <= from)
+            - 1;
+        let mut seconds = 0.0;
+        for (at, segment) in self.segments.iter().enumerate().skip(first) {
+            if segment.tick >= to && at > first {
+                break;
+            }
+            let begin = segment.tick.max(from);
+            let end = self
+                .segments
+                .get(at + 1)
+                .map_or(to, |next| next.tick.min(to));
+            seconds += segment.rate.seconds(end.saturating_sub(begin));
+        }
+        seconds
+    }
+
+    /// The last segment of those matching `before`, which have to come first.
+    fn segment_at(&self, before: impl Fn(&Segment) -> bool) -> Segment {
+        let after = self.segments.partition_point(before);
+        self.segments[after.saturating_sub(1)]
+    }
+
+    fn change_rate(&mut self, tick: u64, rate: Rate) {
+        // Frames running backwards can't move a change before the ones already made.
+        let last = self.segments[self.segments.len() - 1];
+        let tick = tick.max(last.tick);
+        let seconds = self.seconds_at(tick);
+        if last.tick == tick {
+            self.segments.pop();
+        }
+        self.segments.push(Segment {
+            tick,
+            seconds,
+            rate,
+        });
+    }
+}
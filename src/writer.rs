@@ -0,0 +1,428 @@
+//! Incremental writing of replays, one input at a time.
+
+use std::fs::OpenOptions;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::{
+    codec::{Codec, InputEncoder},
+    error::{TcmError, TcmResult},
+    input::InputCommand,
+    meta::{AnyMeta, MetaFormat, MetaInfo},
+    replay::{
+        read_array, read_v1_input, read_var_u32, v1, v2, CountingReader, DeserializerBlob,
+        HEADER_SIZE, PREFIX_SIZE, TCBOT_HEADER,
+    },
+};
+
+/// Offset of the append counter within the metadata block, the same for every version.
+const APPEND_COUNTER_OFFSET: usize = 1;
+
+/// Width of a padded v1 input count, enough for any count.
+const COUNT_WIDTH: usize = 5;
+
+/// Size of the pieces the inputs of a resumed v1 replay are moved in.
+const MOVE_CHUNK: usize = 64 * 1024;
+
+/// Moves `len` bytes of the output from `from` forward to `to`.
+type MoveBody<W> = fn(&mut W, u64, u64, u64) -> TcmResult<()>;
+
+enum Encoding<W> {
+    V1 {
+        /// Position of the input count in the output
+        count_offset: u64,
+        /// Width of the input count currently in the output
+        count_width: usize,
+        count: u64,
+        /// Length of the inputs encoded so far
+        body_len: u64,
+        /// Moves the inputs along when the count outgrows its width, for outputs that
+        /// can be read back; otherwise the count is padded to full width from the start
+        move_body: Option<MoveBody<W>>,
+    },
+    V2(v2::Encoder),
+    /// A registered codec's encoder and the bytes it hands over
+    Registered(Box<dyn InputEncoder>, Vec<u8>),
+}
+
+/// Writes a replay as its inputs come in, for recording live.
+///
+/// The header and metadata are written by [`InputWriter::new`] and every input is
+/// passed on to the writer as soon as it can be encoded. Once [`InputWriter::finish`]
+/// returns, the output is byte-identical to what
+/// [`ReplaySerializer::serialize`](crate::replay::ReplaySerializer::serialize) writes for
+/// the same replay.
+///
+/// v2 action bytes carry the size of the delta to the next input, so the most recent
+/// input is held back until the next one arrives. v1 streams start with their input
+/// count, which is patched in place on [`InputWriter::flush`] and
+/// [`InputWriter::finish`]; when it needs another byte, the inputs written so far are
+/// read back and moved along, which happens at most four times. Outputs that can't be
+/// read back can use [`InputWriter::new_padded`] instead.
+/// Versions plugged in through [`codec::register`](crate::codec::register) write what
+/// their [`InputEncoder`] hands over.
+///
+/// After an error the output is incomplete and the writer shouldn't be used further.
+///
+/// # Example
+/// ```no_run
+/// use std::fs::File;
+/// use tcm::input::{Input, InputCommand, PlayerButton, VanillaInput};
+/// use tcm::meta::MetaV2;
+/// use tcm::writer::InputWriter;
+///
+/// let file = File::create("live.tcm").unwrap();
+/// let mut writer = InputWriter::new(file, &MetaV2::new(240.0, 0, None)).unwrap();
+/// for frame in [10, 20, 30] {
+///     let input = Input::Vanilla(VanillaInput {
+///         button: PlayerButton::Jump,
+///         push: frame != 20,
+///         player2: false,
+///     });
+///     writer.push(&InputCommand::new(frame, input)).unwrap();
+/// }
+/// writer.finish().unwrap();
+/// ```
+pub struct InputWriter<W: Write + Seek> {
+    writer: W,
+    encoding: Encoding<W>,
+}
+
+impl<W: Read + Write + Seek> InputWriter<W> {
+    /// Writes the header and metadata, ready to take inputs.
+    pub fn new<M: MetaFormat>(writer: W, meta: &M) -> TcmResult<Self> {
+        Self::start(writer, meta, Some(move_forward::<W>))
+    }
+}
+
+impl<W: Write + Seek> InputWriter<W> {
+    /// Writes the header and metadata to an output that can't be read back, such as a
+    /// [`BufWriter`](std::io::BufWriter).
+    ///
+    /// v1 inputs can't be moved along to make room for a growing input count there, so
+    /// the count is padded to five bytes, as much as any count takes. That padded count
+    /// is the one difference from the output of
+    /// [`ReplaySerializer::serialize`](crate::replay::ReplaySerializer::serialize), and
+    /// strict reads refuse it as [`TcmError::OverlongVarint`]. Other versions are written
+    /// the same as by [`InputWriter::new`].
+    pub fn new_padded<M: MetaFormat>(writer: W, meta: &M) -> TcmResult<Self> {
+        Self::start(writer, meta, None)
+    }
+
+    fn start<M: MetaFormat>(
+        mut writer: W,
+        meta: &M,
+        move_body: Option<MoveBody<W>>,
+    ) -> TcmResult<Self> {
+        let codec = Codec::for_version(meta.version())?;
+        writer.write_all(&TCBOT_HEADER)?;
+        writer.write_all(&meta.to_bytes())?;
+
+        let encoding = match codec {
+            Codec::V1 => {
+                let count_offset = writer.stream_position()?;
+                let count_width = match move_body {
+                    Some(_) => count_width_of(0),
+                    None => COUNT_WIDTH,
+                };
+                write_count(&mut writer, 0, count_width)?;
+                Encoding::V1 {
+                    count_offset,
+                    count_width,
+                    count: 0,
+                    body_len: 0,
+                    move_body,
+                }
+            }
+            Codec::V2 => Encoding::V2(v2::Encoder::default()),
+            Codec::Registered(codec) => Encoding::Registered(codec.encoder(), Vec::new()),
+        };
+
+        Ok(Self { writer, encoding })
+    }
+
+    /// Encodes an input and writes it out, or holds it back until the next one for v2.
+    pub fn push(&mut self, input: &InputCommand) -> TcmResult<()> {
+        match &mut self.encoding {
+            Encoding::V1 {
+                count, body_len, ..
+            } => {
+                if *count == u32::MAX as u64 {
+                    return Err(TcmError::FrameOutOfRange {
+                        value: *count + 1,
+                        field: "v1 input count".to_string(),
+                    });
+                }
+                // A frame varint and the input byte.
+                let mut encoded = Cursor::new([0u8; 6]);
+                v1::write_input(&mut encoded, input)?;
+                let len = encoded.position() as usize;
+                self.writer.write_all(&encoded.get_ref()[..len])?;
+                *count += 1;
+                *body_len += len as u64;
+                Ok(())
+            }
+            Encoding::V2(encoder) => encoder.push(&mut self.writer, input),
+            Encoding::Registered(encoder, bytes) => {
+                encoder.push(input, bytes)?;
+                self.writer.write_all(bytes)?;
+                bytes.clear();
+                Ok(())
+            }
+        }
+    }
+
+    /// Brings the output up to date and flushes the writer.
+    ///
+    /// For v1 this patches the input count; a held back v2 input is only written once
+    /// the next input arrives or the writer is finished.
+    pub fn flush(&mut self) -> TcmResult<()> {
+        self.patch_count()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes out everything still pending, flushes and returns the writer.
+    pub fn finish(mut self) -> TcmResult<W> {
+        self.patch_count()?;
+        match &mut self.encoding {
+            Encoding::V1 { .. } => self.writer.write_all(&[v1::EOM])?,
+            Encoding::V2(encoder) => encoder.finish(&mut self.writer)?,
+            Encoding::Registered(encoder, bytes) => {
+                encoder.finish(bytes)?;
+                self.writer.write_all(bytes)?;
+            }
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn patch_count(&mut self) -> TcmResult<()> {
+        let Encoding::V1 {
+            count_offset,
+            count_width,
+            count,
+            body_len,
+            move_body,
+        } = &mut self.encoding
+        else {
+            return Ok(());
+        };
+
+        if let Some(move_body) = move_body {
+            let width = count_width_of(*count);
+            if width > *count_width {
+                let body = *count_offset + *count_width as u64;
+                let to = *count_offset + width as u64;
+                move_body(&mut self.writer, body, to, *body_len)?;
+                *count_width = width;
+            }
+        }
+
+        self.writer.seek(SeekFrom::Start(*count_offset))?;
+        write_count(&mut self.writer, *count, *count_width)?;
+        let end = *count_offset + *count_width as u64 + *body_len;
+        self.writer.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+}
+
+impl<W: Read + Write + Seek> InputWriter<W> {
+    /// Continues an existing replay, appending inputs after the ones already written.
+    ///
+    /// The existing input stream is read once to find where it ends and to pick up the
+    /// encoder state there; only the tail of the stream is rewritten. For v1 that is the
+    /// end of macro marker and the input count, which keeps its width until it outgrows
+    /// it; the inputs are then moved along, read back from the writer. For v2
+    /// it is the last action byte, which is written again announcing the delta to the
+    /// first appended input, so the result is byte-identical to serializing all inputs in
+    /// one go. The append counter in the metadata is incremented; a replay whose counter
+    /// is already at 255 is refused.
+    ///
+    /// Files with trailing data after a v1 stream or with a truncated input stream are
+    /// refused, as appending would bury the damage in the middle of the replay. So are
+    /// versions plugged in through [`codec::register`](crate::codec::register): where
+    /// their metadata keeps the append counter and what their encoder holds back at the
+    /// end of the stream is up to the codec.
+    pub fn resume(mut writer: W) -> TcmResult<Self> {
+        writer.seek(SeekFrom::Start(0))?;
+        let mut reader = CountingReader::new(BufReader::new(&mut writer));
+
+        let prefix: [u8; PREFIX_SIZE] = read_array(&mut reader, "header and metadata")?;
+        if prefix[..HEADER_SIZE] != TCBOT_HEADER {
+            return Err(TcmError::InvalidHeader);
+        }
+        let meta = AnyMeta::from_bytes(&prefix[HEADER_SIZE..])?;
+        let counter = meta.append_counter().checked_add(1).ok_or_else(|| {
+            TcmError::invalid_metadata(
+                "The append counter is at 255, the replay can't be appended to again",
+            )
+        })?;
+
+        let (end, encoding) = match Codec::for_version(meta.version())? {
+            Codec::V1 => resume_v1::<W>(&mut reader)?,
+            Codec::V2 => resume_v2(&mut reader)?,
+            Codec::Registered(_) => return Err(not_resumable(meta.version())),
+        };
+        drop(reader);
+
+        writer.seek(SeekFrom::Start(
+            (HEADER_SIZE + APPEND_COUNTER_OFFSET) as u64,
+        ))?;
+        writer.write_all(&[counter])?;
+        writer.seek(SeekFrom::Start(end))?;
+
+        Ok(Self { writer, encoding })
+    }
+}
+
+/// Reads a v1 input stream, returning where the end of macro marker starts.
+fn resume_v1<W: Read + Write + Seek>(
+    reader: &mut CountingReader<impl Read>,
+) -> TcmResult<(u64, Encoding<W>)> {
+    let count_offset = reader.offset;
+    let count = read_var_u32(reader, "v1 input count", false)? as u64;
+    let count_width = (reader.offset - count_offset) as usize;
+
+    let body_offset = reader.offset;
+    for index in 0..count {
+        let offset = reader.offset;
+        read_v1_input(reader, false)
+            .map_err(|e| TcmError::decode(e, offset, index as usize, 0, None))?;
+    }
+    let eom_offset = reader.offset;
+    match read_array(reader, "v1 end of macro marker") {
+        Ok([v1::EOM]) => {}
+        Ok(_) | Err(TcmError::UnexpectedEof { .. }) => return Err(TcmError::MissingEndMarker),
+        Err(e) => return Err(e),
+    }
+    match read_array::<1>(reader, "trailing data") {
+        Ok(_) => {
+            return Err(TcmError::TrailingData {
+                offset: eom_offset + 1,
+            })
+        }
+        Err(TcmError::UnexpectedEof { .. }) => {}
+        Err(e) => return Err(e),
+    }
+
+    let encoding = Encoding::V1 {
+        count_offset,
+        count_width,
+        count,
+        body_len: eom_offset - body_offset,
+        move_body: Some(move_forward::<W>),
+    };
+    Ok((eom_offset, encoding))
+}
+
+/// Moves `len` bytes from `from` forward to `to`, starting from the end so nothing is
+/// overwritten before it is read.
+fn move_forward<W: Read + Write + Seek>(
+    writer: &mut W,
+    from: u64,
+    to: u64,
+    len: u64,
+) -> TcmResult<()> {
+    let mut chunk = vec![0; MOVE_CHUNK.min(len as usize)];
+    let mut left = len;
+    while left > 0 {
+        let size = left.min(chunk.len() as u64);
+        left -= size;
+        let chunk = &mut chunk[..size as usize];
+        writer.seek(SeekFrom::Start(from + left))?;
+        writer.read_exact(chunk)?;
+        writer.seek(SeekFrom::Start(to + left))?;
+        writer.write_all(chunk)?;
+    }
+    Ok(())
+}
+
+/// Number of bytes the shortest varint of `count` takes.
+fn count_width_of(count: u64) -> usize {
+    let bits = (u64::BITS - count.leading_zeros()).max(1) as usize;
+    (bits + 6) / 7
+}
+
+/// Writes a v1 input count as a varint of `width` bytes, padding it with continuation
+/// bytes if it is shorter.
+fn write_count(writer: &mut impl Write, count: u64, width: usize) -> TcmResult<()> {
+    let mut encoded = [0u8; COUNT_WIDTH];
+    let mut value = count;
+    for (i, byte) in encoded[..width].iter_mut().enumerate() {
+        let more = if i + 1 < width { 0x80 } else { 0 };
+        *byte = (value & 0x7F) as u8 | more;
+        value >>= 7;
+    }
+    writer.write_all(&encoded[..width])?;
+    Ok(())
+}
+
+/// Reads a v2 input stream, returning where its last action byte starts.
+fn resume_v2<W>(reader: &mut CountingReader<impl Read>) -> TcmResult<(u64, Encoding<W>)> {
+    let start = reader.offset;
+    let Some(mut decoder) = v2::Decoder::start(reader, false)? else {
+        return Ok((start, Encoding::V2(v2::Encoder::default())));
+    };
+    // Where the last action byte starts, the delta state before it and its inputs.
+    let mut last_action = None;
+    let mut last_inputs = Vec::new();
+    let mut decoded = Vec::new();
+    loop {
+        let offset = reader.offset;
+        let at_action = decoder.state() == DeserializerBlob::Action;
+        let last_delta = decoder.last_delta();
+
+        decoded.clear();
+        if !decoder.step(reader, &mut decoded)? {
+            decoder.check_end(offset)?;
+            break;
+        }
+        if at_action {
+            last_action = Some((offset, last_delta));
+            last_inputs.clear();
+        }
+        last_inputs.append(&mut decoded);
+    }
+
+    let (Some((offset, last_delta)), Some(input)) = (last_action, last_inputs.first()) else {
+        // An initial frame without any inputs; start over from it.
+        return Ok((start, Encoding::V2(v2::Encoder::default())));
+    };
+    let swift = last_inputs.len() == 2;
+    let encoder = v2::Encoder::resume(last_delta, input.clone(), swift);
+    Ok((offset, Encoding::V2(encoder)))
+}
+
+/// Registered codecs can't pick up a stream they didn't write themselves.
+fn not_resumable(version: u8) -> TcmError {
+    TcmError::invalid_input(format!("v{} replays can't be resumed", version))
+}
+
+/// Appends inputs to the replay file at `path` without rewriting the inputs already in
+/// it.
+///
+/// See [`InputWriter::resume`] for how the existing stream is continued.
+///
+/// # Example
+/// ```no_run
+/// use tcm::input::{Input, InputCommand, PlayerButton, VanillaInput};
+/// use tcm::writer::append_to_file;
+///
+/// let press = |frame, push| {
+///     InputCommand::new(
+///         frame,
+///         Input::Vanilla(VanillaInput { button: PlayerButton::Jump, push, player2: false }),
+///     )
+/// };
+/// append_to_file("session.tcm", &[press(500, true), press(520, false)]).unwrap();
+/// ```
+pub fn append_to_file(path: impl AsRef<Path>, inputs: &[InputCommand]) -> TcmResult<()> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut writer = InputWriter::resume(file)?;
+    for input in inputs {
+        writer.push(input)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
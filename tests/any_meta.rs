@@ -0,0 +1,96 @@
+use std::io::Cursor;
+
+use tcm::meta::{AnyMeta, MetaFormat, MetaInfo, MetaV1, MetaV2};
+use tcm::replay::{Replay, ReplayDeserializer};
+use tcm::{DynamicReplay, TcmError};
+
+mod common;
+
+use common::read_example;
+
+#[test]
+fn test_dynamic_replay_variants() {
+    let replay = DynamicReplay::from_bytes(&read_example("restartv1.tcm")).unwrap();
+    let typed = Replay::<MetaV1>::from_bytes(&read_example("restartv1.tcm")).unwrap();
+    assert!(matches!(&replay.meta, AnyMeta::V1(meta) if *meta == typed.meta));
+
+    let bytes = read_example("restartv2.tcm");
+    let replay = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+    let typed = Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+    let AnyMeta::V2(meta) = &replay.meta else {
+        panic!("expected v2 metadata, got {:?}", replay.meta);
+    };
+    assert_eq!(meta.rng_seed, typed.meta.rng_seed);
+    assert_eq!(replay, DynamicReplay::from(typed));
+}
+
+#[test]
+fn test_dynamic_replay_clone_and_compare() {
+    let replay = DynamicReplay::from_bytes(&read_example("longv2.tcm")).unwrap();
+    let mut copy = replay.clone();
+    assert_eq!(copy, replay);
+
+    copy.inputs.pop();
+    assert_ne!(copy, replay);
+
+    let v1 = AnyMeta::from(MetaV1::new(240.0, 0));
+    let v2 = AnyMeta::from(MetaV2::new(240.0, 0, None));
+    assert_ne!(v1, v2);
+    assert_eq!(v1.tps(), v2.tps());
+}
+
+#[test]
+fn test_any_meta_bytes() {
+    for meta in [
+        AnyMeta::V1(MetaV1::new(60.0, 3)),
+        AnyMeta::V2(MetaV2::new(480.0, 1, Some(42))),
+    ] {
+        let bytes = meta.to_bytes();
+        assert_eq!(AnyMeta::from_bytes(&bytes).unwrap(), meta);
+        assert_eq!(bytes.len(), AnyMeta::size());
+    }
+
+    let mut bytes = MetaV2::new(240.0, 0, None).to_bytes();
+    bytes[0] = 9;
+    assert!(matches!(
+        AnyMeta::from_bytes(&bytes),
+        Err(TcmError::UnsupportedVersion { version: 9 })
+    ));
+    assert!(AnyMeta::from_bytes(&[]).is_err());
+}
+
+#[test]
+fn test_meta_info_is_object_safe() {
+    let metas: Vec<Box<dyn MetaInfo>> = vec![
+        Box::new(MetaV1::new(60.0, 0)),
+        Box::new(MetaV2::new(480.0, 0, Some(5))),
+        Box::new(AnyMeta::V2(MetaV2::new(240.0, 2, None))),
+    ];
+    let versions: Vec<_> = metas.iter().map(|meta| meta.version()).collect();
+    assert_eq!(
+        versions,
+        [MetaV1::VERSION, MetaV2::VERSION, MetaV2::VERSION]
+    );
+    assert_eq!(metas[1].rng_seed(), Some(5));
+    assert_eq!(metas[2].append_counter(), 2);
+}
+
+#[test]
+fn test_meta_format_versions() {
+    fn check<M: MetaFormat>(version: u8) {
+        assert_eq!(M::FORMAT_VERSION, Some(version));
+        assert_eq!(M::codec().map(|codec| codec.version()), Some(version));
+        assert_eq!(M::new_empty(240.0).version(), version);
+    }
+    check::<MetaV1>(MetaV1::VERSION);
+    check::<MetaV2>(MetaV2::VERSION);
+
+    assert_eq!(<AnyMeta as MetaFormat>::FORMAT_VERSION, None);
+    assert!(AnyMeta::codec().is_none());
+    let err = AnyMeta::from_bytes(&[0x7F; 0x40]).unwrap_err();
+    assert!(
+        matches!(err, TcmError::UnsupportedVersion { version: 0x7F }),
+        "{:?}",
+        err
+    );
+}
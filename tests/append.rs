@@ -0,0 +1,257 @@
+use std::io::Cursor;
+
+use tcm::input::{Input, InputCommand, RestartInput, RestartType, TpsInput};
+use tcm::meta::{MetaFormat, MetaInfo, MetaV1, MetaV2};
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
+use tcm::writer::{append_to_file, InputWriter};
+use tcm::{DynamicReplay, TcmError};
+
+mod common;
+
+use common::{jump, read_example};
+
+/// Offset of the append counter in a serialized replay.
+const COUNTER_OFFSET: usize = 0x11;
+
+fn read_replay(name: &str) -> DynamicReplay {
+    DynamicReplay::from_reader(&mut Cursor::new(read_example(name))).unwrap()
+}
+
+fn serialize<M: MetaFormat>(replay: &Replay<M>) -> Vec<u8> {
+    let mut bytes = Cursor::new(Vec::new());
+    replay.serialize(&mut bytes).unwrap();
+    bytes.into_inner()
+}
+
+fn read_v2(path: &std::path::Path) -> Replay<MetaV2> {
+    Replay::<MetaV2>::deserialize(&mut std::fs::File::open(path).unwrap()).unwrap()
+}
+
+/// Writes the first `split` inputs, appends the rest and checks the result against
+/// serializing everything at once.
+fn check_split(replay: &DynamicReplay, split: usize) {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let head = Replay::new(replay.meta.clone(), replay.inputs[..split].to_vec());
+    std::fs::write(file.path(), serialize(&head)).unwrap();
+
+    append_to_file(file.path(), &replay.inputs[split..]).unwrap();
+
+    let mut expected = serialize(replay);
+    expected[COUNTER_OFFSET] += 1;
+    let appended = std::fs::read(file.path()).unwrap();
+    assert_eq!(appended, expected, "split at {}", split);
+}
+
+#[test]
+fn test_append_matches_serialize() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let replay = read_replay(name);
+        let len = replay.inputs.len();
+        let step = (len / 40).max(1);
+        for split in (0..=len).step_by(step).chain([len - 1, len]) {
+            check_split(&replay, split);
+        }
+    }
+}
+
+#[test]
+fn test_append_joins_swift_pair() {
+    let replay = Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![
+            jump(5, true),
+            jump(10, true),
+            jump(10, false),
+            jump(30, true),
+        ],
+    );
+    let replay = DynamicReplay::from(replay);
+    for split in 0..=replay.inputs.len() {
+        check_split(&replay, split);
+    }
+}
+
+#[test]
+fn test_append_after_restart_with_seed() {
+    let restart = Input::Restart(RestartInput {
+        restart_type: RestartType::Death,
+        new_seed: Some(0xDEAD_BEEF),
+    });
+    let replay: DynamicReplay = Replay::new(
+        MetaV2::new(240.0, 0, Some(7)).into(),
+        vec![
+            jump(100, true),
+            jump(400, false),
+            InputCommand::new(900, restart),
+            jump(3, true),
+            jump(303, false),
+        ],
+    );
+    for split in 0..=replay.inputs.len() {
+        check_split(&replay, split);
+    }
+}
+
+#[test]
+fn test_append_grows_v1_count() {
+    let inputs: Vec<_> = (0..300).map(|i| jump(i * 2, i % 2 == 0)).collect();
+    let replay: DynamicReplay = Replay::new(MetaV1::new(240.0, 0).into(), inputs);
+    for split in [0, 100, 127, 128, 200] {
+        check_split(&replay, split);
+    }
+}
+
+#[test]
+fn test_append_moves_long_v1_streams() {
+    // A count past three bytes wide, in front of more inputs than are moved at once.
+    let inputs: Vec<_> = (0..16_400).map(|i| jump(i * 1000, i % 2 == 0)).collect();
+    let replay: DynamicReplay = Replay::new(MetaV1::new(240.0, 0).into(), inputs);
+    check_split(&replay, 16_383);
+}
+
+#[test]
+fn test_resume_new_v1_writer() {
+    let inputs: Vec<_> = (0..300).map(|i| jump(i * 2, i % 2 == 0)).collect();
+    let meta = MetaV1::new(240.0, 0);
+
+    let resume = |mut file: Cursor<Vec<u8>>| {
+        let mut writer = InputWriter::resume(&mut file).unwrap();
+        for input in &inputs[100..] {
+            writer.push(input).unwrap();
+        }
+        writer.finish().unwrap();
+        file.into_inner()
+    };
+
+    let mut writer = InputWriter::new(Cursor::new(Vec::new()), &meta).unwrap();
+    for input in &inputs[..100] {
+        writer.push(input).unwrap();
+    }
+    let bytes = resume(writer.finish().unwrap());
+    let mut expected = serialize(&Replay::new(meta.clone(), inputs.clone()));
+    expected[COUNTER_OFFSET] = 1;
+    assert_eq!(bytes, expected);
+
+    let mut writer = InputWriter::new_padded(Cursor::new(Vec::new()), &meta).unwrap();
+    for input in &inputs[..100] {
+        writer.push(input).unwrap();
+    }
+    let file = writer.finish().unwrap();
+    let len = file.get_ref().len();
+    let bytes = resume(file);
+
+    // The padded count keeps its width.
+    assert_eq!(bytes.len(), len + 200 * 3);
+    let resumed = Replay::<MetaV1>::deserialize(&mut Cursor::new(bytes)).unwrap();
+    assert_eq!(resumed.inputs, inputs);
+    assert_eq!(resumed.meta.append_counter, 1);
+}
+
+#[test]
+fn test_append_counter() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), serialize(&Replay::<MetaV2>::new_empty(240.0))).unwrap();
+
+    for session in 1..=3u64 {
+        append_to_file(
+            file.path(),
+            &[jump(session * 10, true), jump(session * 10 + 5, false)],
+        )
+        .unwrap();
+        let replay = read_v2(file.path());
+        assert_eq!(replay.meta.append_counter, session as u8);
+        assert_eq!(replay.inputs.len(), session as usize * 2);
+    }
+
+    // The counter survives conversions in both directions.
+    let replay: DynamicReplay = Replay::new(read_v2(file.path()).meta.into(), Vec::new());
+    let v1 = replay.to_v1().unwrap();
+    assert_eq!(v1.meta.append_counter, 3);
+    let v2 = DynamicReplay::from(v1).to_v2(None);
+    assert_eq!(v2.meta.append_counter, 3);
+
+    // The counter is a single byte and the 256th append would reuse it.
+    let mut bytes = std::fs::read(file.path()).unwrap();
+    bytes[COUNTER_OFFSET] = u8::MAX;
+    std::fs::write(file.path(), &bytes).unwrap();
+    let err = append_to_file(file.path(), &[jump(100, true)]).unwrap_err();
+    assert!(matches!(err, TcmError::InvalidMetadata { .. }), "{:?}", err);
+    assert_eq!(std::fs::read(file.path()).unwrap(), bytes);
+}
+
+#[test]
+fn test_resume_writer() {
+    let replay = read_replay("longv2.tcm");
+    let (first, second) = replay.inputs.split_at(replay.inputs.len() / 2);
+
+    let mut writer = InputWriter::new(Cursor::new(Vec::new()), &replay.meta).unwrap();
+    for input in first {
+        writer.push(input).unwrap();
+    }
+    let mut file = writer.finish().unwrap();
+
+    let mut writer = InputWriter::resume(&mut file).unwrap();
+    for (i, input) in second.iter().enumerate() {
+        writer.push(input).unwrap();
+        if i % 50 == 0 {
+            writer.flush().unwrap();
+        }
+    }
+    writer.finish().unwrap();
+
+    let resumed = DynamicReplay::from_reader(&mut Cursor::new(file.into_inner())).unwrap();
+    assert_eq!(resumed.meta.append_counter(), 1);
+    assert_eq!(resumed.inputs, replay.inputs);
+}
+
+#[test]
+fn test_append_refuses_damaged_files() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+
+    let mut bytes = serialize(&read_replay("restartv1.tcm"));
+    bytes.push(0);
+    std::fs::write(file.path(), &bytes).unwrap();
+    let err = append_to_file(file.path(), &[jump(1, true)]).unwrap_err();
+    assert!(
+        matches!(err, TcmError::TrailingData { .. }),
+        "got {:?}",
+        err
+    );
+    assert_eq!(std::fs::read(file.path()).unwrap(), bytes);
+
+    bytes.truncate(bytes.len() - 2);
+    std::fs::write(file.path(), &bytes).unwrap();
+    let err = append_to_file(file.path(), &[jump(1, true)]).unwrap_err();
+    assert!(matches!(err, TcmError::MissingEndMarker), "got {:?}", err);
+
+    // A v2 stream cut off in the middle of a frame delta.
+    let bytes = serialize(&Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![jump(0, true), jump(70000, false)],
+    ));
+    let truncated = &bytes[..bytes.len() - 3];
+    std::fs::write(file.path(), truncated).unwrap();
+    let err = append_to_file(file.path(), &[jump(80000, true)]).unwrap_err();
+    assert!(
+        matches!(err.root_cause(), TcmError::UnexpectedEof { .. }),
+        "got {:?}",
+        err
+    );
+    assert_eq!(std::fs::read(file.path()).unwrap(), truncated);
+
+    // And one cut off in the middle of a TPS value.
+    let tps = InputCommand::new(10, Input::Tps(TpsInput { tps: 480.0 }));
+    let bytes = serialize(&Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![jump(0, true), tps],
+    ));
+    let truncated = &bytes[..bytes.len() - 2];
+    std::fs::write(file.path(), truncated).unwrap();
+    let err = append_to_file(file.path(), &[jump(20, true)]).unwrap_err();
+    assert!(
+        matches!(err.root_cause(), TcmError::UnexpectedEof { .. }),
+        "got {:?}",
+        err
+    );
+    assert_eq!(std::fs::read(file.path()).unwrap(), truncated);
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "tokio")]
+
+use std::io::Cursor;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use tcm::async_io::{detect_version_async, AsyncInputStream};
+use tcm::meta::{MetaInfo, MetaV1, MetaV2};
+use tcm::replay::{Replay, ReplayDeserializer};
+use tcm::{DynamicReplay, ReadOptions, TcmError};
+
+mod common;
+
+use common::read_example;
+
+#[tokio::test]
+async fn test_async_matches_blocking() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let expected = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+
+        let replay = DynamicReplay::from_async_reader(&mut &bytes[..])
+            .await
+            .unwrap();
+        assert_eq!(replay.meta.version(), expected.meta.version());
+        assert_eq!(replay.inputs, expected.inputs, "{}", name);
+
+        let mut out = Vec::new();
+        replay.serialize_async(&mut out).await.unwrap();
+        assert_eq!(out, bytes, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn test_async_small_reads() {
+    let bytes = read_example("longv2.tcm");
+    let expected = Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+
+    // Hand the bytes over a few at a time, splitting blobs across reads.
+    let mut reader = Trickle {
+        bytes: &bytes,
+        chunk: 3,
+    };
+    let options = ReadOptions::strict();
+    let replay = Replay::<MetaV2>::deserialize_async_with_options(&mut reader, &options)
+        .await
+        .unwrap();
+    assert_eq!(replay.inputs, expected.inputs);
+}
+
+#[tokio::test]
+async fn test_async_stream_errors() {
+    let bytes = read_example("restartv1.tcm");
+    let truncated = &bytes[..bytes.len() / 2];
+
+    let err = Replay::<MetaV1>::deserialize_async(&mut &truncated[..])
+        .await
+        .unwrap_err();
+    let sync_err = Replay::<MetaV1>::deserialize(&mut Cursor::new(truncated)).unwrap_err();
+    assert_eq!(err.to_string(), sync_err.to_string());
+
+    let mut stream = AsyncInputStream::<_, MetaV1>::new(truncated).await.unwrap();
+    let mut last = None;
+    while let Some(input) = stream.next().await {
+        last = Some(input);
+    }
+    assert!(matches!(last, Some(Err(TcmError::Decode { .. }))));
+}
+
+#[tokio::test]
+async fn test_detect_version_async() {
+    let bytes = read_example("restartv1.tcm");
+    let (version, mut reader) = detect_version_async(&bytes[..]).await.unwrap();
+    assert_eq!(version, 1);
+    let replay = Replay::<MetaV1>::deserialize_async(&mut reader)
+        .await
+        .unwrap();
+    assert!(!replay.inputs.is_empty());
+
+    let err = detect_version_async(&bytes[..0x20]).await.err().unwrap();
+    assert!(
+        matches!(err, TcmError::UnexpectedEof { .. }),
+        "got {:?}",
+        err
+    );
+}
+
+/// An async reader handing out at most `chunk` bytes per read.
+struct Trickle<'a> {
+    bytes: &'a [u8],
+    chunk: usize,
+}
+
+impl AsyncRead for Trickle<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let len = self.chunk.min(self.bytes.len()).min(buf.remaining());
+        buf.put_slice(&self.bytes[..len]);
+        self.bytes = &self.bytes[len..];
+        Poll::Ready(Ok(()))
+    }
+}
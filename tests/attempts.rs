@@ -0,0 +1,92 @@
+use std::io::Cursor;
+
+use tcm::index::SeekIndex;
+use tcm::input::{Input, RestartType};
+use tcm::meta::{MetaInfo, MetaV2};
+use tcm::{DynamicReplay, Replay, ReplayBuilder};
+
+mod common;
+
+use common::read_example;
+
+#[test]
+fn test_attempts_cover_every_input() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let replay = DynamicReplay::from_bytes(&read_example(name)).unwrap();
+        let attempts = replay.attempts();
+        let restarts = replay
+            .inputs
+            .iter()
+            .filter(|input| matches!(input.input, Input::Restart(_)))
+            .count();
+        assert_eq!(attempts.len(), restarts + 1, "{}", name);
+
+        let mut offset = 0;
+        for (index, attempt) in attempts.iter().enumerate() {
+            assert_eq!(attempt.index, index);
+            assert_eq!(attempt.input_offset, offset);
+            assert_eq!(
+                attempt.inputs,
+                &replay.inputs[offset..offset + attempt.inputs.len()]
+            );
+            let restarts_inside = attempt.inputs[..attempt.inputs.len().saturating_sub(1)]
+                .iter()
+                .filter(|input| matches!(input.input, Input::Restart(_)))
+                .count();
+            assert_eq!(restarts_inside, 0);
+            assert!(attempt
+                .inputs
+                .iter()
+                .all(|input| attempt.frames.contains(&input.frame)));
+            assert_eq!(attempt.restart_type.is_none(), index == 0);
+            offset += attempt.inputs.len();
+        }
+        assert_eq!(offset, replay.inputs.len());
+    }
+}
+
+#[test]
+fn test_attempts_match_seek_index() {
+    let bytes = read_example("restartv2.tcm");
+    let replay = DynamicReplay::from_bytes(&bytes).unwrap();
+    let index = SeekIndex::build(Cursor::new(&bytes), 1).unwrap();
+    let attempts = replay.attempts();
+
+    for checkpoint in index.checkpoints() {
+        if checkpoint.input_index == replay.inputs.len() {
+            continue;
+        }
+        let attempt = &attempts[checkpoint.attempt];
+        let range = attempt.input_offset..attempt.input_offset + attempt.inputs.len();
+        assert!(range.contains(&checkpoint.input_index), "{:?}", checkpoint);
+    }
+    assert_eq!(replay.meta.rng_seed(), attempts[0].seed);
+}
+
+#[test]
+fn test_attempt_seeds_and_edges() {
+    let replay = ReplayBuilder::new(MetaV2::new(240.0, 0, None))
+        .at(10)
+        .restart(RestartType::RestartFull)
+        .with_seed(5)
+        .restart(RestartType::Restart)
+        .build()
+        .unwrap();
+    let attempts = replay.attempts();
+    assert_eq!(attempts.len(), 3);
+    assert_eq!(attempts[0].frames, 0..11);
+    assert_eq!(
+        (attempts[1].restart_type, attempts[1].seed),
+        (Some(RestartType::RestartFull), Some(5))
+    );
+    assert_eq!(attempts[1].frames, 0..1);
+    assert_eq!(
+        (attempts[2].restart_type, attempts[2].seed),
+        (Some(RestartType::Restart), None)
+    );
+    assert!(attempts[2].inputs.is_empty());
+    assert_eq!(attempts[2].frames, 0..0);
+    assert_eq!(attempts[2].input_offset, 2);
+
+    assert!(Replay::<MetaV2>::new_empty(240.0).attempts().is_empty());
+}
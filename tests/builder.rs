@@ -0,0 +1,161 @@
+use tcm::input::{
+    Input, InputCommand, PlayerButton::*, RestartInput, RestartType, TpsInput, VanillaInput,
+};
+use tcm::meta::{MetaInfo, MetaV2};
+use tcm::{Replay, ReplayBuilder, TcmError};
+
+fn button(frame: u64, button: tcm::input::PlayerButton, push: bool, player2: bool) -> InputCommand {
+    InputCommand::new(
+        frame,
+        Input::Vanilla(VanillaInput {
+            button,
+            push,
+            player2,
+        }),
+    )
+}
+
+#[test]
+fn test_builder_matches_hand_built_inputs() {
+    let replay = ReplayBuilder::v2(240.0)
+        .at(100)
+        .press(Jump)
+        .player2()
+        .hold(Right, 120..130)
+        .player1()
+        .at(150)
+        .release(Jump)
+        .at(300)
+        .restart(RestartType::Death)
+        .with_seed(42)
+        .tps(480.0)
+        .at(20)
+        .bugpoint()
+        .build()
+        .unwrap();
+
+    let expected = vec![
+        button(100, Jump, true, false),
+        button(120, Right, true, true),
+        button(130, Right, false, true),
+        button(150, Jump, false, false),
+        InputCommand::new(
+            300,
+            Input::Restart(RestartInput {
+                restart_type: RestartType::Death,
+                new_seed: Some(42),
+            }),
+        ),
+        InputCommand::new(0, Input::Tps(TpsInput { tps: 480.0 })),
+        InputCommand::new(20, Input::Bugpoint(tcm::input::BugpointInput)),
+    ];
+    assert_eq!(replay.inputs, expected);
+    assert_eq!(replay.meta.tps(), 240.0);
+
+    let bytes = replay.to_bytes().unwrap();
+    assert_eq!(Replay::<MetaV2>::from_bytes(&bytes).unwrap(), replay);
+}
+
+#[test]
+fn test_builder_v1() {
+    let replay = ReplayBuilder::v1(60.0)
+        .hold(Jump, 10..20)
+        .at(40)
+        .restart(RestartType::Restart)
+        .hold(Left, 5..6)
+        .build()
+        .unwrap();
+    assert_eq!(replay.meta.version(), 1);
+    assert_eq!(replay.inputs.len(), 5);
+
+    for builder in [
+        ReplayBuilder::v1(60.0).tps(120.0),
+        ReplayBuilder::v1(60.0).bugpoint(),
+        ReplayBuilder::v1(60.0)
+            .restart(RestartType::Death)
+            .with_seed(1),
+        ReplayBuilder::v1(60.0).at(u32::MAX as u64 + 1).press(Jump),
+    ] {
+        assert!(builder.build().is_err());
+    }
+}
+
+#[test]
+fn test_builder_refuses_misuse() {
+    let err = ReplayBuilder::v2(240.0).with_seed(5).build().unwrap_err();
+    assert!(matches!(err, TcmError::InvalidInput { .. }), "{:?}", err);
+
+    let err = ReplayBuilder::v2(240.0)
+        .hold(Jump, 50..50)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, TcmError::InvalidInput { .. }), "{:?}", err);
+
+    let err = ReplayBuilder::v2(240.0)
+        .hold(Jump, 50..100)
+        .at(80)
+        .restart(RestartType::Death)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, TcmError::InvalidInput { .. }), "{:?}", err);
+
+    assert!(ReplayBuilder::v2(240.0).tps(0.0).build().is_err());
+    assert!(ReplayBuilder::v2(f32::NAN).build().is_err());
+    assert!(ReplayBuilder::new(MetaV2::new(240.0, 0, Some(7)))
+        .build()
+        .is_ok());
+}
+
+#[test]
+fn test_builder_refuses_unordered_and_unpaired_inputs() {
+    let err = ReplayBuilder::v2(240.0)
+        .at(100)
+        .press(Jump)
+        .at(50)
+        .release(Jump)
+        .build()
+        .unwrap_err();
+    assert!(
+        matches!(&err, TcmError::InvalidInput { message } if message.contains("frame 50")),
+        "{:?}",
+        err
+    );
+
+    let builders = [
+        ReplayBuilder::v2(240.0).at(10).release(Jump),
+        ReplayBuilder::v2(240.0)
+            .at(10)
+            .press(Jump)
+            .at(20)
+            .press(Jump),
+        ReplayBuilder::v2(240.0)
+            .hold(Jump, 10..20)
+            .at(30)
+            .restart(RestartType::Death)
+            .at(5)
+            .release(Jump),
+        ReplayBuilder::v2(240.0)
+            .hold(Jump, 10..20)
+            .at(15)
+            .bugpoint(),
+    ];
+    for builder in builders {
+        let err = builder.build().unwrap_err();
+        assert!(matches!(err, TcmError::InvalidInput { .. }), "{:?}", err);
+    }
+
+    // Players hold their buttons apart, and a restart lets go of everything.
+    let replay = ReplayBuilder::v2(240.0)
+        .at(10)
+        .press(Jump)
+        .player2()
+        .press(Jump)
+        .at(20)
+        .restart(RestartType::Death)
+        .player1()
+        .at(5)
+        .press(Jump)
+        .build()
+        .unwrap();
+    assert_eq!(replay.inputs.len(), 4);
+}
@@ -0,0 +1,397 @@
+use std::io::Cursor;
+
+use tcm::codec::{self, InputCodec, InputDecoder, InputEncoder, InputSink, V1Codec, V2Codec};
+use tcm::input::{Input, InputCommand, RestartInput, RestartType};
+use tcm::meta::{AnyMeta, CustomLayout, CustomMeta, MetaInfo, MetaV2};
+use tcm::replay::PREFIX_SIZE;
+use tcm::stream::DynamicInputStream;
+use tcm::writer::InputWriter;
+use tcm::{DynamicReplay, ReadLimits, ReadOptions, TcmError, TcmResult};
+
+mod common;
+
+use common::{jump, read_example};
+
+/// The v2 encoding with every byte inverted, registered under a test-only version.
+struct Inverted(u8);
+
+impl InputCodec for Inverted {
+    fn version(&self) -> u8 {
+        self.0
+    }
+
+    fn encoder(&self) -> Box<dyn InputEncoder> {
+        Box::new(InvertedEncoder(V2Codec.encoder()))
+    }
+
+    fn decoder(&self) -> Box<dyn InputDecoder> {
+        Box::new(InvertedDecoder(V2Codec.decoder(), Vec::new()))
+    }
+}
+
+struct InvertedEncoder(Box<dyn InputEncoder>);
+
+impl InputEncoder for InvertedEncoder {
+    fn push(&mut self, input: &InputCommand, out: &mut Vec<u8>) -> TcmResult<()> {
+        let start = out.len();
+        self.0.push(input, out)?;
+        out[start..].iter_mut().for_each(|b| *b = !*b);
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> TcmResult<()> {
+        let start = out.len();
+        self.0.finish(out)?;
+        out[start..].iter_mut().for_each(|b| *b = !*b);
+        Ok(())
+    }
+}
+
+/// Keeps the bytes it was given inverted, as unused ones are passed in again.
+struct InvertedDecoder(Box<dyn InputDecoder>, Vec<u8>);
+
+impl InputDecoder for InvertedDecoder {
+    fn decode(&mut self, bytes: &[u8], at_end: bool, inputs: &mut InputSink) -> TcmResult<usize> {
+        let known = self.1.len();
+        self.1.extend(bytes[known..].iter().map(|b| !b));
+        let used = self.0.decode(&self.1, at_end, inputs)?;
+        self.1.drain(..used);
+        Ok(used)
+    }
+}
+
+/// A codec whose decoder never runs out of inputs, whatever the stream holds.
+struct Endless(u8);
+
+impl InputCodec for Endless {
+    fn version(&self) -> u8 {
+        self.0
+    }
+
+    fn encoder(&self) -> Box<dyn InputEncoder> {
+        V2Codec.encoder()
+    }
+
+    fn decoder(&self) -> Box<dyn InputDecoder> {
+        Box::new(EndlessDecoder)
+    }
+}
+
+struct EndlessDecoder;
+
+impl InputDecoder for EndlessDecoder {
+    fn decode(&mut self, _: &[u8], _: bool, inputs: &mut InputSink) -> TcmResult<usize> {
+        let mut frame = 0;
+        while !inputs.is_full() {
+            inputs.push(jump(frame, true))?;
+            frame += 1;
+        }
+        Ok(0)
+    }
+}
+
+/// The v2 encoding behind a metadata block that only holds the version and the TPS,
+/// at offset 4.
+struct TpsOnly(u8);
+
+#[derive(Debug)]
+struct TpsOnlyMeta {
+    version: u8,
+    tps: f32,
+}
+
+impl MetaInfo for TpsOnlyMeta {
+    fn tps(&self) -> f32 {
+        self.tps
+    }
+
+    fn tps_dt(&self) -> f32 {
+        1.0 / self.tps
+    }
+
+    fn uses_dt(&self) -> bool {
+        false
+    }
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn rng_seed(&self) -> Option<u64> {
+        None
+    }
+
+    fn is_rng_seed_set(&self) -> bool {
+        false
+    }
+
+    fn append_counter(&self) -> u8 {
+        0
+    }
+}
+
+impl CustomLayout for TpsOnlyMeta {
+    fn to_bytes(&self) -> Box<[u8]> {
+        let mut bytes = vec![0; 0x40];
+        bytes[0] = self.version;
+        bytes[4..8].copy_from_slice(&self.tps.to_le_bytes());
+        bytes.into()
+    }
+}
+
+impl InputCodec for TpsOnly {
+    fn version(&self) -> u8 {
+        self.0
+    }
+
+    fn read_meta(&self, bytes: &[u8], strict: bool) -> TcmResult<CustomMeta> {
+        if strict && bytes[8..].iter().any(|&b| b != 0) {
+            return Err(TcmError::invalid_metadata("non-zero reserved bytes"));
+        }
+        let tps = f32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        Ok(CustomMeta::new(TpsOnlyMeta {
+            version: bytes[0],
+            tps,
+        }))
+    }
+
+    fn encoder(&self) -> Box<dyn InputEncoder> {
+        V2Codec.encoder()
+    }
+
+    fn decoder(&self) -> Box<dyn InputDecoder> {
+        V2Codec.decoder()
+    }
+}
+
+fn custom_replay(version: u8) -> DynamicReplay {
+    let replay = DynamicReplay::from_bytes(&read_example("restartv2.tcm")).unwrap();
+    let AnyMeta::V2(meta) = replay.meta else {
+        panic!("expected v2 metadata");
+    };
+    DynamicReplay::new(
+        AnyMeta::Custom(CustomMeta::v2(version, meta)),
+        replay.inputs,
+    )
+}
+
+/// Several attempts of longv2, long enough to be read in more than one chunk.
+fn long_custom_replay(version: u8) -> DynamicReplay {
+    let replay = DynamicReplay::from_bytes(&read_example("longv2.tcm")).unwrap();
+    let AnyMeta::V2(meta) = replay.meta else {
+        panic!("expected v2 metadata");
+    };
+    let mut inputs = replay.inputs;
+    let last_frame = inputs.last().map_or(0, |i| i.frame);
+    inputs.push(InputCommand::new(
+        last_frame,
+        Input::Restart(RestartInput {
+            restart_type: RestartType::Restart,
+            new_seed: None,
+        }),
+    ));
+    let inputs = std::iter::repeat(inputs).take(6).flatten().collect();
+    DynamicReplay::new(AnyMeta::Custom(CustomMeta::v2(version, meta)), inputs)
+}
+
+#[test]
+fn test_builtin_codecs_match_replays() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let replay = DynamicReplay::from_bytes(&bytes).unwrap();
+        let codec = codec::for_version(replay.meta.version()).unwrap();
+
+        let decoded = codec
+            .decode(&bytes[PREFIX_SIZE..], &ReadOptions::strict())
+            .unwrap();
+        assert_eq!(decoded, replay.inputs);
+
+        let mut encoded = Vec::new();
+        codec.encode(&replay.inputs, &mut encoded).unwrap();
+        assert_eq!(encoded, &bytes[PREFIX_SIZE..]);
+    }
+    assert_eq!(V1Codec.version(), 1);
+    assert!(codec::for_version(0xE0).is_none());
+}
+
+#[test]
+fn test_registered_version_round_trip() {
+    codec::register(Inverted(0xE1)).unwrap();
+    let replay = custom_replay(0xE1);
+    let bytes = replay.to_bytes().unwrap();
+    assert_eq!(bytes[0x10], 0xE1);
+
+    assert_eq!(DynamicReplay::from_bytes(&bytes).unwrap(), replay);
+    let strict =
+        DynamicReplay::from_reader_with_options(&mut Cursor::new(&bytes), &ReadOptions::strict())
+            .unwrap();
+    assert_eq!(strict, replay);
+
+    let streamed: Vec<_> = DynamicInputStream::new(Cursor::new(&bytes))
+        .unwrap()
+        .collect::<TcmResult<_>>()
+        .unwrap();
+    assert_eq!(streamed, replay.inputs);
+
+    let v2 = replay.clone().to_v2_preserve_seed();
+    assert_eq!(v2.meta.rng_seed, replay.meta.rng_seed());
+}
+
+#[test]
+fn test_registered_version_errors() {
+    codec::register(Inverted(0xE2)).unwrap();
+    let bytes = custom_replay(0xE2).to_bytes().unwrap();
+
+    let limits = ReadOptions::default().with_limits(ReadLimits {
+        max_inputs: Some(2),
+        ..ReadLimits::default()
+    });
+    let err = DynamicReplay::from_bytes_with_options(&bytes, &limits).unwrap_err();
+    assert!(matches!(err, TcmError::LimitExceeded { .. }), "{:?}", err);
+
+    let mut corrupt = bytes.clone();
+    corrupt.push(0);
+    let err = DynamicReplay::from_bytes_with_options(&corrupt, &ReadOptions::strict())
+        .expect_err("trailing data should be refused");
+    assert!(matches!(err, TcmError::Decode { .. }), "{:?}", err);
+
+    let err = InputWriter::resume(Cursor::new(bytes))
+        .err()
+        .expect("registered versions aren't resumed");
+    assert!(matches!(err, TcmError::InvalidInput { .. }), "{:?}", err);
+}
+
+#[test]
+fn test_registered_limits_checked_while_decoding() {
+    codec::register(Endless(0xE4)).unwrap();
+    let meta = AnyMeta::Custom(CustomMeta::v2(0xE4, MetaV2::new(240.0, 0, None)));
+    let bytes = DynamicReplay::new(meta, vec![jump(10, true)])
+        .to_bytes()
+        .unwrap();
+
+    let limits = ReadOptions::default().with_limits(ReadLimits {
+        max_inputs: Some(1000),
+        ..ReadLimits::default()
+    });
+    let err = DynamicReplay::from_bytes_with_options(&bytes, &limits).unwrap_err();
+    assert!(
+        matches!(err, TcmError::LimitExceeded { max: 1000, .. }),
+        "{:?}",
+        err
+    );
+
+    let mut stream = DynamicInputStream::with_options(Cursor::new(&bytes), &limits).unwrap();
+    assert_eq!(stream.next().unwrap().unwrap(), jump(0, true));
+    let err = stream.nth(999).unwrap().unwrap_err();
+    assert!(matches!(err, TcmError::LimitExceeded { .. }), "{:?}", err);
+}
+
+#[test]
+fn test_registered_stream_read_in_chunks() {
+    codec::register(Inverted(0xE5)).unwrap();
+    let replay = long_custom_replay(0xE5);
+    let bytes = replay.to_bytes().unwrap();
+    assert!(bytes.len() > 0x20000, "{}", bytes.len());
+
+    assert_eq!(DynamicReplay::from_bytes(&bytes).unwrap(), replay);
+    let read = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(read, replay);
+
+    let mut stream = DynamicInputStream::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(stream.next().unwrap().unwrap(), replay.inputs[0]);
+    assert!(stream.offset() < bytes.len() as u64 / 2);
+    let rest: Vec<_> = stream.collect::<TcmResult<_>>().unwrap();
+    assert_eq!(rest, replay.inputs[1..]);
+
+    let options = ReadOptions::default().with_limits(ReadLimits {
+        max_bytes: Some(0x10000),
+        ..ReadLimits::default()
+    });
+    let err = DynamicReplay::from_bytes_with_options(&bytes, &options).unwrap_err();
+    assert!(matches!(err, TcmError::LimitExceeded { .. }), "{:?}", err);
+
+    let truncated = &bytes[..bytes.len() - 3];
+    let err = DynamicReplay::from_reader_with_options(
+        &mut Cursor::new(truncated),
+        &ReadOptions::strict(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, TcmError::Decode { .. }), "{:?}", err);
+}
+
+#[test]
+fn test_registered_metadata_layout() {
+    codec::register(TpsOnly(0xE6)).unwrap();
+    let meta = AnyMeta::Custom(CustomMeta::new(TpsOnlyMeta {
+        version: 0xE6,
+        tps: 360.0,
+    }));
+    let replay = DynamicReplay::new(meta, vec![jump(10, true), jump(20, false)]);
+    let bytes = replay.to_bytes().unwrap();
+    assert_eq!(&bytes[0x14..0x18], &360f32.to_le_bytes());
+
+    let read =
+        DynamicReplay::from_reader_with_options(&mut Cursor::new(&bytes), &ReadOptions::strict())
+            .unwrap();
+    assert_eq!(read, replay);
+    assert_eq!(read.meta.tps(), 360.0);
+
+    let mut corrupt = bytes.clone();
+    corrupt[0x30] = 1;
+    assert!(DynamicReplay::from_bytes(&corrupt).is_ok());
+    let err =
+        DynamicReplay::from_reader_with_options(&mut Cursor::new(&corrupt), &ReadOptions::strict())
+            .unwrap_err();
+    assert!(matches!(err, TcmError::InvalidMetadata { .. }), "{:?}", err);
+}
+
+#[test]
+fn test_registered_version_written_incrementally() {
+    codec::register(Inverted(0xE7)).unwrap();
+    let replay = long_custom_replay(0xE7);
+
+    let mut writer = InputWriter::new(Cursor::new(Vec::new()), &replay.meta).unwrap();
+    for input in &replay.inputs {
+        writer.push(input).unwrap();
+    }
+    let written = writer.finish().unwrap().into_inner();
+    assert_eq!(written, replay.to_bytes().unwrap());
+}
+
+#[test]
+fn test_register_and_unregister() {
+    let err = codec::register(Inverted(MetaV2::VERSION)).unwrap_err();
+    assert!(matches!(err, TcmError::VersionTaken { version: 2 }));
+
+    codec::register(Inverted(0xE3)).unwrap();
+    let err = codec::register(Inverted(0xE3)).unwrap_err();
+    assert!(matches!(err, TcmError::VersionTaken { version: 0xE3 }));
+
+    let bytes = custom_replay(0xE3).to_bytes().unwrap();
+    assert!(codec::unregister(0xE3).is_some());
+    assert!(codec::unregister(0xE3).is_none());
+    assert!(codec::unregister(MetaV2::VERSION).is_none());
+
+    let err = DynamicReplay::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(
+        err,
+        TcmError::UnsupportedVersion { version: 0xE3 }
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_registered_version_async() {
+    codec::register(Inverted(0xE8)).unwrap();
+    let replay = long_custom_replay(0xE8);
+    let bytes = replay.to_bytes().unwrap();
+
+    let read = DynamicReplay::from_async_reader(&mut &bytes[..])
+        .await
+        .unwrap();
+    assert_eq!(read, replay);
+
+    let mut written = Vec::new();
+    replay.serialize_async(&mut written).await.unwrap();
+    assert_eq!(written, bytes);
+}
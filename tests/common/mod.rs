@@ -0,0 +1,23 @@
+//! Helpers shared by the integration tests.
+
+// Each test file is its own crate and uses only some of the helpers.
+#![allow(dead_code)]
+
+use tcm::input::{Input, InputCommand, PlayerButton, VanillaInput};
+use tcm::Frame;
+
+pub fn read_example(name: &str) -> Vec<u8> {
+    std::fs::read(format!("examples/data/{}", name)).expect("Failed to read example file")
+}
+
+/// A press or release of the first player's jump button.
+pub fn jump(frame: Frame, push: bool) -> InputCommand {
+    InputCommand::new(
+        frame,
+        Input::Vanilla(VanillaInput {
+            button: PlayerButton::Jump,
+            push,
+            player2: false,
+        }),
+    )
+}
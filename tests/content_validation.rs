@@ -2,7 +2,7 @@ use std::fs::File;
 use std::path::Path;
 
 use tcm::input::{Input, PlayerButton, RestartType};
-use tcm::meta::{Meta, MetaV1, MetaV2};
+use tcm::meta::{MetaInfo, MetaV1, MetaV2};
 use tcm::replay::{Replay, ReplayDeserializer};
 
 #[test]
@@ -17,7 +17,7 @@ fn test_v1_content_validation() {
     let replay =
         Replay::<MetaV1>::deserialize(&mut file).expect("Failed to deserialize restartv1.tcm");
 
-    assert_eq!(MetaV1::version(), 1);
+    assert_eq!(MetaV1::VERSION, 1);
 
     assert!(replay.meta.tps() > 0.0, "TPS should be positive");
     assert!(!replay.meta.uses_dt(), "V1 should not use delta time");
@@ -79,7 +79,7 @@ fn test_v2_restart_content_validation() {
     let replay =
         Replay::<MetaV2>::deserialize(&mut file).expect("Failed to deserialize restartv2.tcm");
 
-    assert_eq!(MetaV2::version(), 2);
+    assert_eq!(MetaV2::VERSION, 2);
 
     assert!(replay.meta.tps() > 0.0, "TPS should be positive");
     assert!(
@@ -130,7 +130,7 @@ fn test_v2_long_content_validation() {
     let replay =
         Replay::<MetaV2>::deserialize(&mut file).expect("Failed to deserialize longv2.tcm");
 
-    assert_eq!(MetaV2::version(), 2);
+    assert_eq!(MetaV2::VERSION, 2);
 
     assert!(replay.meta.tps() > 0.0, "TPS should be positive");
     assert!(
@@ -0,0 +1,163 @@
+use std::io::Cursor;
+
+use tcm::input::{Input, InputCommand, PlayerButton, TpsInput, VanillaInput};
+use tcm::meta::{MetaV1, MetaV2};
+use tcm::replay::{DeserializerBlob, Replay, ReplayDeserializer, ReplaySerializer};
+use tcm::{DynamicReplay, TcmError};
+
+mod common;
+
+use common::read_example;
+
+#[test]
+fn test_invalid_header() {
+    let mut bytes = read_example("restartv2.tcm");
+    bytes[0] ^= 0xFF;
+
+    let err = Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).unwrap_err();
+    assert!(matches!(err, TcmError::InvalidHeader), "got {:?}", err);
+
+    let err = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).expect_err("Expected an error");
+    assert!(matches!(err, TcmError::InvalidHeader), "got {:?}", err);
+}
+
+#[test]
+fn test_unsupported_version() {
+    let mut bytes = read_example("restartv2.tcm");
+    bytes[0x10] = 7;
+
+    let err = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).expect_err("Expected an error");
+    assert!(
+        matches!(err, TcmError::UnsupportedVersion { version: 7 }),
+        "got {:?}",
+        err
+    );
+}
+
+#[test]
+fn test_truncated_file() {
+    let bytes = read_example("restartv1.tcm");
+
+    let err = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes[..0x08])).unwrap_err();
+    assert!(
+        matches!(err, TcmError::UnexpectedEof { .. }),
+        "got {:?}",
+        err
+    );
+
+    let err = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes[..0x30])).unwrap_err();
+    assert!(
+        matches!(err, TcmError::UnexpectedEof { .. }),
+        "got {:?}",
+        err
+    );
+
+    let err =
+        Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes[..bytes.len() - 3])).unwrap_err();
+    assert!(
+        matches!(err.root_cause(), TcmError::UnexpectedEof { .. }),
+        "got {:?}",
+        err
+    );
+}
+
+#[test]
+fn test_to_v1_rejects_v2_only_inputs() {
+    let bytes = read_example("longv2.tcm");
+    let replay = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+
+    let has_v2_only = replay.inputs.iter().any(|i| {
+        matches!(
+            i.input,
+            tcm::input::Input::Tps(_) | tcm::input::Input::Bugpoint(_)
+        )
+    });
+
+    match replay.to_v1() {
+        Ok(_) => assert!(!has_v2_only),
+        Err(err) => assert!(
+            matches!(err, TcmError::InvalidInput { .. }),
+            "got {:?}",
+            err
+        ),
+    }
+}
+
+#[test]
+fn test_decode_error_context() {
+    let jump = |frame, push| {
+        InputCommand::new(
+            frame,
+            Input::Vanilla(VanillaInput {
+                button: PlayerButton::Jump,
+                push,
+                player2: false,
+            }),
+        )
+    };
+    let replay = Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![
+            jump(10, true),
+            jump(20, false),
+            InputCommand::new(300, Input::Tps(TpsInput { tps: 480.0 })),
+            jump(310, true),
+        ],
+    );
+
+    let mut bytes = Cursor::new(Vec::new());
+    replay.serialize(&mut bytes).unwrap();
+    let bytes = bytes.into_inner();
+
+    // Cut the stream in the middle of the TPS value.
+    let tps_offset = bytes.len() - 6;
+    let truncated = &bytes[..tps_offset + 2];
+
+    let err = Replay::<MetaV2>::deserialize(&mut Cursor::new(truncated)).unwrap_err();
+    match &err {
+        TcmError::Decode {
+            offset,
+            input_index,
+            frame,
+            state,
+            ..
+        } => {
+            assert_eq!(*offset, tps_offset as u64);
+            assert_eq!(*input_index, 2);
+            assert_eq!(*frame, 300);
+            assert_eq!(*state, Some(DeserializerBlob::Tps));
+        }
+        _ => panic!("Expected a decode error, got {:?}", err),
+    }
+    assert!(matches!(err.root_cause(), TcmError::UnexpectedEof { .. }));
+}
+
+#[test]
+fn test_v1_decode_error_context() {
+    let mut bytes = read_example("restartv1.tcm");
+    // The first input follows the header, metadata, input count and first frame.
+    let count_len = bytes[0x50..].iter().position(|b| b & 0x80 == 0).unwrap() + 1;
+    let first_input = 0x50 + count_len;
+    let frame_len = bytes[first_input..]
+        .iter()
+        .position(|b| b & 0x80 == 0)
+        .unwrap()
+        + 1;
+    bytes[first_input + frame_len] = 0x07;
+
+    let err = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap_err();
+    match &err {
+        TcmError::Decode {
+            offset,
+            input_index,
+            state,
+            ..
+        } => {
+            assert_eq!(*offset, first_input as u64);
+            assert_eq!(*input_index, 0);
+            assert_eq!(*state, None);
+        }
+        _ => panic!("Expected a decode error, got {:?}", err),
+    }
+    assert!(matches!(err.root_cause(), TcmError::InvalidFormat { .. }));
+}
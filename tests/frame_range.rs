@@ -0,0 +1,94 @@
+use std::io::Cursor;
+
+use tcm::meta::{MetaFormat, MetaV1, MetaV2};
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
+use tcm::{TcmError, TcmResult};
+
+mod common;
+
+use common::{jump, read_example};
+
+fn serialize<M: MetaFormat>(replay: &Replay<M>) -> TcmResult<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    replay.serialize(&mut cursor)?;
+    Ok(cursor.into_inner())
+}
+
+#[test]
+fn test_v1_frame_out_of_range() {
+    let frame = u32::MAX as u64 + 1;
+    let replay = Replay::new(MetaV1::new(240.0, 0), vec![jump(frame, true)]);
+    let err = serialize(&replay).unwrap_err();
+    assert!(
+        matches!(err, TcmError::FrameOutOfRange { value, .. } if value == frame),
+        "got {:?}",
+        err
+    );
+
+    let replay = Replay::new(MetaV1::new(240.0, 0), vec![jump(u32::MAX as u64, true)]);
+    let bytes = serialize(&replay).unwrap();
+    let reread = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(reread.inputs, replay.inputs);
+}
+
+#[test]
+fn test_v2_initial_frame_out_of_range() {
+    let replay = Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![jump(u32::MAX as u64 + 5, true)],
+    );
+    let err = serialize(&replay).unwrap_err();
+    assert!(
+        matches!(err, TcmError::FrameOutOfRange { .. }),
+        "got {:?}",
+        err
+    );
+}
+
+#[test]
+fn test_v2_frames_beyond_u32_round_trip() {
+    let step = 3_000_000_000u64;
+    let inputs = (0..6).map(|i| jump(i * step, i % 2 == 0)).collect();
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+
+    let bytes = serialize(&replay).unwrap();
+    let reread = Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(reread.inputs, replay.inputs);
+    assert!(reread.inputs.last().unwrap().frame > u32::MAX as u64);
+}
+
+#[test]
+fn test_v2_delta_out_of_range() {
+    let replay = Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![jump(0, true), jump(u32::MAX as u64 + 1, false)],
+    );
+    let err = serialize(&replay).unwrap_err();
+    assert!(
+        matches!(err, TcmError::FrameOutOfRange { value, .. } if value == u32::MAX as u64 + 1),
+        "got {:?}",
+        err
+    );
+}
+
+#[test]
+fn test_varint_overflow() {
+    let header = read_example("restartv1.tcm")[..0x50].to_vec();
+
+    for varint in [
+        &[0xFF, 0xFF, 0xFF, 0xFF, 0x1F][..],
+        &[
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01,
+        ][..],
+        &[0x80; 16][..],
+    ] {
+        let mut bytes = header.clone();
+        bytes.extend_from_slice(varint);
+        let err = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap_err();
+        assert!(
+            matches!(err.root_cause(), TcmError::VarintOverflow { .. }),
+            "got {:?}",
+            err
+        );
+    }
+}
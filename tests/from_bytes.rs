@@ -0,0 +1,125 @@
+use std::io::Cursor;
+
+use tcm::input::{Input, InputCommand, RestartInput, RestartType};
+use tcm::meta::{MetaInfo, MetaV1, MetaV2};
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
+use tcm::{DynamicReplay, ReadLimits, ReadOptions, TcmError};
+
+mod common;
+
+use common::read_example;
+
+#[test]
+fn test_from_bytes_matches_reader() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let expected = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+
+        let replay = DynamicReplay::from_bytes(&bytes).unwrap();
+        assert_eq!(replay.meta.version(), expected.meta.version());
+        assert_eq!(replay.inputs, expected.inputs, "{}", name);
+    }
+
+    let bytes = read_example("restartv1.tcm");
+    let replay = Replay::<MetaV1>::from_bytes(&bytes).unwrap();
+    assert!(!replay.inputs.is_empty());
+}
+
+#[test]
+fn test_to_bytes_matches_serialize() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let replay = DynamicReplay::from_bytes(&bytes).unwrap();
+
+        let mut serialized = Cursor::new(Vec::new());
+        replay.serialize(&mut serialized).unwrap();
+        assert_eq!(
+            replay.to_bytes().unwrap(),
+            serialized.into_inner(),
+            "{}",
+            name
+        );
+        assert_eq!(replay.to_bytes().unwrap(), bytes, "{}", name);
+    }
+}
+
+#[test]
+fn test_from_bytes_errors_match_reader() {
+    let bytes = read_example("longv2.tcm");
+
+    for len in [0, 0x10, 0x4F, 0x51, bytes.len() / 3, bytes.len() - 3] {
+        let truncated = &bytes[..len];
+        let options = ReadOptions::strict();
+
+        let from_reader =
+            Replay::<MetaV2>::deserialize_with_options(&mut Cursor::new(truncated), &options)
+                .map(|r| r.inputs);
+        let from_bytes =
+            Replay::<MetaV2>::from_bytes_with_options(truncated, &options).map(|r| r.inputs);
+        assert_eq!(
+            format!("{:?}", from_bytes),
+            format!("{:?}", from_reader),
+            "length {}",
+            len
+        );
+    }
+}
+
+#[test]
+fn test_from_bytes_limits() {
+    let bytes = read_example("longv2.tcm");
+    let options = ReadOptions::default().with_limits(ReadLimits {
+        max_inputs: Some(10),
+        ..ReadLimits::default()
+    });
+
+    let err = Replay::<MetaV2>::from_bytes_with_options(&bytes, &options).unwrap_err();
+    assert!(
+        matches!(err, TcmError::LimitExceeded { max: 10, .. }),
+        "got {:?}",
+        err
+    );
+}
+
+#[test]
+fn test_reader_matches_from_bytes_across_chunks() {
+    // Several attempts of longv2, so the reader decodes it over more than one chunk.
+    let mut replay = Replay::<MetaV2>::from_bytes(&read_example("longv2.tcm")).unwrap();
+    let last_frame = replay.inputs.last().map_or(0, |i| i.frame);
+    replay.inputs.push(InputCommand::new(
+        last_frame,
+        Input::Restart(RestartInput {
+            restart_type: RestartType::Restart,
+            new_seed: None,
+        }),
+    ));
+    let inputs: Vec<_> = std::iter::repeat(replay.inputs).take(6).flatten().collect();
+    let bytes = Replay::new(replay.meta, inputs.clone()).to_bytes().unwrap();
+    assert_eq!(Replay::<MetaV2>::from_bytes(&bytes).unwrap().inputs, inputs);
+
+    let read = |bytes: &[u8], options: &ReadOptions| {
+        let from_reader =
+            Replay::<MetaV2>::deserialize_with_options(&mut Cursor::new(bytes), options)
+                .map(|r| r.inputs);
+        let from_bytes =
+            Replay::<MetaV2>::from_bytes_with_options(bytes, options).map(|r| r.inputs);
+        (format!("{:?}", from_reader), format!("{:?}", from_bytes))
+    };
+
+    for len in [bytes.len(), 0x10051, 0x10052, bytes.len() - 1] {
+        for options in [ReadOptions::default(), ReadOptions::strict()] {
+            let (from_reader, from_bytes) = read(&bytes[..len], &options);
+            assert_eq!(from_reader, from_bytes, "length {}", len);
+        }
+    }
+
+    for max_bytes in [0x10000, 0x10050, 0x20001, bytes.len() as u64 - 1] {
+        let options = ReadOptions::default().with_limits(ReadLimits {
+            max_bytes: Some(max_bytes),
+            ..ReadLimits::default()
+        });
+        let (from_reader, from_bytes) = read(&bytes, &options);
+        assert!(from_reader.contains("LimitExceeded"), "{}", from_reader);
+        assert_eq!(from_reader, from_bytes, "max_bytes {}", max_bytes);
+    }
+}
@@ -0,0 +1,128 @@
+//! Fuzz-style checks that arbitrary or corrupted input never panics.
+
+use std::io::Cursor;
+
+use tcm::index::SeekIndex;
+use tcm::input::{Input, InputCommand, RestartInput, RestartType};
+use tcm::meta::{MetaV1, MetaV2};
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
+use tcm::DynamicReplay;
+
+mod common;
+
+use common::{jump, read_example};
+
+const EXAMPLES: [&str; 3] = ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"];
+
+/// Small deterministic xorshift generator so failures are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+fn decode_all(bytes: &[u8]) {
+    let _ = Replay::<MetaV1>::deserialize(&mut Cursor::new(bytes));
+    let _ = Replay::<MetaV2>::deserialize(&mut Cursor::new(bytes));
+    let _ = DynamicReplay::from_reader(&mut Cursor::new(bytes));
+    let _ = DynamicReplay::recover(&mut Cursor::new(bytes));
+    let _ = SeekIndex::build(Cursor::new(bytes), 16);
+
+    // The slice decoder has to agree with the reader path on every input.
+    let from_reader = DynamicReplay::from_reader(&mut Cursor::new(bytes)).map(|r| r.inputs);
+    let from_bytes = DynamicReplay::from_bytes(bytes).map(|r| r.inputs);
+    assert_eq!(format!("{:?}", from_bytes), format!("{:?}", from_reader));
+}
+
+fn header() -> Vec<u8> {
+    read_example(EXAMPLES[0])[..0x10].to_vec()
+}
+
+#[test]
+fn test_random_bytes_never_panic() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+    for _ in 0..2000 {
+        let len = rng.below(256);
+        decode_all(&rng.bytes(len));
+    }
+}
+
+#[test]
+fn test_random_payloads_never_panic() {
+    let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+    let header = header();
+
+    for _ in 0..5000 {
+        let mut bytes = header.clone();
+        let mut meta = rng.bytes(0x40);
+        meta[0] = 1 + rng.below(2) as u8;
+        bytes.extend_from_slice(&meta);
+        let len = rng.below(512);
+        bytes.extend(rng.bytes(len));
+        decode_all(&bytes);
+    }
+}
+
+#[test]
+fn test_truncated_examples_never_panic() {
+    for name in EXAMPLES {
+        let bytes = read_example(name);
+        let step = (bytes.len() / 128).max(1);
+        for len in (0..bytes.len()).step_by(step) {
+            decode_all(&bytes[..len]);
+        }
+    }
+}
+
+#[test]
+fn test_mutated_examples_never_panic() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+
+    for name in EXAMPLES {
+        let original = read_example(name);
+        for _ in 0..100 {
+            let mut bytes = original.clone();
+            for _ in 0..1 + rng.below(8) {
+                let at = 0x10 + rng.below(bytes.len() - 0x10);
+                bytes[at] = rng.next() as u8;
+            }
+            decode_all(&bytes);
+        }
+    }
+}
+
+#[test]
+fn test_unrepresentable_replays_return_errors() {
+    let huge_delta = Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![jump(0, true), jump(u32::MAX as u64 * 4, false)],
+    );
+    assert!(huge_delta.serialize(&mut Cursor::new(Vec::new())).is_err());
+
+    let restart = Input::Restart(RestartInput {
+        restart_type: RestartType::Death,
+        new_seed: None,
+    });
+    let tps_in_v1 = Replay::new(
+        MetaV1::new(240.0, 0),
+        vec![
+            InputCommand::new(0, restart),
+            InputCommand::new(10, Input::Tps(tcm::input::TpsInput { tps: 60.0 })),
+        ],
+    );
+    assert!(tps_in_v1.serialize(&mut Cursor::new(Vec::new())).is_err());
+}
@@ -0,0 +1,205 @@
+use tcm::hold::Hold;
+use tcm::input::{Input, InputCommand, PlayerButton::*, RestartType, VanillaInput};
+use tcm::meta::MetaV2;
+use tcm::{DynamicReplay, Frame, Replay, ReplayBuilder};
+
+mod common;
+
+use common::{jump, read_example};
+
+#[test]
+fn test_holds_of_examples() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let replay = DynamicReplay::from_bytes(&bytes).unwrap();
+
+        for attempt in replay.attempts() {
+            let holds = attempt.holds();
+            let restarted = matches!(
+                attempt.inputs.last().map(|input| &input.input),
+                Some(Input::Restart(_))
+            );
+            for (at, hold) in holds.iter().enumerate() {
+                assert!(hold.start <= hold.end, "{}: {:?}", name, hold);
+                assert!(!restarted || hold.end != Frame::MAX, "{}: {:?}", name, hold);
+                // Holds of the same button never overlap, though swift pairs can fall
+                // within a hold.
+                let empty = hold.start == hold.end;
+                assert!(
+                    empty
+                        || holds.as_slice()[at + 1..]
+                            .iter()
+                            .filter(|h| (h.button, h.player2) == (hold.button, hold.player2))
+                            .all(|h| h.start == h.end || h.start >= hold.end)
+                );
+
+                let state = holds.state_at(hold.start);
+                assert!(!hold.is_swift() || state.is_swift(hold.button, hold.player2));
+                if !empty {
+                    assert!(state.is_held(hold.button, hold.player2));
+                    assert!(holds
+                        .state_at(hold.end - 1)
+                        .is_held(hold.button, hold.player2));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_swift_pairs_and_restarts() {
+    let replay = ReplayBuilder::v2(240.0)
+        .at(10)
+        .press(Jump)
+        .release(Jump)
+        .press(Jump)
+        .at(20)
+        .release(Jump)
+        .press(Jump)
+        .player2()
+        .at(25)
+        .hold(Right, 25..28)
+        .at(30)
+        .press(Left)
+        .at(40)
+        .restart(RestartType::Death)
+        .player1()
+        .at(5)
+        .press(Jump)
+        .build()
+        .unwrap();
+    let attempts = replay.attempts();
+
+    let hold = |player2, button, start, end, swift| Hold {
+        player2,
+        button,
+        start,
+        end,
+        swift,
+    };
+    assert_eq!(
+        attempts[0].holds().as_slice(),
+        [
+            hold(false, Jump, 10, 10, true),
+            hold(false, Jump, 10, 40, false),
+            hold(false, Jump, 20, 20, true),
+            hold(true, Right, 25, 28, false),
+            hold(true, Left, 30, 40, false),
+        ]
+    );
+    assert_eq!(
+        attempts[1].holds().into_iter().collect::<Vec<_>>(),
+        [hold(false, Jump, 5, Frame::MAX, false)]
+    );
+
+    let state = attempts[0].state_at(10);
+    assert!(state.is_held(Jump, false) && state.is_swift(Jump, false));
+    assert!(!state.is_held(Jump, true));
+    let state = attempts[0].state_at(20);
+    assert!(state.is_held(Jump, false) && state.is_swift(Jump, false));
+    assert!(attempts[0].state_at(27).is_held(Right, true));
+    assert!(!attempts[0].state_at(28).is_held(Right, true));
+    assert!(attempts[0].state_at(40).is_empty());
+    assert!(attempts[1].state_at(4).is_empty());
+    assert!(attempts[1].state_at(1_000_000).is_held(Jump, false));
+}
+
+#[test]
+fn test_unpaired_inputs_are_ignored() {
+    // The builder refuses unpaired presses and releases.
+    let inputs = [(5, false), (10, true), (15, true), (20, false), (25, false)]
+        .into_iter()
+        .map(|(frame, push)| jump(frame, push))
+        .collect();
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+    let holds = replay.attempts()[0].holds();
+    assert_eq!(holds.as_slice().len(), 1);
+    assert_eq!(
+        (holds.as_slice()[0].start, holds.as_slice()[0].end),
+        (10, 20)
+    );
+    assert!(holds.as_slice()[0].contains(19));
+    assert!(!holds.as_slice()[0].contains(20));
+}
+
+#[test]
+fn test_swift_pairs_match_serializer() {
+    // The release and press on frame 20 are written as one swift input.
+    let inputs: Vec<_> = [(10, true), (20, false), (20, true), (30, false)]
+        .into_iter()
+        .map(|(frame, push)| jump(frame, push))
+        .collect();
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+    let bytes = replay.to_bytes().unwrap();
+    assert_eq!(bytes[0x50..], [0x0a, 0x45, 0x0a, 0x31, 0x01]);
+
+    let attempt = &replay.attempts()[0];
+    let holds = attempt.holds();
+    let spans: Vec<_> = holds.iter().map(|hold| (hold.start, hold.end)).collect();
+    assert_eq!(spans, [(10, 30), (20, 20)]);
+    let state = holds.state_at(20);
+    assert!(state.is_held(Jump, false) && state.is_swift(Jump, false));
+    assert!(holds.state_at(29).is_held(Jump, false));
+    assert!(holds.state_at(30).is_empty());
+}
+
+#[test]
+fn test_swift_press_of_held_button_keeps_hold() {
+    // The builder refuses pressing a button that is already held.
+    let inputs = [(10, true), (15, true), (15, false), (20, false)]
+        .into_iter()
+        .map(|(frame, push)| jump(frame, push))
+        .collect();
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+    let attempt = &replay.attempts()[0];
+    let spans: Vec<_> = attempt
+        .holds()
+        .iter()
+        .map(|hold| (hold.start, hold.end))
+        .collect();
+    assert_eq!(spans, [(10, 20), (15, 15)]);
+    let state = attempt.state_at(15);
+    assert!(state.is_held(Jump, false) && state.is_swift(Jump, false));
+    assert!(attempt.state_at(19).is_held(Jump, false));
+    assert!(attempt.state_at(20).is_empty());
+}
+
+#[test]
+fn test_swift_flag_comes_from_pairs() {
+    let left = |frame, push| {
+        let input = VanillaInput {
+            button: Left,
+            push,
+            player2: false,
+        };
+        InputCommand::new(frame, Input::Vanilla(input))
+    };
+    // A release and press of a button that isn't down is one swift input that leaves
+    // the button down, and a single hold.
+    let inputs = vec![jump(10, false), jump(10, true), jump(30, false)];
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+    let holds = replay.attempts()[0].holds();
+    assert_eq!(holds.as_slice().len(), 1);
+    let hold = holds.as_slice()[0];
+    assert_eq!((hold.start, hold.end, hold.swift), (10, 30, true));
+    let state = holds.state_at(10);
+    assert!(state.is_held(Jump, false) && state.is_swift(Jump, false));
+    assert!(!holds.state_at(11).is_swift(Jump, false));
+
+    // A press and release on the same frame that aren't next to each other aren't a
+    // swift pair.
+    let inputs = vec![
+        jump(10, true),
+        left(10, true),
+        jump(10, false),
+        left(20, false),
+    ];
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+    let holds = replay.attempts()[0].holds();
+    let jump_hold = holds.iter().find(|hold| hold.button == Jump).unwrap();
+    assert_eq!((jump_hold.start, jump_hold.end), (10, 10));
+    assert!(!jump_hold.is_swift());
+    let state = holds.state_at(10);
+    assert!(!state.is_held(Jump, false) && !state.is_swift(Jump, false));
+    assert!(state.is_held(Left, false));
+}
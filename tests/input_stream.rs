@@ -0,0 +1,131 @@
+use std::io::Cursor;
+
+use tcm::meta::{MetaInfo, MetaV1, MetaV2};
+use tcm::options::ResourceLimit;
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
+use tcm::stream::{DynamicInputStream, InputStream};
+use tcm::{DynamicReplay, ReadLimits, ReadOptions, TcmError};
+
+mod common;
+
+use common::read_example;
+
+#[test]
+fn test_stream_matches_deserialize() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let full = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+
+        // A plain slice is enough, the stream never seeks.
+        let stream = DynamicInputStream::new(&bytes[..]).unwrap();
+        assert_eq!(stream.meta().version(), full.meta.version());
+        let inputs = stream.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(inputs, full.inputs, "{}", name);
+    }
+}
+
+#[test]
+fn test_typed_stream() {
+    let bytes = read_example("restartv1.tcm");
+    let full = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+
+    let mut stream = InputStream::<_, MetaV1>::new(&bytes[..]).unwrap();
+    assert_eq!(stream.next().unwrap().unwrap(), full.inputs[0]);
+    assert_eq!(stream.count(), full.inputs.len() - 1);
+
+    assert!(InputStream::<_, MetaV2>::new(&bytes[..]).is_err());
+}
+
+#[test]
+fn test_stream_stops_after_error() {
+    let mut bytes = read_example("restartv1.tcm");
+    let full = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+
+    // Corrupt the input byte of the last entry (just before the end of macro marker).
+    let at = bytes.len() - 2;
+    bytes[at] = 0x07;
+
+    let mut stream = InputStream::<_, MetaV1>::new(&bytes[..]).unwrap();
+    let mut decoded = 0;
+    let err = loop {
+        match stream.next() {
+            Some(Ok(_)) => decoded += 1,
+            Some(Err(err)) => break err,
+            None => panic!("Expected an error"),
+        }
+    };
+    assert_eq!(decoded, full.inputs.len() - 1);
+    assert!(matches!(err, TcmError::Decode { .. }), "got {:?}", err);
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn test_stream_hands_out_every_input_within_the_limits() {
+    let bytes = read_example("longv2.tcm");
+    let full = Replay::<MetaV2>::from_bytes(&bytes).unwrap();
+    let end_of = |index: usize| {
+        let inputs = full.inputs[..index].to_vec();
+        Replay::new(full.meta.clone(), inputs)
+            .to_bytes()
+            .unwrap()
+            .len() as u64
+    };
+
+    for (limits, expected, limit) in [
+        (
+            ReadLimits {
+                max_inputs: Some(full.inputs.len() - 10),
+                ..ReadLimits::default()
+            },
+            full.inputs.len() - 10,
+            ResourceLimit::Inputs,
+        ),
+        (
+            ReadLimits {
+                max_bytes: Some(end_of(1000)),
+                ..ReadLimits::default()
+            },
+            1000,
+            ResourceLimit::Bytes,
+        ),
+    ] {
+        let options = ReadOptions::default().with_limits(limits);
+        let mut stream = InputStream::<_, MetaV2>::with_options(&bytes[..], &options).unwrap();
+        let mut decoded = Vec::new();
+        let err = loop {
+            match stream.next() {
+                Some(Ok(input)) => decoded.push(input),
+                Some(Err(err)) => break err,
+                None => panic!("Expected an error"),
+            }
+        };
+        assert_eq!(decoded, full.inputs[..expected]);
+        assert!(
+            matches!(err, TcmError::LimitExceeded { limit: l, .. } if l == limit),
+            "got {:?}",
+            err
+        );
+        assert!(stream.next().is_none());
+    }
+}
+
+#[test]
+fn test_empty_replays_round_trip() {
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), Vec::new());
+    let mut bytes = Cursor::new(Vec::new());
+    replay.serialize(&mut bytes).unwrap();
+    let bytes = bytes.into_inner();
+
+    assert_eq!(DynamicInputStream::new(&bytes[..]).unwrap().count(), 0);
+    let loaded = Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+    assert!(loaded.inputs.is_empty());
+
+    let replay = Replay::new(MetaV1::new(240.0, 0), Vec::new());
+    let mut bytes = Cursor::new(Vec::new());
+    replay.serialize(&mut bytes).unwrap();
+    let options = ReadOptions::strict();
+    let loaded =
+        Replay::<MetaV1>::deserialize_with_options(&mut Cursor::new(bytes.into_inner()), &options)
+            .unwrap();
+    assert!(loaded.inputs.is_empty());
+}
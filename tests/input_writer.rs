@@ -0,0 +1,145 @@
+use std::io::Cursor;
+
+use tcm::input::{Input, InputCommand, RestartInput, RestartType, TpsInput};
+use tcm::meta::{MetaFormat, MetaV1, MetaV2};
+use tcm::recovery::RecoveryDiagnosticKind;
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer, PREFIX_SIZE};
+use tcm::writer::InputWriter;
+use tcm::{DynamicReplay, ReadOptions, TcmError};
+
+mod common;
+
+use common::{jump, read_example};
+
+fn serialize<M: MetaFormat>(replay: &Replay<M>) -> Vec<u8> {
+    let mut bytes = Cursor::new(Vec::new());
+    replay.serialize(&mut bytes).unwrap();
+    bytes.into_inner()
+}
+
+fn write<M: MetaFormat>(replay: &Replay<M>) -> Vec<u8> {
+    let mut writer = InputWriter::new(Cursor::new(Vec::new()), &replay.meta).unwrap();
+    for (i, input) in replay.inputs.iter().enumerate() {
+        writer.push(input).unwrap();
+        if i % 50 == 0 {
+            writer.flush().unwrap();
+        }
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn test_writer_matches_serialize() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let replay = DynamicReplay::from_reader(&mut Cursor::new(read_example(name))).unwrap();
+        assert_eq!(write(&replay), serialize(&replay), "{}", name);
+    }
+}
+
+#[test]
+fn test_v1_count_width_growth() {
+    // 20000 inputs need a three byte count.
+    let inputs = (0..20_000).map(|i| jump(i, i % 2 == 0)).collect();
+    let replay = Replay::new(MetaV1::new(240.0, 0), inputs);
+    let bytes = write(&replay);
+    assert_eq!(bytes, serialize(&replay));
+
+    for len in [0, 1, 127, 128, 16_383, 16_384] {
+        let replay = Replay::new(replay.meta.clone(), replay.inputs[..len].to_vec());
+        assert_eq!(write(&replay), serialize(&replay), "{} inputs", len);
+    }
+}
+
+#[test]
+fn test_padded_v1_count() {
+    let inputs = (0..200).map(|i| jump(i, i % 2 == 0)).collect();
+    let replay = Replay::new(MetaV1::new(240.0, 0), inputs);
+
+    let mut writer = InputWriter::new_padded(Cursor::new(Vec::new()), &replay.meta).unwrap();
+    for input in &replay.inputs {
+        writer.push(input).unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+
+    // 200 is `c8 01`, padded with continuation bytes to five.
+    let serialized = serialize(&replay);
+    assert_eq!(
+        bytes[PREFIX_SIZE..PREFIX_SIZE + 5],
+        [0xc8, 0x81, 0x80, 0x80, 0x00]
+    );
+    assert_eq!(bytes[PREFIX_SIZE + 5..], serialized[PREFIX_SIZE + 2..]);
+
+    let loaded = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(loaded.inputs, replay.inputs);
+    let err = Replay::<MetaV1>::deserialize_with_options(
+        &mut Cursor::new(&bytes),
+        &ReadOptions::strict(),
+    )
+    .unwrap_err();
+    assert!(
+        matches!(err.root_cause(), TcmError::OverlongVarint { .. }),
+        "{:?}",
+        err
+    );
+
+    // Other versions don't pad anything.
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), replay.inputs);
+    let mut writer = InputWriter::new_padded(Cursor::new(Vec::new()), &replay.meta).unwrap();
+    for input in &replay.inputs {
+        writer.push(input).unwrap();
+    }
+    assert_eq!(writer.finish().unwrap().into_inner(), serialize(&replay));
+}
+
+#[test]
+fn test_flushed_v1_is_readable() {
+    let mut bytes = Vec::new();
+    let mut writer = InputWriter::new(Cursor::new(&mut bytes), &MetaV1::new(240.0, 0)).unwrap();
+    for frame in 0..200 {
+        writer.push(&jump(frame, frame % 2 == 0)).unwrap();
+    }
+    writer.flush().unwrap();
+    // Simulate a crash before the writer is finished.
+    drop(writer);
+
+    // Only the end of macro marker is missing.
+    let recovered = Replay::<MetaV1>::recover(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(recovered.replay.inputs.len(), 200);
+    assert_eq!(recovered.diagnostics.len(), 1);
+    assert!(matches!(
+        recovered.diagnostics[0].kind,
+        RecoveryDiagnosticKind::Truncated { .. }
+    ));
+}
+
+#[test]
+fn test_trailing_payloads_round_trip() {
+    let tps = InputCommand::new(40, Input::Tps(TpsInput { tps: 480.0 }));
+    let restart = InputCommand::new(
+        50,
+        Input::Restart(RestartInput {
+            restart_type: RestartType::Death,
+            new_seed: Some(0xDEAD_BEEF),
+        }),
+    );
+
+    for last in [tps, restart] {
+        let replay = Replay::new(
+            MetaV2::new(240.0, 0, None),
+            vec![jump(10, true), jump(10, false), jump(20, true), last],
+        );
+
+        let bytes = serialize(&replay);
+        assert_eq!(write(&replay), bytes);
+
+        let loaded = Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(loaded.inputs, replay.inputs);
+    }
+}
+
+#[test]
+fn test_writer_rejects_unrepresentable_input() {
+    let mut writer = InputWriter::new(Cursor::new(Vec::new()), &MetaV1::new(240.0, 0)).unwrap();
+    let tps = InputCommand::new(40, Input::Tps(TpsInput { tps: 480.0 }));
+    assert!(writer.push(&tps).is_err());
+}
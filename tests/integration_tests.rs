@@ -3,7 +3,7 @@ use std::path::Path;
 use tempfile::NamedTempFile;
 
 use tcm::input::Input;
-use tcm::meta::{Meta, MetaV1, MetaV2};
+use tcm::meta::{MetaInfo, MetaV1, MetaV2};
 use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
 
 #[test]
@@ -18,7 +18,7 @@ fn test_v1_round_trip() {
     let replay =
         Replay::<MetaV1>::deserialize(&mut file).expect("Failed to deserialize restartv1.tcm");
 
-    assert_eq!(MetaV1::version(), 1);
+    assert_eq!(MetaV1::VERSION, 1);
 
     let temp_file = NamedTempFile::new().expect("Failed to create temp file");
     let mut writer = File::create(temp_file.path()).expect("Failed to create writer");
@@ -77,7 +77,7 @@ fn test_v2_restart_round_trip() {
     let replay =
         Replay::<MetaV2>::deserialize(&mut file).expect("Failed to deserialize restartv2.tcm");
 
-    assert_eq!(MetaV2::version(), 2);
+    assert_eq!(MetaV2::VERSION, 2);
 
     let temp_file = NamedTempFile::new().expect("Failed to create temp file");
     let mut writer = File::create(temp_file.path()).expect("Failed to create writer");
@@ -131,7 +131,7 @@ fn test_v2_long_round_trip() {
     let replay =
         Replay::<MetaV2>::deserialize(&mut file).expect("Failed to deserialize longv2.tcm");
 
-    assert_eq!(MetaV2::version(), 2);
+    assert_eq!(MetaV2::VERSION, 2);
 
     let temp_file = NamedTempFile::new().expect("Failed to create temp file");
     let mut writer = File::create(temp_file.path()).expect("Failed to create writer");
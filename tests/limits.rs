@@ -0,0 +1,118 @@
+use std::io::Cursor;
+
+use tcm::input::{Input, InputCommand, TpsInput};
+use tcm::meta::{MetaV1, MetaV2};
+use tcm::options::ResourceLimit;
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
+use tcm::{DynamicReplay, ReadLimits, ReadOptions, TcmError};
+
+mod common;
+
+use common::{jump, read_example};
+
+fn read_limited(bytes: &[u8], limits: ReadLimits) -> Result<DynamicReplay, TcmError> {
+    let options = ReadOptions::default().with_limits(limits);
+    DynamicReplay::from_reader_with_options(&mut Cursor::new(bytes), &options)
+}
+
+fn assert_limit(result: Result<DynamicReplay, TcmError>, expected: ResourceLimit) {
+    match result {
+        Err(TcmError::LimitExceeded { limit, .. }) => assert_eq!(limit, expected),
+        Err(e) => panic!("Expected {:?} limit error, got {:?}", expected, e),
+        Ok(_) => panic!("Expected {:?} limit error, got a replay", expected),
+    }
+}
+
+#[test]
+fn test_v1_announced_count_is_limited() {
+    let mut bytes = read_example("restartv1.tcm")[..0x50].to_vec();
+    // Announce u32::MAX inputs and provide none of them.
+    bytes.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+
+    let limits = ReadLimits {
+        max_inputs: Some(1_000_000),
+        ..ReadLimits::default()
+    };
+    assert_limit(read_limited(&bytes, limits), ResourceLimit::Inputs);
+}
+
+#[test]
+fn test_input_and_byte_limits() {
+    for name in ["restartv1.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let full = read_limited(&bytes, ReadLimits::default()).unwrap();
+        let max_frame = full.inputs.iter().map(|i| i.frame).max().unwrap();
+
+        let exact = ReadLimits {
+            max_inputs: Some(full.inputs.len()),
+            max_frame: Some(max_frame),
+            max_tps_changes: Some(usize::MAX),
+            max_bytes: Some(bytes.len() as u64),
+        };
+        assert!(read_limited(&bytes, exact).is_ok(), "{}", name);
+
+        let limits = ReadLimits {
+            max_inputs: Some(full.inputs.len() - 1),
+            ..ReadLimits::default()
+        };
+        assert_limit(read_limited(&bytes, limits), ResourceLimit::Inputs);
+
+        let limits = ReadLimits {
+            max_frame: Some(max_frame - 1),
+            ..ReadLimits::default()
+        };
+        assert_limit(read_limited(&bytes, limits), ResourceLimit::Frame);
+
+        let limits = ReadLimits {
+            max_bytes: Some(bytes.len() as u64 / 2),
+            ..ReadLimits::default()
+        };
+        assert_limit(read_limited(&bytes, limits), ResourceLimit::Bytes);
+    }
+}
+
+#[test]
+fn test_tps_change_limit() {
+    let replay = Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![
+            jump(10, true),
+            InputCommand::new(20, Input::Tps(TpsInput { tps: 480.0 })),
+            InputCommand::new(30, Input::Tps(TpsInput { tps: 240.0 })),
+            jump(40, false),
+        ],
+    );
+    let mut cursor = Cursor::new(Vec::new());
+    replay.serialize(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+
+    let limits = ReadLimits {
+        max_tps_changes: Some(1),
+        ..ReadLimits::default()
+    };
+    assert_limit(read_limited(&bytes, limits), ResourceLimit::TpsChanges);
+
+    let limits = ReadLimits {
+        max_tps_changes: Some(2),
+        ..ReadLimits::default()
+    };
+    assert!(read_limited(&bytes, limits).is_ok());
+}
+
+#[test]
+fn test_typed_reader_limits() {
+    let bytes = read_example("restartv1.tcm");
+    let options = ReadOptions::strict().with_limits(ReadLimits {
+        max_inputs: Some(3),
+        ..ReadLimits::default()
+    });
+    let err =
+        Replay::<MetaV1>::deserialize_with_options(&mut Cursor::new(&bytes), &options).unwrap_err();
+    assert!(matches!(
+        err,
+        TcmError::LimitExceeded {
+            limit: ResourceLimit::Inputs,
+            max: 3
+        }
+    ));
+}
@@ -0,0 +1,167 @@
+use tcm::input::{
+    Input, InputCommand, PlayerButton, PlayerButton::*, RestartInput, RestartType, TpsInput,
+    VanillaInput,
+};
+use tcm::lint::{Lint, LintKind, LintOptions, Severity};
+use tcm::meta::MetaV2;
+use tcm::{DynamicReplay, Replay, ReplayBuilder};
+
+mod common;
+
+use common::read_example;
+
+fn button(frame: u64, button: PlayerButton, push: bool, player2: bool) -> InputCommand {
+    InputCommand::new(
+        frame,
+        Input::Vanilla(VanillaInput {
+            button,
+            push,
+            player2,
+        }),
+    )
+}
+
+fn kinds(lints: &[Lint]) -> Vec<(usize, LintKind)> {
+    lints
+        .iter()
+        .map(|lint| (lint.input_index, lint.kind.clone()))
+        .collect()
+}
+
+#[test]
+fn test_examples_have_no_errors() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let replay = DynamicReplay::from_bytes(&bytes).unwrap();
+        let lints = replay.validate();
+        assert!(
+            lints.iter().all(|lint| lint.severity == Severity::Warning),
+            "{}: {:?}",
+            name,
+            lints
+        );
+        assert!(lints
+            .windows(2)
+            .all(|w| w[0].input_index <= w[1].input_index));
+    }
+}
+
+#[test]
+fn test_clean_replay_has_no_findings() {
+    let replay = ReplayBuilder::v2(240.0)
+        .at(10)
+        .press(Jump)
+        .player2()
+        .hold(Jump, 15..16)
+        .player1()
+        .at(20)
+        .release(Jump)
+        .player2()
+        .at(30)
+        .press(Jump)
+        .release(Jump)
+        .at(40)
+        .restart(RestartType::Death)
+        .tps(480.0)
+        .hold(Jump, 5..8)
+        .build()
+        .unwrap();
+    assert_eq!(replay.validate(), Vec::new());
+}
+
+#[test]
+fn test_button_findings() {
+    // The builder refuses unpaired presses and releases.
+    let restart = Input::Restart(RestartInput {
+        restart_type: RestartType::Restart,
+        new_seed: None,
+    });
+    let inputs = vec![
+        button(5, Jump, false, false),
+        button(10, Jump, true, false),
+        button(12, Jump, true, false),
+        button(20, Jump, false, false),
+        button(25, Jump, true, true),
+        InputCommand::new(30, restart),
+        button(3, Jump, true, false),
+    ];
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+
+    let lints = replay.validate();
+    let (button, player2) = (Jump, false);
+    assert_eq!(
+        kinds(&lints),
+        vec![
+            (0, LintKind::ReleaseWithoutPress { button, player2 }),
+            (2, LintKind::DoublePress { button, player2 }),
+            (6, LintKind::NeverReleased { button, player2 }),
+        ]
+    );
+    assert_eq!(lints[2].frame, 3);
+}
+
+#[test]
+fn test_frame_and_tps_errors() {
+    let inputs = vec![
+        InputCommand::new(100, Input::Tps(TpsInput { tps: 120.0 })),
+        InputCommand::new(50, Input::Tps(TpsInput { tps: 0.0 })),
+        InputCommand::new(60, Input::Tps(TpsInput { tps: f32::NAN })),
+    ];
+    let replay = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+    let lints = replay.validate();
+
+    assert_eq!(lints.len(), 4);
+    assert!(lints.iter().all(|lint| lint.severity == Severity::Error));
+    assert_eq!(
+        kinds(&lints[..3]),
+        vec![
+            (1, LintKind::FrameWentBack { previous: 100 }),
+            (1, LintKind::InvalidTps { tps: 0.0 }),
+            (2, LintKind::FrameWentBack { previous: 100 }),
+        ]
+    );
+    assert!(matches!(lints[3].kind, LintKind::InvalidTps { tps } if tps.is_nan()));
+    assert_eq!(lints[0].frame, 50);
+}
+
+#[test]
+fn test_platformer_buttons() {
+    let classic = ReplayBuilder::v2(240.0)
+        .hold(Jump, 10..20)
+        .at(30)
+        .restart(RestartType::Death)
+        .hold(Jump, 10..20)
+        .hold(Right, 30..40)
+        .build()
+        .unwrap();
+    let lints = classic.validate();
+    assert_eq!(lints.len(), 2);
+    assert!(lints
+        .iter()
+        .all(|lint| matches!(lint.kind, LintKind::PlatformerButton { button: Right, .. })));
+
+    let platformer = LintOptions {
+        platformer: Some(true),
+    };
+    assert!(classic.validate_with(&platformer).is_empty());
+
+    // A stray Left in a single attempt of jumps.
+    let mut builder = ReplayBuilder::v2(240.0);
+    for frame in (0..10).map(|i| i * 100) {
+        builder = builder.hold(Jump, frame..frame + 10);
+    }
+    let stray = builder.hold(Left, 950..960).build().unwrap();
+    let lints = stray.validate();
+    assert_eq!(lints.len(), 2);
+    assert!(lints
+        .iter()
+        .all(|lint| matches!(lint.kind, LintKind::PlatformerButton { button: Left, .. })));
+    assert_eq!((lints[0].frame, lints[1].frame), (950, 960));
+
+    let run = ReplayBuilder::v2(240.0).hold(Left, 10..20).build().unwrap();
+    assert!(run.validate().is_empty());
+    let classic = LintOptions {
+        platformer: Some(false),
+    };
+    assert_eq!(run.validate_with(&classic).len(), 2);
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "mmap")]
+
+use std::io::Cursor;
+
+use tcm::meta::{AnyMeta, MetaInfo, MetaV2};
+use tcm::mmap::MappedReplay;
+use tcm::replay::{Replay, ReplaySerializer};
+use tcm::{DynamicReplay, ReadOptions, TcmError};
+
+const EXAMPLES: [&str; 3] = [
+    "examples/data/restartv1.tcm",
+    "examples/data/restartv2.tcm",
+    "examples/data/longv2.tcm",
+];
+
+#[test]
+fn test_open_matches_reader() {
+    for path in EXAMPLES {
+        let bytes = std::fs::read(path).expect("Failed to read example file");
+        let expected = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+
+        let replay = DynamicReplay::open(path).unwrap();
+        assert_eq!(replay.meta.version(), expected.meta.version());
+        assert_eq!(replay.inputs, expected.inputs, "{}", path);
+    }
+}
+
+#[test]
+fn test_mapped_lazy_inputs() {
+    let path = "examples/data/longv2.tcm";
+    let expected = DynamicReplay::open(path).unwrap();
+
+    let mapped = MappedReplay::open(path).unwrap();
+    let stream = mapped.inputs::<MetaV2>().unwrap();
+    let inputs: Vec<_> = stream.collect::<Result<_, _>>().unwrap();
+    assert_eq!(inputs, expected.inputs);
+
+    // Stopping early only decodes the chunk the input is in.
+    let mut inputs = Vec::new();
+    for _ in 0..10 {
+        inputs.extend(expected.inputs.iter().cloned());
+    }
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let long = Replay::new(MetaV2::new(240.0, 0, None), inputs);
+    long.serialize(&mut file.reopen().unwrap()).unwrap();
+    let mapped = MappedReplay::open(file.path()).unwrap();
+    let mut stream = mapped.inputs::<AnyMeta>().unwrap();
+    let first = stream.next().unwrap().unwrap();
+    assert_eq!(first, expected.inputs[0]);
+    assert!(stream.offset() < mapped.as_bytes().len() as u64 / 2);
+}
+
+#[test]
+fn test_open_written_file() {
+    let replay = Replay::<MetaV2>::open("examples/data/restartv2.tcm").unwrap();
+    let file = tempfile::NamedTempFile::new().unwrap();
+    replay.serialize(&mut file.reopen().unwrap()).unwrap();
+
+    let reopened = Replay::<MetaV2>::open(file.path()).unwrap();
+    assert_eq!(reopened.inputs, replay.inputs);
+}
+
+#[test]
+fn test_open_errors() {
+    let err = DynamicReplay::open("examples/data/missing.tcm")
+        .map(|r| r.inputs)
+        .unwrap_err();
+    assert!(matches!(err, TcmError::Io(_)), "got {:?}", err);
+
+    // An empty file maps fine and is reported like any other short read.
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let err = DynamicReplay::open(file.path())
+        .map(|r| r.inputs)
+        .unwrap_err();
+    let expected = DynamicReplay::from_reader(&mut Cursor::new(Vec::new()))
+        .map(|r| r.inputs)
+        .unwrap_err();
+    assert_eq!(format!("{:?}", err), format!("{:?}", expected));
+
+    let strict = ReadOptions::strict();
+    let mut bytes = std::fs::read(EXAMPLES[0]).unwrap();
+    bytes.push(0);
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), &bytes).unwrap();
+    assert!(DynamicReplay::open(file.path()).is_ok());
+    assert!(matches!(
+        DynamicReplay::open_with_options(file.path(), &strict),
+        Err(TcmError::TrailingData { .. })
+    ));
+}
@@ -0,0 +1,82 @@
+use std::io::{Cursor, Read};
+
+use tcm::meta::{MetaInfo, MetaV1, MetaV2};
+use tcm::replay::{detect_version, Replay, ReplayDeserializer, ReplaySerializer, PREFIX_SIZE};
+use tcm::{DynamicReplay, ReadOptions, TcmError};
+
+mod common;
+
+use common::read_example;
+
+/// A reader that can't seek and hands out at most three bytes per read, like a pipe.
+struct Pipe<'a>(&'a [u8]);
+
+impl Read for Pipe<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.0.len()).min(3);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn test_dynamic_replay_from_pipe() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let expected = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+
+        let replay = DynamicReplay::from_reader(&mut Pipe(&bytes)).unwrap();
+        assert_eq!(replay.inputs, expected.inputs, "{}", name);
+
+        let options = ReadOptions::strict();
+        let replay = DynamicReplay::from_reader_with_options(&mut Pipe(&bytes), &options).unwrap();
+        assert_eq!(replay.meta.version(), expected.meta.version());
+    }
+}
+
+#[test]
+fn test_serialize_to_plain_writer() {
+    for name in ["restartv1.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let replay = DynamicReplay::from_reader(&mut Pipe(&bytes)).unwrap();
+
+        // A Vec<u8> is only Write, not Seek.
+        let mut out = Vec::new();
+        replay.serialize(&mut out).unwrap();
+        assert_eq!(out, bytes, "{}", name);
+    }
+}
+
+#[test]
+fn test_detect_version() {
+    let bytes = read_example("restartv1.tcm");
+    let (version, mut reader) = detect_version(Pipe(&bytes)).unwrap();
+    assert_eq!(version, 1);
+    let replay = Replay::<MetaV1>::deserialize(&mut reader).unwrap();
+    assert!(!replay.inputs.is_empty());
+
+    let bytes = read_example("restartv2.tcm");
+    let (version, mut reader) = detect_version(Pipe(&bytes)).unwrap();
+    assert_eq!(version, 2);
+    let replay = Replay::<MetaV2>::deserialize(&mut reader).unwrap();
+    assert!(!replay.inputs.is_empty());
+}
+
+#[test]
+fn test_detect_version_errors() {
+    let mut bytes = read_example("restartv2.tcm");
+
+    let err = detect_version(Pipe(&bytes[..PREFIX_SIZE - 1]))
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, TcmError::UnexpectedEof { .. }),
+        "got {:?}",
+        err
+    );
+
+    bytes[0] ^= 0xFF;
+    let err = detect_version(Pipe(&bytes)).err().unwrap();
+    assert!(matches!(err, TcmError::InvalidHeader), "got {:?}", err);
+}
@@ -2,7 +2,7 @@ use std::fs::File;
 use std::path::Path;
 
 use tcm::input::Input;
-use tcm::meta::{Meta, MetaV1, MetaV2};
+use tcm::meta::{MetaFormat, MetaInfo, MetaV1, MetaV2};
 use tcm::replay::{Replay, ReplayDeserializer};
 
 #[test]
@@ -17,7 +17,7 @@ fn test_v1_read_only() {
     let replay =
         Replay::<MetaV1>::deserialize(&mut file).expect("Failed to deserialize restartv1.tcm");
 
-    assert_eq!(MetaV1::version(), 1);
+    assert_eq!(MetaV1::VERSION, 1);
     assert!(replay.meta.tps() > 0.0, "TPS should be positive");
     assert!(!replay.meta.uses_dt(), "V1 should not use delta time");
     assert!(
@@ -60,7 +60,7 @@ fn test_v2_restart_read_only() {
     let replay =
         Replay::<MetaV2>::deserialize(&mut file).expect("Failed to deserialize restartv2.tcm");
 
-    assert_eq!(MetaV2::version(), 2);
+    assert_eq!(MetaV2::VERSION, 2);
     assert!(replay.meta.tps() > 0.0, "TPS should be positive");
 
     assert!(!replay.inputs.is_empty(), "File should contain inputs");
@@ -92,7 +92,7 @@ fn test_v2_long_read_only() {
     let replay =
         Replay::<MetaV2>::deserialize(&mut file).expect("Failed to deserialize longv2.tcm");
 
-    assert_eq!(MetaV2::version(), 2);
+    assert_eq!(MetaV2::VERSION, 2);
     assert!(replay.meta.tps() > 0.0, "TPS should be positive");
 
     assert!(!replay.inputs.is_empty(), "File should contain inputs");
@@ -170,7 +170,7 @@ fn test_metadata_parsing() {
         );
 
         let meta_bytes = &file_bytes[16..16 + 0x40];
-        let meta = MetaV1::from_bytes(meta_bytes);
+        let meta = MetaV1::from_bytes(meta_bytes).expect("Failed to parse V1 metadata");
 
         println!(
             "V1 metadata: TPS={}, append_counter={}",
@@ -191,7 +191,7 @@ fn test_metadata_parsing() {
         );
 
         let meta_bytes = &file_bytes[16..16 + 0x40];
-        let meta = MetaV2::from_bytes(meta_bytes);
+        let meta = MetaV2::from_bytes(meta_bytes).expect("Failed to parse V2 metadata");
 
         println!(
             "{} metadata: TPS={}, uses_dt={}, rng_seed={:?}, append_counter={}",
@@ -0,0 +1,101 @@
+use std::io::Cursor;
+
+use tcm::meta::{MetaV1, MetaV2};
+use tcm::recovery::RecoveryDiagnosticKind;
+use tcm::replay::{Replay, ReplayDeserializer};
+use tcm::DynamicReplay;
+
+mod common;
+
+use common::read_example;
+
+#[test]
+fn test_recover_complete_files() {
+    for name in ["restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let full = Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+        let recovered = Replay::<MetaV2>::recover(&mut Cursor::new(&bytes)).unwrap();
+
+        assert!(recovered.is_complete(), "{:?}", recovered.diagnostics);
+        assert_eq!(recovered.stopped_at, bytes.len() as u64);
+        assert_eq!(recovered.replay.inputs, full.inputs);
+    }
+
+    let bytes = read_example("restartv1.tcm");
+    let full = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+    let recovered = Replay::<MetaV1>::recover(&mut Cursor::new(&bytes)).unwrap();
+
+    assert!(recovered.is_complete(), "{:?}", recovered.diagnostics);
+    assert_eq!(recovered.stopped_at, bytes.len() as u64);
+    assert_eq!(recovered.replay.inputs, full.inputs);
+}
+
+#[test]
+fn test_recover_truncated_v2() {
+    let bytes = read_example("longv2.tcm");
+    let full = Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+
+    for len in (0x51..bytes.len()).step_by(97) {
+        let recovered = Replay::<MetaV2>::recover(&mut Cursor::new(&bytes[..len])).unwrap();
+        let inputs = &recovered.replay.inputs;
+
+        assert!(recovered.stopped_at <= len as u64);
+        assert!(inputs.len() <= full.inputs.len());
+        assert_eq!(inputs[..], full.inputs[..inputs.len()]);
+    }
+}
+
+#[test]
+fn test_recover_truncated_v1() {
+    let bytes = read_example("restartv1.tcm");
+    let full = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+
+    let len = bytes.len() / 2;
+    let recovered = Replay::<MetaV1>::recover(&mut Cursor::new(&bytes[..len])).unwrap();
+    let inputs = &recovered.replay.inputs;
+
+    assert!(!inputs.is_empty());
+    assert_eq!(inputs[..], full.inputs[..inputs.len()]);
+    assert!(recovered.diagnostics.iter().any(|d| matches!(
+        d.kind,
+        RecoveryDiagnosticKind::MissingInputs { expected, found }
+            if expected == full.inputs.len() && found == inputs.len()
+    )));
+
+    // Without the end of macro marker every input is still there.
+    let recovered = Replay::<MetaV1>::recover(&mut Cursor::new(&bytes[..bytes.len() - 1])).unwrap();
+    assert_eq!(recovered.replay.inputs, full.inputs);
+    assert!(matches!(
+        recovered.diagnostics[0].kind,
+        RecoveryDiagnosticKind::Truncated { .. }
+    ));
+}
+
+#[test]
+fn test_recover_corrupt_v1() {
+    let mut bytes = read_example("restartv1.tcm");
+    let full = Replay::<MetaV1>::deserialize(&mut Cursor::new(&bytes)).unwrap();
+
+    // Corrupt the input byte of the last entry (just before the end of macro marker).
+    let at = bytes.len() - 2;
+    bytes[at] = 0x07;
+
+    let recovered = DynamicReplay::recover(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(recovered.replay.inputs.len(), full.inputs.len() - 1);
+
+    let first = &recovered.diagnostics[0];
+    assert!(matches!(first.kind, RecoveryDiagnosticKind::Corrupt { .. }));
+    assert_eq!(first.input_index, full.inputs.len() - 1);
+    assert_eq!(recovered.stopped_at, first.offset);
+    assert!(recovered.diagnostics.iter().any(|d| matches!(
+        d.kind,
+        RecoveryDiagnosticKind::SkippedBytes { count } if count == bytes.len() as u64 - first.offset
+    )));
+}
+
+#[test]
+fn test_recover_requires_valid_header() {
+    let mut bytes = read_example("restartv2.tcm");
+    bytes[3] ^= 0xFF;
+    assert!(Replay::<MetaV2>::recover(&mut Cursor::new(&bytes)).is_err());
+}
@@ -0,0 +1,66 @@
+use std::io::Cursor;
+
+use tcm::meta::{MetaFormat, MetaV1, MetaV2};
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
+
+mod common;
+
+use common::read_example;
+
+const META: usize = 0x10;
+
+fn round_trip<M: MetaFormat>(bytes: &[u8]) -> (Replay<M>, Vec<u8>) {
+    let replay = Replay::<M>::deserialize(&mut Cursor::new(bytes)).expect("Failed to deserialize");
+    let mut out = Cursor::new(Vec::new());
+    replay.serialize(&mut out).expect("Failed to serialize");
+    (replay, out.into_inner())
+}
+
+#[test]
+fn test_v1_reserved_bytes_round_trip() {
+    let mut bytes = read_example("restartv1.tcm");
+    bytes[META + 2] = 0xAB;
+    bytes[META + 3] = 0xCD;
+    bytes[META + 0x3F] = 0x42;
+
+    let (replay, written) = round_trip::<MetaV1>(&bytes);
+    assert_eq!(written, bytes);
+
+    let reserved = replay.meta.reserved_bytes();
+    assert_eq!(reserved.len(), 0x40);
+    assert_eq!(&reserved[2..4], &[0xAB, 0xCD]);
+    assert_eq!(reserved[0x3F], 0x42);
+    // Known fields are not part of the reserved view.
+    assert_eq!(reserved[0], 0);
+    assert_eq!(&reserved[4..8], &[0; 4]);
+}
+
+#[test]
+fn test_v2_reserved_bytes_round_trip() {
+    let mut bytes = read_example("restartv2.tcm");
+    bytes[META + 2] |= 0x40;
+    bytes[META + 3] = 0x11;
+    for (i, b) in bytes[META + 0x10..META + 0x40].iter_mut().enumerate() {
+        *b = i as u8;
+    }
+
+    let (replay, written) = round_trip::<MetaV2>(&bytes);
+    assert_eq!(written, bytes);
+
+    let reserved = replay.meta.reserved_bytes();
+    assert_eq!(reserved[3], 0x11);
+    assert_eq!(reserved[0x11], 1);
+    assert_eq!(&reserved[8..16], &[0; 8]);
+}
+
+#[test]
+fn test_new_meta_has_no_reserved_bytes() {
+    assert!(MetaV1::new(240.0, 0)
+        .reserved_bytes()
+        .iter()
+        .all(|&b| b == 0));
+    assert!(MetaV2::new(240.0, 0, Some(5))
+        .reserved_bytes()
+        .iter()
+        .all(|&b| b == 0));
+}
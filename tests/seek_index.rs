@@ -0,0 +1,237 @@
+use std::io::Cursor;
+
+use tcm::index::SeekIndex;
+use tcm::input::{Input, InputCommand, RestartInput, RestartType, TpsInput};
+use tcm::meta::{MetaV1, MetaV2};
+use tcm::options::ResourceLimit;
+use tcm::replay::DeserializerBlob;
+use tcm::replay::{Replay, PREFIX_SIZE};
+use tcm::stream::{DynamicInputStream, InputStream};
+use tcm::writer::InputWriter;
+use tcm::{ReadLimits, ReadOptions, TcmError};
+
+mod common;
+
+use common::{jump, read_example};
+
+/// A replay with several attempts, swift pairs, magic deltas and seeded restarts.
+fn attempts_replay() -> Vec<u8> {
+    let mut inputs = Vec::new();
+    for attempt in 0..6u64 {
+        for i in 0..40u64 {
+            let frame = 10 + i * (50 + attempt * 300);
+            inputs.push(jump(frame, true));
+            inputs.push(jump(frame + (i % 3) * 7, false));
+        }
+        let restart = Input::Restart(RestartInput {
+            restart_type: RestartType::Death,
+            new_seed: (attempt % 2 == 0).then_some(attempt + 1),
+        });
+        inputs.push(InputCommand::new(20_000, restart));
+    }
+    Replay::new(MetaV2::new(240.0, 0, None), inputs)
+        .to_bytes()
+        .unwrap()
+}
+
+/// Pairs every input with the attempt it belongs to.
+fn with_attempts(inputs: &[InputCommand]) -> Vec<(usize, InputCommand)> {
+    let mut attempt = 0;
+    inputs
+        .iter()
+        .map(|input| {
+            let item = (attempt, input.clone());
+            attempt += matches!(input.input, Input::Restart(_)) as usize;
+            item
+        })
+        .collect()
+}
+
+#[test]
+fn test_resume_from_every_checkpoint() {
+    for bytes in [
+        read_example("restartv2.tcm"),
+        read_example("longv2.tcm"),
+        attempts_replay(),
+    ] {
+        let full = Replay::<MetaV2>::from_bytes(&bytes).unwrap();
+        let interval = (full.inputs.len() / 50).max(7);
+        let index = SeekIndex::build(Cursor::new(&bytes), interval).unwrap();
+
+        assert_eq!(index.input_count(), full.inputs.len());
+        assert_eq!(index.stream_len(), bytes.len() as u64);
+        assert_eq!(index.checkpoints()[0].state, DeserializerBlob::Action);
+        assert!(index.checkpoints().len() >= full.inputs.len() / (interval + 1));
+
+        let attempts = with_attempts(&full.inputs);
+        for checkpoint in index.checkpoints() {
+            let stream =
+                DynamicInputStream::resume(Cursor::new(&bytes), &index, checkpoint).unwrap();
+            let inputs: Vec<_> = stream.collect::<Result<_, _>>().unwrap();
+            assert_eq!(inputs, full.inputs[checkpoint.input_index..]);
+
+            if let Some((attempt, _)) = attempts.get(checkpoint.input_index) {
+                assert_eq!(checkpoint.attempt, *attempt);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_checkpoint_lookup() {
+    let bytes = attempts_replay();
+    let full = Replay::<MetaV2>::from_bytes(&bytes).unwrap();
+    let attempts = with_attempts(&full.inputs);
+    let index = SeekIndex::build(Cursor::new(&bytes), 8).unwrap();
+
+    for (attempt, frame) in [
+        (0, 0),
+        (0, 500),
+        (2, 0),
+        (2, 4_000),
+        (3, 9_000),
+        (5, 19_999),
+        (9, 0),
+    ] {
+        let checkpoint = index.checkpoint_for_frame(attempt, frame).unwrap();
+        let inputs: Vec<_> =
+            InputStream::<_, MetaV2>::resume(Cursor::new(&bytes), &index, checkpoint)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+
+        // Every input at or after the target is decoded from the checkpoint.
+        let first = attempts
+            .iter()
+            .position(|(a, input)| (*a, input.frame) >= (attempt, frame))
+            .unwrap_or(attempts.len());
+        assert!(checkpoint.input_index <= first);
+        assert_eq!(
+            inputs[first - checkpoint.input_index..],
+            full.inputs[first..]
+        );
+    }
+
+    for input_index in [0, 1, 7, 8, 9, 100, full.inputs.len() - 1] {
+        let checkpoint = index.checkpoint_for_input(input_index).unwrap();
+        assert!(checkpoint.input_index <= input_index);
+        assert!(input_index - checkpoint.input_index < 2 * index.interval());
+    }
+}
+
+#[test]
+fn test_sidecar_round_trip() {
+    let bytes = read_example("longv2.tcm");
+    let index = SeekIndex::build(Cursor::new(&bytes), 64).unwrap();
+
+    let mut sidecar = Vec::new();
+    index.write_to(&mut sidecar).unwrap();
+    let read = SeekIndex::read_from(&mut Cursor::new(&sidecar)).unwrap();
+    assert_eq!(read, index);
+
+    // Damaged sidecars are errors, never panics.
+    for len in 0..sidecar.len() {
+        assert!(SeekIndex::read_from(&mut Cursor::new(&sidecar[..len])).is_err());
+    }
+    let mut bad = sidecar.clone();
+    bad[0] ^= 0xFF;
+    assert!(SeekIndex::read_from(&mut Cursor::new(&bad)).is_err());
+}
+
+#[test]
+fn test_resume_checks_the_index_against_the_replay() {
+    let bytes = attempts_replay();
+    let index = SeekIndex::build(Cursor::new(&bytes), 16).unwrap();
+    let checkpoint = &index.checkpoints()[3];
+    let resume = |bytes: &[u8], checkpoint| {
+        DynamicInputStream::resume(Cursor::new(bytes), &index, checkpoint).map(|_| ())
+    };
+    let assert_mismatch = |result: Result<(), TcmError>| {
+        assert!(
+            matches!(result, Err(TcmError::IndexMismatch { .. })),
+            "got {:?}",
+            result
+        );
+    };
+    assert!(resume(&bytes, checkpoint).is_ok());
+
+    // The replay has grown since the index was built.
+    let mut file = Cursor::new(bytes.clone());
+    let mut writer = InputWriter::resume(&mut file).unwrap();
+    writer.push(&jump(30_000, true)).unwrap();
+    writer.finish().unwrap();
+    assert_mismatch(resume(file.get_ref(), checkpoint));
+
+    // Same size, other metadata.
+    let mut other = bytes.clone();
+    other[PREFIX_SIZE - 1] ^= 1;
+    assert_mismatch(resume(&other, checkpoint));
+
+    // A checkpoint the index doesn't hold.
+    let mut foreign = *checkpoint;
+    foreign.offset += 1;
+    assert_mismatch(resume(&bytes, &foreign));
+}
+
+#[test]
+fn test_resumed_stream_counts_inputs_before_the_checkpoint() {
+    let bytes = attempts_replay();
+    let index = SeekIndex::build(Cursor::new(&bytes), 16).unwrap();
+    let checkpoint = &index.checkpoints()[3];
+    let limited = |max_inputs| {
+        let limits = ReadLimits {
+            max_inputs: Some(max_inputs),
+            ..ReadLimits::default()
+        };
+        let options = ReadOptions::default().with_limits(limits);
+        DynamicInputStream::resume_with_options(Cursor::new(&bytes), &index, checkpoint, &options)
+    };
+
+    let inputs: Result<Vec<_>, _> = limited(checkpoint.input_index + 5).unwrap().collect();
+    match inputs {
+        Err(TcmError::LimitExceeded { limit, .. }) => assert_eq!(limit, ResourceLimit::Inputs),
+        other => panic!("got {:?}", other),
+    }
+    assert!(matches!(
+        limited(checkpoint.input_index - 1),
+        Err(TcmError::LimitExceeded { .. })
+    ));
+}
+
+#[test]
+fn test_index_edge_cases() {
+    let empty = Replay::<MetaV2>::new_empty(240.0).to_bytes().unwrap();
+    let index = SeekIndex::build(Cursor::new(&empty), 10).unwrap();
+    assert!(index.checkpoints().is_empty());
+    assert_eq!(index.checkpoint_for_frame(0, 0), None);
+
+    let v1 = Replay::new(MetaV1::new(240.0, 0), vec![jump(1, true)])
+        .to_bytes()
+        .unwrap();
+    assert!(matches!(
+        SeekIndex::build(Cursor::new(&v1), 10),
+        Err(TcmError::InvalidInput { .. })
+    ));
+
+    // Resuming a v1 replay from a v2 checkpoint is refused.
+    let bytes = read_example("longv2.tcm");
+    let index = SeekIndex::build(Cursor::new(&bytes), 10).unwrap();
+    assert!(DynamicInputStream::resume(Cursor::new(&v1), &index, &index.checkpoints()[1]).is_err());
+
+    // A TPS value cut short is refused, with the position of its blob given once.
+    let tps = InputCommand::new(10, Input::Tps(TpsInput { tps: 480.0 }));
+    let cut = Replay::new(MetaV2::new(240.0, 0, None), vec![jump(0, true), tps]);
+    let mut cut = cut.to_bytes().unwrap();
+    cut.truncate(cut.len() - 2);
+    match SeekIndex::build(Cursor::new(&cut), 10) {
+        Err(TcmError::Decode { source, state, .. }) => {
+            assert_eq!(state, Some(DeserializerBlob::Tps));
+            assert!(
+                matches!(*source, TcmError::UnexpectedEof { .. }),
+                "got {:?}",
+                source
+            );
+        }
+        other => panic!("got {:?}", other),
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::Cursor;
+
+use tcm::input::{Input, InputCommand, TpsInput};
+use tcm::meta::{MetaV1, MetaV2};
+use tcm::replay::{Replay, ReplayDeserializer, ReplaySerializer};
+use tcm::{DynamicReplay, ReadOptions, TcmError};
+
+mod common;
+
+use common::{jump, read_example};
+
+const META: usize = 0x10;
+
+fn strict_v1(bytes: &[u8]) -> Result<Replay<MetaV1>, TcmError> {
+    Replay::<MetaV1>::deserialize_with_options(&mut Cursor::new(bytes), &ReadOptions::strict())
+}
+
+fn strict_v2(bytes: &[u8]) -> Result<Replay<MetaV2>, TcmError> {
+    Replay::<MetaV2>::deserialize_with_options(&mut Cursor::new(bytes), &ReadOptions::strict())
+}
+
+fn serialize<M: tcm::meta::MetaFormat>(replay: &Replay<M>) -> Vec<u8> {
+    let mut cursor = Cursor::new(Vec::new());
+    replay.serialize(&mut cursor).unwrap();
+    cursor.into_inner()
+}
+
+#[test]
+fn test_examples_are_canonical() {
+    assert!(strict_v1(&read_example("restartv1.tcm")).is_ok());
+    assert!(strict_v2(&read_example("restartv2.tcm")).is_ok());
+    assert!(strict_v2(&read_example("longv2.tcm")).is_ok());
+
+    let bytes = read_example("longv2.tcm");
+    let replay =
+        DynamicReplay::from_reader_with_options(&mut Cursor::new(&bytes), &ReadOptions::strict());
+    assert!(replay.is_ok());
+}
+
+#[test]
+fn test_v1_end_marker() {
+    let bytes = read_example("restartv1.tcm");
+
+    let err = strict_v1(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(
+        matches!(err.root_cause(), TcmError::MissingEndMarker),
+        "got {:?}",
+        err
+    );
+
+    let mut wrong_marker = bytes.clone();
+    *wrong_marker.last_mut().unwrap() = 0x00;
+    let err = strict_v1(&wrong_marker).unwrap_err();
+    assert!(
+        matches!(err.root_cause(), TcmError::MissingEndMarker),
+        "got {:?}",
+        err
+    );
+
+    let mut trailing = bytes.clone();
+    trailing.push(0x00);
+    let err = strict_v1(&trailing).unwrap_err();
+    assert!(
+        matches!(err, TcmError::TrailingData { offset } if offset == bytes.len() as u64),
+        "got {:?}",
+        err
+    );
+
+    // Permissive reads keep accepting all of these.
+    for bytes in [&bytes[..bytes.len() - 1], &wrong_marker[..], &trailing[..]] {
+        assert!(Replay::<MetaV1>::deserialize(&mut Cursor::new(bytes)).is_ok());
+    }
+}
+
+#[test]
+fn test_v2_metadata_flags_and_reserved() {
+    let bytes = read_example("restartv2.tcm");
+
+    let mut flags = bytes.clone();
+    flags[META + 2] |= 0x80;
+    let err = strict_v2(&flags).unwrap_err();
+    assert!(
+        matches!(err, TcmError::UnknownFlags { .. }),
+        "got {:?}",
+        err
+    );
+
+    let mut reserved = bytes.clone();
+    reserved[META + 0x20] = 1;
+    let err = strict_v2(&reserved).unwrap_err();
+    assert!(
+        matches!(err, TcmError::NonZeroReserved { offset: 0x20 }),
+        "got {:?}",
+        err
+    );
+
+    assert!(Replay::<MetaV2>::deserialize(&mut Cursor::new(&flags)).is_ok());
+    assert!(Replay::<MetaV2>::deserialize(&mut Cursor::new(&reserved)).is_ok());
+}
+
+#[test]
+fn test_v1_reserved() {
+    let mut bytes = read_example("restartv1.tcm");
+    bytes[META + 3] = 1;
+    let err = strict_v1(&bytes).unwrap_err();
+    assert!(
+        matches!(err, TcmError::NonZeroReserved { offset: 3 }),
+        "got {:?}",
+        err
+    );
+}
+
+#[test]
+fn test_invalid_tps() {
+    for tps in [f32::NAN, 0.0, -240.0, f32::INFINITY] {
+        let bytes = serialize(&Replay::new(MetaV1::new(tps, 0), vec![jump(1, true)]));
+        let err = strict_v1(&bytes).unwrap_err();
+        assert!(matches!(err, TcmError::InvalidTps { .. }), "got {:?}", err);
+    }
+
+    let replay = Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![
+            jump(1, true),
+            InputCommand::new(5, Input::Tps(TpsInput { tps: 0.0 })),
+            jump(9, false),
+        ],
+    );
+    let bytes = serialize(&replay);
+    let err = strict_v2(&bytes).unwrap_err();
+    assert!(matches!(err, TcmError::InvalidTps { .. }), "got {:?}", err);
+    assert!(Replay::<MetaV2>::deserialize(&mut Cursor::new(&bytes)).is_ok());
+}
+
+#[test]
+fn test_v2_truncated_delta() {
+    let replay = Replay::new(
+        MetaV2::new(240.0, 0, None),
+        vec![jump(1, true), jump(1000, false)],
+    );
+    let bytes = serialize(&replay);
+    // Drop the final action byte and half of the two byte delta before it.
+    let truncated = &bytes[..bytes.len() - 2];
+
+    let err = strict_v2(truncated).unwrap_err();
+    assert!(
+        matches!(err.root_cause(), TcmError::UnexpectedEof { .. }),
+        "got {:?}",
+        err
+    );
+    assert!(Replay::<MetaV2>::deserialize(&mut Cursor::new(truncated)).is_ok());
+}
+
+#[test]
+fn test_overlong_varints() {
+    let inputs = vec![jump(5, true), jump(300, false)];
+    let v1 = serialize(&Replay::new(MetaV1::new(240.0, 0), inputs.clone()));
+    let v2 = serialize(&Replay::new(MetaV2::new(240.0, 0, None), inputs.clone()));
+    let start = 0x50;
+    assert_eq!(v1[start..start + 2], [0x02, 0x05]);
+    assert_eq!(v2[start], 0x05);
+
+    let splice = |bytes: &[u8], at: usize, len: usize, with: &[u8]| {
+        let mut bytes = bytes.to_vec();
+        bytes.splice(at..at + len, with.iter().copied());
+        bytes
+    };
+    let cases = [
+        ("v1 input count", splice(&v1, start, 1, &[0x82, 0x00])),
+        (
+            "v1 input frame",
+            splice(&v1, start + 1, 1, &[0x85, 0x80, 0x00]),
+        ),
+        ("v2 initial frame", splice(&v2, start, 1, &[0x85, 0x00])),
+    ];
+    for (context, bytes) in cases {
+        let strict = DynamicReplay::from_reader_with_options(
+            &mut Cursor::new(&bytes),
+            &ReadOptions::strict(),
+        )
+        .map(|replay| replay.inputs);
+        assert!(
+            matches!(
+                strict.as_ref().map_err(TcmError::root_cause),
+                Err(TcmError::OverlongVarint { context: c }) if c == context
+            ),
+            "{}: got {:?}",
+            context,
+            strict
+        );
+        let from_bytes = DynamicReplay::from_bytes_with_options(&bytes, &ReadOptions::strict());
+        assert!(from_bytes.is_err(), "{}", context);
+
+        let replay = DynamicReplay::from_reader(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(replay.inputs, inputs, "{}", context);
+    }
+}
@@ -0,0 +1,154 @@
+use tcm::input::{PlayerButton::Jump, RestartType};
+use tcm::meta::{MetaFormat, MetaInfo, MetaV2};
+use tcm::{DynamicReplay, Replay, ReplayBuilder};
+
+mod common;
+
+use common::read_example;
+
+/// v2 metadata storing a delta time instead of a TPS value.
+fn dt_meta(dt: f32) -> MetaV2 {
+    let mut bytes = vec![0u8; MetaV2::size()];
+    bytes[0] = MetaV2::VERSION;
+    bytes[4..8].copy_from_slice(&dt.to_le_bytes());
+    let meta = MetaV2::from_bytes(&bytes).unwrap();
+    assert!(meta.uses_dt());
+    meta
+}
+
+#[test]
+fn test_timeline_of_examples() {
+    for name in ["restartv1.tcm", "restartv2.tcm", "longv2.tcm"] {
+        let bytes = read_example(name);
+        let replay = DynamicReplay::from_bytes(&bytes).unwrap();
+        let timeline = replay.timeline();
+        assert_eq!(timeline.entries().len(), replay.inputs.len());
+        assert_eq!(timeline.attempts(), replay.attempts().len());
+
+        for (entry, input) in timeline.entries().iter().zip(&replay.inputs) {
+            assert_eq!(
+                timeline.locate(entry.tick),
+                Some((entry.attempt, input.frame))
+            );
+            assert_eq!(
+                timeline.tick_at(entry.attempt, input.frame),
+                Some(entry.tick)
+            );
+            assert_eq!(timeline.seconds_at(entry.tick), entry.seconds);
+            assert_eq!(timeline.tick_at_seconds(entry.seconds), entry.tick);
+        }
+        assert!(timeline
+            .entries()
+            .windows(2)
+            .all(|w| w[0].tick <= w[1].tick && w[0].seconds <= w[1].seconds));
+    }
+}
+
+#[test]
+fn test_tps_changes_are_integrated() {
+    let replay = ReplayBuilder::v2(240.0)
+        .at(240)
+        .tps(480.0)
+        .at(480)
+        .press(Jump)
+        .at(600)
+        .restart(RestartType::Death)
+        .at(480)
+        .press(Jump)
+        .build()
+        .unwrap();
+    let timeline = replay.timeline();
+    let seconds: Vec<_> = timeline.entries().iter().map(|e| e.seconds).collect();
+    assert_eq!(seconds, [1.0, 1.5, 1.75, 1.5 + 601.0 / 480.0]);
+    assert_eq!(timeline.entries()[3].tick, 601 + 480);
+
+    assert_eq!(timeline.tick_at(0, 600), Some(600));
+    assert_eq!(timeline.tick_at(0, 601), None);
+    assert_eq!(timeline.tick_at(1, 10_000), Some(601 + 10_000));
+    assert_eq!(timeline.tick_at(2, 0), None);
+    assert_eq!(timeline.locate(601), Some((1, 0)));
+
+    assert_eq!(timeline.tick_at_seconds(-1.0), 0);
+    assert_eq!(timeline.tick_at_seconds(1.0), 240);
+    assert_eq!(timeline.tick_at_seconds(1.0 - 1e-9), 239);
+    assert_eq!(timeline.tick_at_seconds(1.5 + 1.0 / 960.0), 480);
+}
+
+#[test]
+fn test_delta_time_has_no_drift() {
+    let dt = 1.0 / 360.0;
+    let replay = ReplayBuilder::new(dt_meta(dt))
+        .at(10_000_000)
+        .press(Jump)
+        .build()
+        .unwrap();
+    let timeline = replay.timeline();
+
+    let press = timeline.entries()[0];
+    assert_eq!(press.seconds, 10_000_000.0 * dt as f64);
+    for tick in (0..50_000_000).step_by(999_983) {
+        assert_eq!(timeline.tick_at_seconds(timeline.seconds_at(tick)), tick);
+    }
+
+    let empty = Replay::new(dt_meta(dt), Vec::new()).timeline();
+    assert_eq!(empty.locate(0), None);
+    assert_eq!(empty.seconds_at(360), 360.0 * dt as f64);
+}
+
+#[test]
+fn test_frame_and_seconds_conversion() {
+    let dt = 1.0 / 360.0;
+    let replay = ReplayBuilder::new(dt_meta(dt))
+        .at(3_000_000)
+        .press(Jump)
+        .at(3_000_001)
+        .restart(RestartType::Death)
+        .at(360)
+        .tps(240.0)
+        .at(480)
+        .restart(RestartType::Restart)
+        .at(120)
+        .press(Jump)
+        .build()
+        .unwrap();
+
+    // Attempts are measured from their own start, without error carried over from
+    // the attempts before.
+    assert_eq!(
+        replay.frame_to_seconds(0, 2_999_999),
+        Some(2_999_999.0 * dt as f64)
+    );
+    assert_eq!(replay.frame_to_seconds(1, 360), Some(360.0 * dt as f64));
+    assert_eq!(
+        replay.frame_to_seconds(1, 480),
+        Some(360.0 * dt as f64 + 0.5)
+    );
+    assert_eq!(replay.frame_to_seconds(2, 120), Some(0.5));
+    assert_eq!(replay.frame_to_seconds(0, 3_000_002), None);
+    assert_eq!(replay.frame_to_seconds(3, 0), None);
+
+    let timeline = replay.timeline();
+    for (attempt, frames) in [(0, 3_000_002), (1, 481), (2, 1_000)] {
+        for frame in (0..frames).step_by(7) {
+            let seconds = timeline.frame_to_seconds(attempt, frame).unwrap();
+            assert_eq!(timeline.seconds_to_frame(attempt, seconds), Some(frame));
+        }
+    }
+    assert_eq!(replay.seconds_to_frame(1, 0.0), Some(0));
+    for seconds in [-1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        assert_eq!(replay.seconds_to_frame(1, seconds), None, "{}", seconds);
+        assert_eq!(replay.seconds_to_frame(2, seconds), None, "{}", seconds);
+    }
+    assert_eq!(replay.seconds_to_frame(1, 180.0 * dt as f64), Some(180));
+    assert_eq!(
+        replay.seconds_to_frame(1, 180.0 * dt as f64 - 1e-9),
+        Some(179)
+    );
+    assert_eq!(
+        replay.seconds_to_frame(1, 360.0 * dt as f64 + 0.5),
+        Some(480)
+    );
+    assert_eq!(replay.seconds_to_frame(1, 10.0), None);
+    assert_eq!(replay.seconds_to_frame(2, 10.0), Some(2_400));
+    assert_eq!(replay.seconds_to_frame(3, 0.0), None);
+}